@@ -30,7 +30,7 @@ impl<D: Debugger> Command<D> for BreakpointCommand {
         "Commands for operating on breakpoints."
     }
 
-    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Set { name } => {
@@ -13,7 +13,7 @@ pub trait Command<D: Debugger> {
     fn description(&self) -> &'static str {
         "No description yet"
     }
-    fn run(&self, debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()>;
+    fn run(&self, debugger: &mut D, context: &mut CommandContext, args: Vec<&str>) -> Result<()>;
 }
 
 pub trait AliasCommand {
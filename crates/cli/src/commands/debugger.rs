@@ -1,5 +1,5 @@
 use anyhow::Result;
-use wasminspect_vm::{Instruction, ModuleIndex, Signal, Store, WasmValue};
+use wasminspect_vm::{ExecutionStats, Instruction, ModuleIndex, Store, WasmValue};
 
 pub enum Breakpoint {
     Function { name: String },
@@ -8,6 +8,25 @@ pub enum Breakpoint {
 pub enum RunResult {
     Finish(Vec<WasmValue>),
     Breakpoint,
+    Interrupted,
+}
+
+pub enum Signal {
+    Next,
+    Breakpoint,
+    /// The process was paused by an interrupt request or ran out of fuel
+    Interrupted,
+    End,
+}
+
+impl From<wasminspect_vm::Signal> for Signal {
+    fn from(signal: wasminspect_vm::Signal) -> Self {
+        match signal {
+            wasminspect_vm::Signal::Next => Self::Next,
+            wasminspect_vm::Signal::Breakpoint => Self::Breakpoint,
+            wasminspect_vm::Signal::End => Self::End,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -17,22 +36,87 @@ pub enum StepStyle {
     StepOut,
 }
 
+#[derive(Clone, Default)]
+pub struct ProcessStdio {
+    pub stdin: Option<String>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    /// Capture stdout and stderr which are not redirected to files
+    pub capture: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum TraceStyle {
+    Calls,
+    Insts,
+}
+
+pub struct TraceConfig {
+    pub style: TraceStyle,
+    /// Trace only functions whose name contains the pattern
+    pub filter: Option<String>,
+    /// Write the trace to the file instead of the standard output
+    pub file: Option<String>,
+}
+
 pub struct FunctionFrame {
     pub module_index: ModuleIndex,
-    pub argument_count: usize,
+    pub return_count: usize,
+    /// Index of the first value pushed by the frame in `Debugger::stack_values`
+    pub value_stack_base: usize,
 }
 
 pub trait Debugger {
     fn run(&mut self, name: Option<String>) -> Result<RunResult>;
+    fn set_stdio(&mut self, stdio: ProcessStdio);
+    fn captured_output(&self) -> Result<Vec<u8>>;
     fn is_running(&self) -> bool;
     fn frame(&self) -> Vec<String>;
+    /// Code offsets where each frame in `frame` is, outermost first.
+    /// Callers are at their call instructions.
+    fn frame_code_offsets(&self) -> Vec<Option<usize>>;
     fn current_frame(&self) -> Option<FunctionFrame>;
     fn locals(&self) -> Vec<WasmValue>;
+    fn set_local(&mut self, index: usize, value: WasmValue) -> Result<()>;
+    fn globals(&self) -> Vec<WasmValue>;
+    fn set_global(&mut self, index: usize, value: WasmValue) -> Result<()>;
     fn memory(&self) -> Result<Vec<u8>>;
+    /// Call `f` with the linear memory of the current module without copying it
+    fn with_memory<T, F: FnOnce(&[u8]) -> T>(&self, f: F) -> Result<T>;
+    fn write_memory(&mut self, address: usize, bytes: &[u8]) -> Result<()>;
     fn store(&self) -> &Store;
     fn set_breakpoint(&mut self, breakpoint: Breakpoint);
-    fn stack_values(&self) -> Vec<String>;
+    /// Stop the process when `size` bytes at `address` in the current memory are modified,
+    /// both forward and in reverse
+    fn set_watchpoint(&mut self, address: usize, size: usize) -> Result<()>;
+    fn stack_values(&self) -> Vec<WasmValue>;
+    fn set_stack_value(&mut self, index: usize, value: WasmValue) -> Result<()>;
     fn instructions(&self) -> Result<(&[Instruction], usize)>;
     fn step(&self, style: StepStyle) -> Result<Signal>;
+    /// Revert recorded instructions in the opposite direction of `step`.
+    /// Returns `Signal::End` when the beginning of the recorded history is reached.
+    fn step_back(&self, style: StepStyle) -> Result<Signal>;
+    /// Code offset of the instruction in the current frame to which `step_back(StepInstOver)` moves
+    fn previous_inst_offset(&self) -> Option<usize>;
+    /// Return from the current function with values parsed as its result types
+    fn force_return(&mut self, values: Vec<String>) -> Result<Signal>;
+    /// Move the program counter to the instruction in the current function
+    fn jump(&mut self, inst_index: usize) -> Result<()>;
     fn process(&self) -> Result<Signal>;
+    /// Limit the number of instructions executed by `process`. `None` removes the limit.
+    fn set_fuel(&self, fuel: Option<u64>) -> Result<()>;
+    /// The number of instructions `process` can execute before it stops
+    fn fuel(&self) -> Option<u64>;
+    /// Write the state of the process to the file as a wasm core dump
+    fn save_core(&self, path: &str) -> Result<()>;
+    /// Statistics of the current process, or of the last run if it finished
+    fn stats(&self) -> Option<ExecutionStats>;
+    fn start_trace(&mut self, config: TraceConfig) -> Result<()>;
+    fn stop_trace(&mut self) -> Result<()>;
+    /// Save the current state of the process with the name
+    fn save_checkpoint(&mut self, name: String) -> Result<()>;
+    /// Restore the state of the process saved with the name
+    fn restore_checkpoint(&mut self, name: &str) -> Result<()>;
+    /// Revert instructions until a breakpoint or the beginning of the recorded history
+    fn reverse_process(&self) -> Result<Signal>;
 }
@@ -20,7 +20,7 @@ impl<D: Debugger> Command<D> for DisassembleCommand {
         "Disassemble instructions in the current function."
     }
 
-    fn run(&self, debugger: &mut D, _context: &CommandContext, _args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, _args: Vec<&str>) -> Result<()> {
         display_asm(debugger)
     }
 }
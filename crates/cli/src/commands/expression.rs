@@ -1,8 +1,7 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
+use super::subroutine::{FrameState, StateChange};
 use anyhow::{anyhow, Result};
-use std::convert::TryInto;
-use wasminspect_vm::WasmValue;
 
 pub struct ExpressionCommand {}
 
@@ -14,9 +13,14 @@ impl ExpressionCommand {
 
 use structopt::StructOpt;
 #[derive(StructOpt)]
+#[structopt(
+    setting = structopt::clap::AppSettings::TrailingVarArg,
+    setting = structopt::clap::AppSettings::AllowLeadingHyphen
+)]
 struct Opts {
-    #[structopt(name = "SYMBOL")]
-    symbol: String,
+    /// C-like expression, e.g. `p->next->value`, `arr[i] + 1`, `(long)x` or `$local0`
+    #[structopt(name = "EXPRESSION", required = true)]
+    expression: Vec<String>,
 }
 
 impl<D: Debugger> Command<D> for ExpressionCommand {
@@ -25,32 +29,60 @@ impl<D: Debugger> Command<D> for ExpressionCommand {
     }
 
     fn description(&self) -> &'static str {
-        "Evaluate an expression on the process (only support variable name now)."
+        "Evaluate a C-like expression on the current frame."
     }
 
-    fn run(&self, debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
-        let (insts, next_index) = debugger.instructions()?;
-        let current_index = if next_index == 0 { 0 } else { next_index - 1 };
-        let current_inst = insts[current_index].clone();
-        let argument_count = debugger
-            .current_frame()
-            .ok_or(anyhow!("function frame not found"))?
-            .argument_count;
-        let locals = debugger.locals();
-        let rbp = match locals
-            .get(argument_count + 2)
-            .ok_or(anyhow!("failed to get rbp"))?
-        {
-            WasmValue::I32(v) => v,
-            x => return Err(anyhow!("invalid type rbp: '{:?}'", x)),
-        };
-        context.subroutine.display_variable(
-            current_inst.offset,
-            TryInto::<u32>::try_into(*rbp)?,
-            &debugger.memory()?,
-            opts.symbol,
-        )?;
+        let output = evaluate_expression(debugger, context, &opts.expression.join(" "))?;
+        println!("{}", output);
         Ok(())
     }
 }
+
+/// Code offset of the instruction to be executed next, which decides the scope of variables
+pub fn next_code_offset<D: Debugger>(debugger: &D) -> Result<usize> {
+    let (insts, next_index) = debugger.instructions()?;
+    insts
+        .get(next_index)
+        .or_else(|| insts.last())
+        .map(|inst| inst.offset)
+        .ok_or(anyhow!("No instruction in the current function"))
+}
+
+/// Evaluates the expression on the current frame, applies its side effects and returns the formatted result
+pub fn evaluate_expression<D: Debugger>(
+    debugger: &mut D,
+    context: &CommandContext,
+    expression: &str,
+) -> Result<String> {
+    let code_offset = next_code_offset(debugger)?;
+    let locals = debugger.locals();
+    let stack_base = debugger
+        .current_frame()
+        .map(|frame| frame.value_stack_base)
+        .unwrap_or(0);
+    let stack = debugger.stack_values().split_off(stack_base);
+    let globals = debugger.globals();
+    let result = debugger.with_memory(|memory| {
+        let frame = FrameState {
+            code_offset,
+            locals: &locals,
+            globals: &globals,
+            stack: &stack,
+            memory,
+        };
+        context.subroutine.evaluate_expression(&frame, expression)
+    })??;
+    for change in result.changes {
+        match change {
+            StateChange::Memory { address, bytes } => debugger.write_memory(address, &bytes)?,
+            StateChange::Local { index, value } => debugger.set_local(index, value)?,
+            StateChange::Global { index, value } => debugger.set_global(index, value)?,
+            StateChange::Stack { index, value } => {
+                debugger.set_stack_value(stack_base + index, value)?
+            }
+        }
+    }
+    Ok(result.output)
+}
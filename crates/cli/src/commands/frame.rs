@@ -1,5 +1,6 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
+use super::expression::{evaluate_expression, next_code_offset};
 use anyhow::Result;
 
 use structopt::StructOpt;
@@ -14,6 +15,7 @@ impl FrameCommand {
 
 #[derive(StructOpt)]
 enum Opts {
+    /// Print the values of the variables in the current frame
     #[structopt(name = "variable")]
     Variable,
 }
@@ -27,16 +29,16 @@ impl<D: Debugger> Command<D> for FrameCommand {
         "Commands for selecting current stack frame."
     }
 
-    fn run(&self, debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Variable => {
-                let (insts, next_index) = debugger.instructions()?;
-                let current_index = if next_index == 0 { 0 } else { next_index - 1 };
-                let current_inst = insts[current_index].clone();
-                let variable_names = context.subroutine.variable_name_list(current_inst.offset)?;
+                let code_offset = next_code_offset(debugger)?;
+                let variable_names = context.subroutine.variable_name_list(code_offset)?;
                 for variable in variable_names {
-                    println!("{}: {}", variable.name, variable.type_name);
+                    let value = evaluate_expression(debugger, context, &variable.name)
+                        .unwrap_or_else(|err| format!("<<{}>>", err));
+                    println!("{}: {} = {}", variable.name, variable.type_name, value);
                 }
                 Ok(())
             }
@@ -1,5 +1,6 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
+use super::value::parse_value_like;
 use anyhow::{anyhow, Result};
 
 use structopt::StructOpt;
@@ -19,6 +20,14 @@ enum Opts {
         #[structopt(name = "INDEX")]
         index: usize,
     },
+    /// Overwrite a mutable global with a value of the same type
+    #[structopt(name = "write")]
+    Write {
+        #[structopt(name = "INDEX")]
+        index: usize,
+        #[structopt(name = "VALUE", allow_hyphen_values = true)]
+        value: String,
+    },
 }
 
 impl<D: Debugger> Command<D> for GlobalCommand {
@@ -30,7 +39,7 @@ impl<D: Debugger> Command<D> for GlobalCommand {
         "Commands for operating globals."
     }
 
-    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         use wasminspect_vm::*;
         match opts {
@@ -44,6 +53,14 @@ impl<D: Debugger> Command<D> for GlobalCommand {
                 println!("{:?}", global.borrow().value());
                 Ok(())
             }
+            Opts::Write { index, value } => {
+                let globals = debugger.globals();
+                let original = globals
+                    .get(index)
+                    .ok_or(anyhow!("Global {} is out of range", index))?;
+                let value = parse_value_like(original, &value)?;
+                debugger.set_global(index, value)
+            }
         }
     }
 }
@@ -20,7 +20,7 @@ impl<D: Debugger> Command<D> for ListCommand {
         "List relevant source code."
     }
 
-    fn run(&self, debugger: &mut D, context: &CommandContext, _args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, context: &mut CommandContext, _args: Vec<&str>) -> Result<()> {
         let line_info = next_line_info(debugger, &context.sourcemap)?;
         display_source(line_info)
     }
@@ -1,6 +1,7 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
-use anyhow::Result;
+use super::value::parse_value_like;
+use anyhow::{anyhow, Result};
 
 use structopt::StructOpt;
 
@@ -19,6 +20,14 @@ enum Opts {
         #[structopt(name = "INDEX")]
         index: Option<usize>,
     },
+    /// Overwrite a local with a value of the same type
+    #[structopt(name = "write")]
+    Write {
+        #[structopt(name = "INDEX")]
+        index: usize,
+        #[structopt(name = "VALUE", allow_hyphen_values = true)]
+        value: String,
+    },
 }
 
 impl<D: Debugger> Command<D> for LocalCommand {
@@ -30,7 +39,7 @@ impl<D: Debugger> Command<D> for LocalCommand {
         "Commands for operating locals."
     }
 
-    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Read { index: None } => {
@@ -41,6 +50,14 @@ impl<D: Debugger> Command<D> for LocalCommand {
             Opts::Read { index: Some(index) } => {
                 println!("{:?}", debugger.locals()[index]);
             }
+            Opts::Write { index, value } => {
+                let locals = debugger.locals();
+                let original = locals
+                    .get(index)
+                    .ok_or(anyhow!("Local {} is out of range", index))?;
+                let value = parse_value_like(original, &value)?;
+                debugger.set_local(index, value)?;
+            }
         }
         Ok(())
     }
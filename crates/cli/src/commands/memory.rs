@@ -1,6 +1,7 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
-use anyhow::Result;
+use super::value::{parse_integer, parse_value, value_bytes};
+use anyhow::{anyhow, Result};
 
 use structopt::StructOpt;
 
@@ -21,6 +22,17 @@ enum Opts {
         #[structopt(short, long, default_value = "32")]
         count: u32,
     },
+    /// Write bytes, or values of the given type, to the linear memory
+    #[structopt(name = "write")]
+    Write {
+        #[structopt(name = "ADDRESS")]
+        address: String,
+        #[structopt(name = "VALUE", required = true, allow_hyphen_values = true)]
+        values: Vec<String>,
+        /// Value type (i32, i64, f32 or f64). Each VALUE is written as a byte if not specified
+        #[structopt(short, long = "type")]
+        ty: Option<String>,
+    },
 }
 
 impl<D: Debugger> Command<D> for MemoryCommand {
@@ -31,16 +43,11 @@ impl<D: Debugger> Command<D> for MemoryCommand {
     fn description(&self) -> &'static str {
         "Commands for operating on memory."
     }
-    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Read { address, count } => {
-                let address = if address.starts_with("0x") {
-                    let raw = address.trim_start_matches("0x");
-                    i64::from_str_radix(raw, 16)?
-                } else {
-                    i64::from_str_radix(&address, 10)?
-                };
+                let address = parse_integer(&address)?;
                 let memory = debugger.memory()?;
 
                 let begin = address as usize;
@@ -57,6 +64,27 @@ impl<D: Debugger> Command<D> for MemoryCommand {
                 }
                 Ok(())
             }
+            Opts::Write {
+                address,
+                values,
+                ty,
+            } => {
+                let address = parse_integer(&address)?;
+                let mut bytes = Vec::new();
+                for value in values {
+                    match ty {
+                        Some(ref ty) => bytes.append(&mut value_bytes(&parse_value(ty, &value)?)),
+                        None => {
+                            let byte = parse_integer(&value)?;
+                            if !(0..=0xff).contains(&byte) {
+                                return Err(anyhow!("'{}' is out of range of byte", value));
+                            }
+                            bytes.push(byte as u8);
+                        }
+                    }
+                }
+                debugger.write_memory(address as usize, &bytes)
+            }
         }
     }
 }
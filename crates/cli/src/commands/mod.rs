@@ -2,6 +2,7 @@ pub mod command;
 pub mod debugger;
 pub mod sourcemap;
 pub mod subroutine;
+pub mod value;
 
 // commands
 pub mod backtrace;
@@ -17,4 +18,7 @@ pub mod process;
 pub mod run;
 pub mod settings;
 pub mod stack;
+pub mod target;
 pub mod thread;
+pub mod trace;
+pub mod watchpoint;
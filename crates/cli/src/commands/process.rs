@@ -1,7 +1,10 @@
 use super::command::{Command, CommandContext};
-use super::debugger::Debugger;
-use anyhow::Result;
-use wasminspect_vm::Signal;
+use super::debugger::{Debugger, ProcessStdio, Signal};
+use super::run::start_process;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use wasminspect_vm::{ExecutableFuncAddr, ExecutionStats, FunctionInstance};
 
 use structopt::StructOpt;
 
@@ -16,7 +19,67 @@ impl ProcessCommand {
 #[derive(StructOpt)]
 enum Opts {
     #[structopt(name = "continue")]
-    Continue,
+    Continue {
+        /// Stop after executing the number of instructions
+        #[structopt(long)]
+        max_insts: Option<u64>,
+    },
+    /// Run backward until a breakpoint or the beginning of the recorded history
+    #[structopt(name = "reverse-continue")]
+    ReverseContinue,
+    /// Launch the executable with redirected standard streams
+    #[structopt(name = "launch")]
+    Launch {
+        #[structopt(name = "FUNCTION NAME")]
+        name: Option<String>,
+        /// Read the standard input of the process from the given file
+        #[structopt(long)]
+        stdin: Option<String>,
+        /// Write the standard output of the process to the given file
+        #[structopt(long)]
+        stdout: Option<String>,
+        /// Write the standard error of the process to the given file
+        #[structopt(long)]
+        stderr: Option<String>,
+        /// Capture the output of the process to show it by `process output`
+        #[structopt(long)]
+        capture: bool,
+    },
+    /// Show the captured output of the process
+    #[structopt(name = "output")]
+    Output,
+    /// Write the state of the process to the file as a wasm core dump
+    #[structopt(name = "save-core")]
+    SaveCore {
+        #[structopt(name = "FILE")]
+        path: String,
+    },
+    /// Show instruction, call and memory statistics of the current or last run
+    #[structopt(name = "stats")]
+    Stats {
+        /// The number of functions and imports shown
+        #[structopt(long, default_value = "10")]
+        top: usize,
+    },
+    /// Save or restore the state of the process
+    #[structopt(name = "checkpoint")]
+    Checkpoint(CheckpointOpts),
+}
+
+#[derive(StructOpt)]
+enum CheckpointOpts {
+    /// Save the current state of the process with the name
+    #[structopt(name = "save")]
+    Save {
+        #[structopt(name = "NAME")]
+        name: String,
+    },
+    /// Restore the state of the process saved with the name
+    #[structopt(name = "restore")]
+    Restore {
+        #[structopt(name = "NAME")]
+        name: String,
+    },
 }
 
 impl<D: Debugger> Command<D> for ProcessCommand {
@@ -28,17 +91,116 @@ impl<D: Debugger> Command<D> for ProcessCommand {
         "Commands for interacting with processes."
     }
 
-    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
-            Opts::Continue => match debugger.process()? {
+            Opts::Continue { max_insts } => {
+                debugger.set_fuel(max_insts)?;
+                let result = debugger.process();
+                let out_of_fuel = debugger.fuel() == Some(0);
+                debugger.set_fuel(None)?;
+                match result? {
+                    Signal::Next => unreachable!(),
+                    Signal::End => {}
+                    Signal::Breakpoint => {
+                        println!("Hit breakpoit");
+                    }
+                    Signal::Interrupted if out_of_fuel => {
+                        println!(
+                            "Stopped after executing {} instructions",
+                            max_insts.unwrap_or(0)
+                        );
+                    }
+                    Signal::Interrupted => {
+                        println!("Process interrupted");
+                    }
+                }
+            }
+            Opts::ReverseContinue => match debugger.reverse_process()? {
                 Signal::Next => unreachable!(),
-                Signal::End => {}
+                Signal::End => {
+                    println!("Reached the beginning of the recorded history");
+                }
                 Signal::Breakpoint => {
                     println!("Hit breakpoit");
                 }
+                Signal::Interrupted => {
+                    println!("Process interrupted");
+                }
             },
+            Opts::Launch {
+                name,
+                stdin,
+                stdout,
+                stderr,
+                capture,
+            } => {
+                debugger.set_stdio(ProcessStdio {
+                    stdin,
+                    stdout,
+                    stderr,
+                    capture,
+                });
+                start_process(debugger, name)?;
+            }
+            Opts::Output => {
+                let output = debugger.captured_output()?;
+                std::io::stdout().write_all(&output)?;
+            }
+            Opts::SaveCore { path } => {
+                debugger.save_core(&path)?;
+            }
+            Opts::Stats { top } => {
+                let stats = debugger.stats().ok_or(anyhow!("No statistics recorded"))?;
+                print_stats(debugger, &stats, top);
+            }
+            Opts::Checkpoint(CheckpointOpts::Save { name }) => {
+                debugger.save_checkpoint(name)?;
+            }
+            Opts::Checkpoint(CheckpointOpts::Restore { name }) => {
+                debugger.restore_checkpoint(&name)?;
+            }
         }
         Ok(())
     }
 }
+
+fn print_stats<D: Debugger>(debugger: &D, stats: &ExecutionStats, top: usize) {
+    fn sorted_counts(names: HashMap<String, u64>) -> Vec<(String, u64)> {
+        let mut counts: Vec<_> = names.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+    // Functions are counted by address, so merge ones with the same name
+    let call_counts = |counts: &HashMap<ExecutableFuncAddr, u64>| {
+        let mut names: HashMap<String, u64> = HashMap::new();
+        for (addr, count) in counts {
+            let name = match debugger.store().func_global(*addr) {
+                FunctionInstance::Defined(func) => func.name().clone(),
+                FunctionInstance::Host(func) => {
+                    format!("{}.{}", func.module_name(), func.field_name())
+                }
+            };
+            *names.entry(name).or_insert(0) += count;
+        }
+        sorted_counts(names)
+    };
+
+    println!("Total instructions: {}", stats.inst_count());
+    for (class, count) in stats.class_counts() {
+        println!("  {:<12} {:>12}", format!("{:?}", class), count);
+    }
+    println!("Calls per function:");
+    for (name, count) in call_counts(stats.call_counts()).iter().take(top) {
+        println!("  {:>12}  {}", count, name);
+    }
+    println!("Host calls per import:");
+    for (name, count) in call_counts(stats.host_call_counts()).iter().take(top) {
+        println!("  {:>12}  {}", count, name);
+    }
+    println!(
+        "memory.grow: {} times, peak {} pages",
+        stats.memory_grow_count(),
+        stats.peak_memory_pages()
+    );
+}
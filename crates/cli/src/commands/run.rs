@@ -1,5 +1,5 @@
 use super::command::{Command, CommandContext};
-use super::debugger::{Debugger, RunResult};
+use super::debugger::{Debugger, ProcessStdio, RunResult};
 use std::io::Write;
 
 use structopt::StructOpt;
@@ -27,29 +27,37 @@ impl<D: Debugger> Command<D> for RunCommand {
     fn description(&self) -> &'static str {
         "Launch the executable in the debugger."
     }
-    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
-        if debugger.is_running() {
-            print!("There is a running process, kill it and restart?: [Y/n] ");
-            std::io::stdout().flush().unwrap();
-            let stdin = std::io::stdin();
-            let mut input = String::new();
-            stdin.read_line(&mut input).unwrap();
-            if input != "Y\n" {
-                return Ok(());
-            }
+        debugger.set_stdio(ProcessStdio::default());
+        start_process(debugger, opts.name)
+    }
+}
+
+pub fn start_process<D: Debugger>(debugger: &mut D, name: Option<String>) -> Result<()> {
+    if debugger.is_running() {
+        print!("There is a running process, kill it and restart?: [Y/n] ");
+        std::io::stdout().flush().unwrap();
+        let stdin = std::io::stdin();
+        let mut input = String::new();
+        stdin.read_line(&mut input).unwrap();
+        if input != "Y\n" {
+            return Ok(());
+        }
+    }
+    match debugger.run(name) {
+        Ok(RunResult::Finish(values)) => {
+            println!("{:?}", values);
+        }
+        Ok(RunResult::Breakpoint) => {
+            println!("Hit breakpoit");
+        }
+        Ok(RunResult::Interrupted) => {
+            println!("Process interrupted");
         }
-        match debugger.run(opts.name) {
-            Ok(RunResult::Finish(values)) => {
-                println!("{:?}", values);
-            }
-            Ok(RunResult::Breakpoint) => {
-                println!("Hit breakpoit");
-            }
-            Err(msg) => {
-                eprintln!("{}", msg);
-            }
+        Err(msg) => {
+            eprintln!("{}", msg);
         }
-        Ok(())
     }
+    Ok(())
 }
@@ -1,6 +1,6 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use structopt::StructOpt;
 
@@ -14,11 +14,12 @@ impl SettingsCommand {
 
 #[derive(StructOpt)]
 enum Opts {
+    /// Set `directory.map <FROM> <TO>` or `print.max-depth <DEPTH>`
     #[structopt(name = "set")]
     Set {
         key: String,
         operand1: String,
-        operand2: String,
+        operand2: Option<String>,
     },
 }
 
@@ -31,7 +32,7 @@ impl<D: Debugger> Command<D> for SettingsCommand {
         "Commands for setting environment"
     }
 
-    fn run(&self, _debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, _debugger: &mut D, context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Set {
@@ -40,8 +41,12 @@ impl<D: Debugger> Command<D> for SettingsCommand {
                 operand2,
             } => match key.as_str() {
                 "directory.map" => {
+                    let operand2 = operand2.ok_or(anyhow!("'directory.map' requires two paths"))?;
                     context.sourcemap.set_directory_map(operand1, operand2);
                 }
+                "print.max-depth" => {
+                    context.subroutine.set_max_depth(operand1.parse()?);
+                }
                 _ => println!("'{}' is not valid key", key),
             },
         }
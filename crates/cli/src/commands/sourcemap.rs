@@ -22,6 +22,8 @@ pub struct LineInfo {
 
 pub trait SourceMap {
     fn find_line_info(&self, offset: usize) -> Option<LineInfo>;
+    /// All rows of the line table
+    fn line_infos(&self) -> Vec<LineInfo>;
     fn set_directory_map(&self, from: String, to: String);
 }
 
@@ -36,5 +38,8 @@ impl SourceMap for EmptySourceMap {
     fn find_line_info(&self, _: usize) -> Option<LineInfo> {
         None
     }
+    fn line_infos(&self) -> Vec<LineInfo> {
+        vec![]
+    }
     fn set_directory_map(&self, _: String, _: String) {}
 }
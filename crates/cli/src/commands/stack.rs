@@ -1,6 +1,9 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
-use anyhow::Result;
+use super::value::parse_value_like;
+use anyhow::{anyhow, Result};
+
+use structopt::StructOpt;
 
 pub struct StackCommand {}
 
@@ -10,6 +13,26 @@ impl StackCommand {
     }
 }
 
+#[derive(StructOpt)]
+struct Opts {
+    #[structopt(subcommand)]
+    command: Option<SubCommand>,
+}
+
+#[derive(StructOpt)]
+enum SubCommand {
+    #[structopt(name = "read")]
+    Read,
+    /// Overwrite a value on the value stack with a value of the same type
+    #[structopt(name = "write")]
+    Write {
+        #[structopt(name = "INDEX")]
+        index: usize,
+        #[structopt(name = "VALUE", allow_hyphen_values = true)]
+        value: String,
+    },
+}
+
 impl<D: Debugger> Command<D> for StackCommand {
     fn name(&self) -> &'static str {
         "stack"
@@ -19,9 +42,22 @@ impl<D: Debugger> Command<D> for StackCommand {
         "Commands for operating stack."
     }
 
-    fn run(&self, debugger: &mut D, _context: &CommandContext, _args: Vec<&str>) -> Result<()> {
-        for (index, value) in debugger.stack_values().iter().enumerate() {
-            println!("{}: {}", index, value)
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts.command.unwrap_or(SubCommand::Read) {
+            SubCommand::Read => {
+                for (index, value) in debugger.stack_values().iter().enumerate() {
+                    println!("{}: {:?}", index, value)
+                }
+            }
+            SubCommand::Write { index, value } => {
+                let values = debugger.stack_values();
+                let original = values
+                    .get(index)
+                    .ok_or(anyhow!("Stack value {} is out of range", index))?;
+                let value = parse_value_like(original, &value)?;
+                debugger.set_stack_value(index, value)?;
+            }
         }
         Ok(())
     }
@@ -1,19 +1,64 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use wasminspect_vm::WasmValue;
 
 pub struct Variable {
     pub name: String,
     pub type_name: String,
 }
 
+/// Snapshot of the current frame used to evaluate expressions
+pub struct FrameState<'a> {
+    pub code_offset: usize,
+    pub locals: &'a [WasmValue],
+    pub globals: &'a [WasmValue],
+    /// Operand stack of the current function from the bottom
+    pub stack: &'a [WasmValue],
+    pub memory: &'a [u8],
+}
+
+/// Modification of the process state requested by an expression like `x = 5`
+pub enum StateChange {
+    Memory {
+        address: usize,
+        bytes: Vec<u8>,
+    },
+    Local {
+        index: usize,
+        value: WasmValue,
+    },
+    Global {
+        index: usize,
+        value: WasmValue,
+    },
+    /// `index` is relative to the operand stack of the current function
+    Stack {
+        index: usize,
+        value: WasmValue,
+    },
+}
+
+/// Function inlined at the current code offset, shown as a virtual frame
+pub struct InlinedFrame {
+    pub name: String,
+    /// Source location where the function was inlined
+    pub call_file: Option<String>,
+    pub call_line: Option<u64>,
+}
+
+pub struct ExpressionResult {
+    pub output: String,
+    pub changes: Vec<StateChange>,
+}
+
 pub trait SubroutineMap {
     fn variable_name_list(&self, code_offset: usize) -> Result<Vec<Variable>>;
-    fn display_variable(
-        &self,
-        code_offset: usize,
-        rbp: u32,
-        memory: &[u8],
-        name: String,
-    ) -> Result<()>;
+    fn display_return_value(&self, code_offset: usize, value: &WasmValue) -> Result<String>;
+    fn evaluate_expression(&self, frame: &FrameState, expression: &str)
+        -> Result<ExpressionResult>;
+    /// Sets how many levels of pointers are followed when printing values
+    fn set_max_depth(&self, depth: usize);
+    /// Inlined functions containing `code_offset`, innermost first
+    fn inlined_frames(&self, code_offset: usize) -> Vec<InlinedFrame>;
 }
 
 pub struct EmptySubroutineMap {}
@@ -27,7 +72,14 @@ impl SubroutineMap for EmptySubroutineMap {
     fn variable_name_list(&self, _code_offset: usize) -> Result<Vec<Variable>> {
         Ok(vec![])
     }
-    fn display_variable(&self, _: usize, _: u32, _: &[u8], _: String) -> Result<()> {
-        Ok(())
+    fn display_return_value(&self, _: usize, _: &WasmValue) -> Result<String> {
+        Err(anyhow!("No debug information is available"))
+    }
+    fn evaluate_expression(&self, _: &FrameState, _: &str) -> Result<ExpressionResult> {
+        Err(anyhow!("No debug information is available"))
+    }
+    fn set_max_depth(&self, _: usize) {}
+    fn inlined_frames(&self, _: usize) -> Vec<InlinedFrame> {
+        vec![]
     }
 }
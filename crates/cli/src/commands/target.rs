@@ -0,0 +1,55 @@
+use super::command::{Command, CommandContext};
+use super::debugger::Debugger;
+use crate::dwarf::load_debug_info;
+use anyhow::Result;
+use std::path::Path;
+
+use structopt::StructOpt;
+
+pub struct TargetCommand {}
+
+impl TargetCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    #[structopt(name = "symbols")]
+    Symbols(SymbolsOpts),
+}
+
+#[derive(StructOpt)]
+enum SymbolsOpts {
+    /// Load DWARF from a wasm file in place of the debug info of the current module
+    #[structopt(name = "add")]
+    Add {
+        #[structopt(name = "FILE")]
+        file: String,
+    },
+}
+
+impl<D: Debugger> Command<D> for TargetCommand {
+    fn name(&self) -> &'static str {
+        "target"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for operating debug targets."
+    }
+
+    fn run(&self, _debugger: &mut D, context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Symbols(SymbolsOpts::Add { file }) => {
+                let buffer = std::fs::read(&file)?;
+                let debug_info = load_debug_info(Path::new(&file), &buffer)?;
+                context.sourcemap = Box::new(debug_info.sourcemap);
+                context.subroutine = Box::new(debug_info.subroutine);
+                println!("Symbols were loaded from {}", file);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use super::command::{Command, CommandContext};
-use super::debugger::{Debugger, StepStyle};
+use super::debugger::{Debugger, Signal, StepStyle};
+use super::value::parse_integer;
 
 pub struct ThreadCommand {}
 
@@ -9,8 +10,9 @@ impl ThreadCommand {
     }
 }
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use structopt::StructOpt;
+use wasminspect_vm::WasmValue;
 
 #[derive(StructOpt)]
 enum Opts {
@@ -21,17 +23,50 @@ enum Opts {
     #[structopt(name = "step-in")]
     StepIn,
     #[structopt(name = "step-over")]
-    StepOver,
+    StepOver {
+        /// Number of lines to step over
+        #[structopt(short, long, default_value = "1")]
+        count: usize,
+    },
+    /// Continue until the source line is reached in the current frame or the frame returns
+    #[structopt(name = "until")]
+    Until {
+        #[structopt(name = "LINE")]
+        line: u64,
+    },
     #[structopt(name = "step-out")]
     StepOut,
     #[structopt(name = "step-inst-in")]
     StepInstIn,
     #[structopt(name = "step-inst-over")]
     StepInstOver,
+    /// Step back to the beginning of the previous source line in the current frame
+    #[structopt(name = "step-back")]
+    StepBack,
+    /// Step back a single instruction
+    #[structopt(name = "step-inst-back")]
+    StepInstBack,
+    /// Return immediately from the current function with the given values
+    #[structopt(name = "return")]
+    Return {
+        #[structopt(name = "VALUE", allow_hyphen_values = true)]
+        values: Vec<String>,
+    },
+    /// Move the program counter to another location in the current function
+    #[structopt(name = "jump")]
+    Jump {
+        /// Source line in the current file to jump to
+        #[structopt(short, long, conflicts_with = "address", required_unless = "address")]
+        line: Option<u64>,
+        /// Code offset of the instruction to jump to
+        #[structopt(short, long)]
+        address: Option<String>,
+    },
 }
 
 use super::disassemble::display_asm;
 use super::list::{display_source, next_line_info};
+use super::sourcemap::LineInfo;
 impl<D: Debugger> Command<D> for ThreadCommand {
     fn name(&self) -> &'static str {
         "thread"
@@ -41,7 +76,7 @@ impl<D: Debugger> Command<D> for ThreadCommand {
         "Commands for operating the thread."
     }
 
-    fn run(&self, debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args.clone())?;
         match opts {
             Opts::Info => {
@@ -68,28 +103,91 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                 }
             }
             Opts::Backtrace => {
-                for (index, frame) in debugger.frame().iter().rev().enumerate() {
-                    println!("{}: {}", index, frame);
+                let frames = debugger.frame();
+                let code_offsets = debugger.frame_code_offsets();
+                let mut index = 0;
+                for (frame, code_offset) in frames.iter().zip(code_offsets).rev() {
+                    let mut location = code_offset
+                        .and_then(|offset| context.sourcemap.find_line_info(offset))
+                        .map(|info| (Some(info.filepath), info.line));
+                    // Inlined functions are shown as frames called from the enclosing one
+                    let inlined_frames = code_offset
+                        .map(|offset| context.subroutine.inlined_frames(offset))
+                        .unwrap_or_default();
+                    for inlined in inlined_frames {
+                        println!(
+                            "{}: {} [inlined]{}",
+                            index,
+                            inlined.name,
+                            format_location(&location)
+                        );
+                        location = Some((inlined.call_file, inlined.call_line));
+                        index += 1;
+                    }
+                    println!("{}: {}{}", index, frame, format_location(&location));
+                    index += 1;
                 }
             }
-            Opts::StepIn | Opts::StepOver => {
-                let style = match opts {
-                    Opts::StepIn => StepStyle::StepInstIn,
-                    Opts::StepOver => StepStyle::StepInstOver,
-                    _ => panic!(),
-                };
+            Opts::StepIn => {
+                step_line(debugger, context, StepStyle::StepInstIn)?;
+                let line_info = next_line_info(debugger, &context.sourcemap)?;
+                display_source(line_info)?;
+            }
+            Opts::StepOver { count } => {
+                for _ in 0..count {
+                    step_line(debugger, context, StepStyle::StepInstOver)?;
+                }
+                let line_info = next_line_info(debugger, &context.sourcemap)?;
+                display_source(line_info)?;
+            }
+            Opts::Until { line } => {
                 let initial_line_info = next_line_info(debugger, &context.sourcemap)?;
-                while {
-                    debugger.step(style)?;
-                    let line_info = next_line_info(debugger, &context.sourcemap)?;
-                    initial_line_info.filepath == line_info.filepath
-                        && initial_line_info.line == line_info.line
-                } {}
+                let initial_frame_depth = debugger.frame().len();
+                loop {
+                    match debugger.step(StepStyle::StepInstOver)? {
+                        Signal::Next => (),
+                        Signal::Breakpoint | Signal::Interrupted => break,
+                        Signal::End => {
+                            println!("Process finished");
+                            return Ok(());
+                        }
+                    }
+                    if debugger.frame().len() < initial_frame_depth {
+                        break;
+                    }
+                    if let Some(line_info) = find_next_line_info(debugger, context) {
+                        if line_info.filepath == initial_line_info.filepath
+                            && line_info.line == Some(line)
+                        {
+                            break;
+                        }
+                    }
+                }
                 let line_info = next_line_info(debugger, &context.sourcemap)?;
                 display_source(line_info)?;
             }
             Opts::StepOut => {
-                debugger.step(StepStyle::StepOut)?;
+                if inlined_depth(debugger, context) > 0 {
+                    if step_out_inlined(debugger, context)? {
+                        let line_info = next_line_info(debugger, &context.sourcemap)?;
+                        display_source(line_info)?;
+                    }
+                    return Ok(());
+                }
+                let return_count = debugger
+                    .current_frame()
+                    .ok_or(anyhow!("function frame not found"))?
+                    .return_count;
+                let (insts, next_index) = debugger.instructions()?;
+                let code_offset = insts[next_index].offset;
+                match debugger.step(StepStyle::StepOut)? {
+                    Signal::Breakpoint | Signal::Interrupted => (),
+                    Signal::Next | Signal::End => {
+                        let values = debugger.stack_values();
+                        let values = &values[values.len().saturating_sub(return_count)..];
+                        display_return_values(values, code_offset, context);
+                    }
+                }
                 let line_info = next_line_info(debugger, &context.sourcemap)?;
                 display_source(line_info)?;
             }
@@ -102,7 +200,174 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                 debugger.step(style)?;
                 display_asm(debugger)?;
             }
+            Opts::StepBack => {
+                step_line_back(debugger, context)?;
+                let line_info = next_line_info(debugger, &context.sourcemap)?;
+                display_source(line_info)?;
+            }
+            Opts::StepInstBack => {
+                if let Signal::End = debugger.step_back(StepStyle::StepInstIn)? {
+                    println!("Reached the beginning of the recorded history");
+                }
+                display_asm(debugger)?;
+            }
+            Opts::Return { values } => {
+                if let Signal::End = debugger.force_return(values)? {
+                    println!("Process finished");
+                    return Ok(());
+                }
+                match next_line_info(debugger, &context.sourcemap) {
+                    Ok(line_info) => display_source(line_info)?,
+                    Err(_) => display_asm(debugger)?,
+                }
+            }
+            Opts::Jump { line, address } => {
+                let (insts, _) = debugger.instructions()?;
+                let inst_index = if let Some(line) = line {
+                    let filepath = next_line_info(debugger, &context.sourcemap)?.filepath;
+                    insts.iter().position(|inst| {
+                        match context.sourcemap.find_line_info(inst.offset) {
+                            Some(info) => info.filepath == filepath && info.line == Some(line),
+                            None => false,
+                        }
+                    })
+                } else if let Some(address) = address {
+                    let address = parse_integer(&address)? as usize;
+                    insts.iter().position(|inst| inst.offset == address)
+                } else {
+                    unreachable!()
+                };
+                let inst_index = inst_index.ok_or(anyhow!(
+                    "No instruction found at the location in the current function"
+                ))?;
+                debugger.jump(inst_index)?;
+                match next_line_info(debugger, &context.sourcemap) {
+                    Ok(line_info) => display_source(line_info)?,
+                    Err(_) => display_asm(debugger)?,
+                }
+            }
         }
         Ok(())
     }
 }
+
+fn step_line<D: Debugger>(debugger: &D, context: &CommandContext, style: StepStyle) -> Result<()> {
+    let initial_line_info = next_line_info(debugger, &context.sourcemap)?;
+    let initial_frame_depth = debugger.frame().len();
+    let initial_inlined_depth = inlined_depth(debugger, context);
+    while {
+        if let Signal::Interrupted = debugger.step(style)? {
+            return Ok(());
+        }
+        let line_info = next_line_info(debugger, &context.sourcemap)?;
+        // Stepping over also steps over functions inlined into the current frame
+        let in_inlined_callee = match style {
+            StepStyle::StepInstOver => {
+                debugger.frame().len() == initial_frame_depth
+                    && inlined_depth(debugger, context) > initial_inlined_depth
+            }
+            _ => false,
+        };
+        in_inlined_callee
+            || initial_line_info.filepath == line_info.filepath
+                && initial_line_info.line == line_info.line
+    } {}
+    Ok(())
+}
+
+/// Step until the innermost inlined function returns to its caller.
+/// Returns false when the process stops for other reasons.
+fn step_out_inlined<D: Debugger>(debugger: &D, context: &CommandContext) -> Result<bool> {
+    let initial_frame_depth = debugger.frame().len();
+    let initial_inlined_depth = inlined_depth(debugger, context);
+    loop {
+        match debugger.step(StepStyle::StepInstOver)? {
+            Signal::Next => (),
+            Signal::Breakpoint | Signal::Interrupted => return Ok(false),
+            Signal::End => {
+                println!("Process finished");
+                return Ok(false);
+            }
+        }
+        if debugger.frame().len() < initial_frame_depth
+            || inlined_depth(debugger, context) < initial_inlined_depth
+        {
+            return Ok(true);
+        }
+    }
+}
+
+/// Number of inlined functions containing the next instruction of the current frame
+fn inlined_depth<D: Debugger>(debugger: &D, context: &CommandContext) -> usize {
+    match debugger.instructions() {
+        Ok((insts, next_index)) => match insts.get(next_index) {
+            Some(inst) => context.subroutine.inlined_frames(inst.offset).len(),
+            None => 0,
+        },
+        Err(_) => 0,
+    }
+}
+
+fn format_location(location: &Option<(Option<String>, Option<u64>)>) -> String {
+    match location {
+        Some((Some(filepath), Some(line))) => format!(" at {}:{}", filepath, line),
+        Some((Some(filepath), None)) => format!(" at {}", filepath),
+        _ => "".to_string(),
+    }
+}
+
+fn step_line_back<D: Debugger>(debugger: &D, context: &CommandContext) -> Result<()> {
+    let initial_line_info = next_line_info(debugger, &context.sourcemap)?;
+    let initial_frame_depth = debugger.frame().len();
+    loop {
+        match debugger.step_back(StepStyle::StepInstOver)? {
+            Signal::Next => (),
+            Signal::Breakpoint | Signal::Interrupted => return Ok(()),
+            Signal::End => {
+                println!("Reached the beginning of the recorded history");
+                return Ok(());
+            }
+        }
+        if debugger.frame().len() < initial_frame_depth {
+            return Ok(());
+        }
+        let line_info = next_line_info(debugger, &context.sourcemap)?;
+        if initial_line_info.filepath != line_info.filepath
+            || initial_line_info.line != line_info.line
+        {
+            break;
+        }
+    }
+    // Rewind to the first instruction of the line
+    let line_info = next_line_info(debugger, &context.sourcemap)?;
+    while let Some(offset) = debugger.previous_inst_offset() {
+        match context.sourcemap.find_line_info(offset) {
+            Some(info) if info.filepath == line_info.filepath && info.line == line_info.line => (),
+            _ => break,
+        }
+        if let Signal::Next = debugger.step_back(StepStyle::StepInstOver)? {
+            continue;
+        }
+        break;
+    }
+    Ok(())
+}
+
+fn find_next_line_info<D: Debugger>(debugger: &D, context: &CommandContext) -> Option<LineInfo> {
+    let (insts, next_index) = debugger.instructions().ok()?;
+    context
+        .sourcemap
+        .find_line_info(insts.get(next_index)?.offset)
+}
+
+fn display_return_values(values: &[WasmValue], code_offset: usize, context: &CommandContext) {
+    if values.is_empty() {
+        return;
+    }
+    println!("Return value: {:?}", values);
+    if let [value] = values {
+        if let Ok(output) = context.subroutine.display_return_value(code_offset, value) {
+            println!("{}", output);
+        }
+    }
+}
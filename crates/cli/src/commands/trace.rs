@@ -0,0 +1,69 @@
+use super::command::{Command, CommandContext};
+use super::debugger::{Debugger, TraceConfig, TraceStyle};
+use anyhow::Result;
+use structopt::StructOpt;
+
+pub struct TraceCommand {}
+
+impl TraceCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Start logging function calls or instructions
+    #[structopt(name = "start")]
+    Start {
+        /// Write the trace to the given file instead of the standard output
+        #[structopt(short, long)]
+        file: Option<String>,
+        /// Log every function entry and exit with arguments and results
+        #[structopt(long, conflicts_with = "insts")]
+        calls: bool,
+        /// Log every instruction with its offset and top-of-stack values
+        #[structopt(long)]
+        insts: bool,
+        /// Log only functions whose name contains the pattern
+        #[structopt(long)]
+        filter: Option<String>,
+    },
+    /// Stop logging
+    #[structopt(name = "stop")]
+    Stop,
+}
+
+impl<D: Debugger> Command<D> for TraceCommand {
+    fn name(&self) -> &'static str {
+        "trace"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for tracing the execution."
+    }
+
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Start {
+                file,
+                calls,
+                insts,
+                filter,
+            } => {
+                let style = if calls || !insts {
+                    TraceStyle::Calls
+                } else {
+                    TraceStyle::Insts
+                };
+                debugger.start_trace(TraceConfig {
+                    style,
+                    filter,
+                    file,
+                })
+            }
+            Opts::Stop => debugger.stop_trace(),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::{anyhow, Result};
+use wasminspect_vm::WasmValue;
+
+/// Parse decimal or `0x` prefixed hexadecimal integer with optional sign
+pub fn parse_integer(input: &str) -> Result<i128> {
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, input),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => digits.parse::<i128>(),
+    }
+    .map_err(|_| anyhow!("Invalid integer '{}'", input))?;
+    Ok(if negative { -value } else { value })
+}
+
+pub fn value_type_name(value: &WasmValue) -> &'static str {
+    match value {
+        WasmValue::I32(_) => "i32",
+        WasmValue::I64(_) => "i64",
+        WasmValue::F32(_) => "f32",
+        WasmValue::F64(_) => "f64",
+    }
+}
+
+/// Parse a value of the given type name (`i32`, `i64`, `f32` or `f64`)
+pub fn parse_value(ty: &str, input: &str) -> Result<WasmValue> {
+    let value = match ty {
+        "i32" => {
+            let value = parse_integer(input)?;
+            if value < i32::MIN as i128 || value > u32::MAX as i128 {
+                return Err(anyhow!("'{}' is out of range of i32", input));
+            }
+            WasmValue::I32(value as i32)
+        }
+        "i64" => {
+            let value = parse_integer(input)?;
+            if value < i64::MIN as i128 || value > u64::MAX as i128 {
+                return Err(anyhow!("'{}' is out of range of i64", input));
+            }
+            WasmValue::I64(value as i64)
+        }
+        "f32" => WasmValue::F32(
+            input
+                .parse()
+                .map_err(|_| anyhow!("Invalid float '{}'", input))?,
+        ),
+        "f64" => WasmValue::F64(
+            input
+                .parse()
+                .map_err(|_| anyhow!("Invalid float '{}'", input))?,
+        ),
+        _ => return Err(anyhow!("Unknown value type '{}'", ty)),
+    };
+    Ok(value)
+}
+
+/// Parse a value which has the same type as the original one
+pub fn parse_value_like(original: &WasmValue, input: &str) -> Result<WasmValue> {
+    parse_value(value_type_name(original), input)
+}
+
+pub fn value_bytes(value: &WasmValue) -> Vec<u8> {
+    match value {
+        WasmValue::I32(v) => v.to_le_bytes().to_vec(),
+        WasmValue::I64(v) => v.to_le_bytes().to_vec(),
+        WasmValue::F32(v) => v.to_le_bytes().to_vec(),
+        WasmValue::F64(v) => v.to_le_bytes().to_vec(),
+    }
+}
@@ -0,0 +1,53 @@
+use super::command::{Command, CommandContext};
+use super::debugger::Debugger;
+use super::value::parse_integer;
+use anyhow::{anyhow, Result};
+use std::convert::TryFrom;
+use structopt::StructOpt;
+
+pub struct WatchpointCommand {}
+
+impl WatchpointCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Sets a watchpoint which stops the process when bytes at the address are modified
+    #[structopt(name = "set")]
+    Set {
+        #[structopt(name = "ADDRESS")]
+        address: String,
+        /// The number of watched bytes
+        #[structopt(short, long, default_value = "4")]
+        size: usize,
+    },
+}
+
+impl<D: Debugger> Command<D> for WatchpointCommand {
+    fn name(&self) -> &'static str {
+        "watchpoint"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for operating on watchpoints."
+    }
+
+    fn run(&self, debugger: &mut D, _context: &mut CommandContext, args: Vec<&str>) -> Result<()> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Set { address, size } => {
+                let address = usize::try_from(parse_integer(&address)?)
+                    .map_err(|_| anyhow!("'{}' is not a memory address", address))?;
+                if size == 0 {
+                    return Err(anyhow!("The size of a watchpoint must not be zero"));
+                }
+                debugger.set_watchpoint(address, size)?;
+                println!("Watchpoint set at 0x{:08x} with {} bytes", address, size);
+                Ok(())
+            }
+        }
+    }
+}
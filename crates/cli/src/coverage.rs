@@ -0,0 +1,127 @@
+use super::commands::sourcemap::SourceMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+/// Hit counts per line, keyed by file path
+pub struct LineCoverage {
+    files: BTreeMap<String, BTreeMap<u64, u64>>,
+}
+
+impl LineCoverage {
+    /// Map executed instruction offsets to source lines. A line is hit as
+    /// many times as its most executed instruction, and lines in the line
+    /// table without any executed instruction are reported with zero hits.
+    pub fn new(inst_counts: &HashMap<usize, u64>, sourcemap: &dyn SourceMap) -> Self {
+        let mut files: BTreeMap<String, BTreeMap<u64, u64>> = BTreeMap::new();
+        for info in sourcemap.line_infos() {
+            if let Some(line) = info.line {
+                files.entry(info.filepath).or_default().insert(line, 0);
+            }
+        }
+        for (offset, count) in inst_counts.iter() {
+            let info = match sourcemap.find_line_info(*offset) {
+                Some(info) => info,
+                None => continue,
+            };
+            if let Some(line) = info.line {
+                let hits = files
+                    .entry(info.filepath)
+                    .or_default()
+                    .entry(line)
+                    .or_insert(0);
+                *hits = (*hits).max(*count);
+            }
+        }
+        Self { files }
+    }
+
+    /// The number of hit lines and all lines
+    pub fn summary(&self) -> (usize, usize) {
+        let lines = self.files.values().flat_map(|lines| lines.values());
+        let hit = lines.clone().filter(|hits| **hits > 0).count();
+        (hit, lines.count())
+    }
+
+    /// Write the coverage in the lcov tracefile format
+    pub fn write_lcov<W: Write>(&self, test_name: &str, output: &mut W) -> std::io::Result<()> {
+        writeln!(output, "TN:{}", test_name)?;
+        for (filepath, lines) in self.files.iter() {
+            writeln!(output, "SF:{}", filepath)?;
+            for (line, hits) in lines.iter() {
+                writeln!(output, "DA:{},{}", line, hits)?;
+            }
+            let hit = lines.values().filter(|hits| **hits > 0).count();
+            writeln!(output, "LH:{}", hit)?;
+            writeln!(output, "LF:{}", lines.len())?;
+            writeln!(output, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::commands::sourcemap::{ColumnType, EmptySourceMap, LineInfo};
+    use super::*;
+
+    /// Offset `n` is in line `n / 10 + 1` of main.c, and lines 1 to 3 are in the line table
+    struct Lines {}
+
+    fn line_info(line: u64) -> LineInfo {
+        LineInfo {
+            filepath: "main.c".to_string(),
+            line: Some(line),
+            column: ColumnType::LeftEdge,
+        }
+    }
+
+    impl SourceMap for Lines {
+        fn find_line_info(&self, offset: usize) -> Option<LineInfo> {
+            Some(line_info(offset as u64 / 10 + 1))
+        }
+        fn line_infos(&self) -> Vec<LineInfo> {
+            (1..=3).map(line_info).collect()
+        }
+        fn set_directory_map(&self, _: String, _: String) {}
+    }
+
+    fn coverage() -> LineCoverage {
+        let mut inst_counts = HashMap::new();
+        inst_counts.insert(0, 1);
+        inst_counts.insert(1, 1);
+        inst_counts.insert(20, 5);
+        inst_counts.insert(21, 3);
+        LineCoverage::new(&inst_counts, &Lines {})
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(coverage().summary(), (2, 3));
+    }
+
+    #[test]
+    fn test_write_lcov() {
+        let mut output = Vec::new();
+        coverage().write_lcov("test.wasm", &mut output).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "TN:test.wasm\n\
+             SF:main.c\n\
+             DA:1,1\n\
+             DA:2,0\n\
+             DA:3,5\n\
+             LH:2\n\
+             LF:3\n\
+             end_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_empty_coverage() {
+        let coverage = LineCoverage::new(&HashMap::new(), &EmptySourceMap::new());
+        assert_eq!(coverage.summary(), (0, 0));
+        let mut output = Vec::new();
+        coverage.write_lcov("test.wasm", &mut output).unwrap();
+        assert_eq!(std::str::from_utf8(&output).unwrap(), "TN:test.wasm\n");
+    }
+}
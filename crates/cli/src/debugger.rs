@@ -1,14 +1,149 @@
-use super::commands::debugger;
+use super::commands::debugger::{self, Signal};
+use super::commands::value::{parse_value, value_type_name};
+use super::profile::Profiler;
 use anyhow::{anyhow, Result};
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::rc::Rc;
+use tempfile::NamedTempFile;
 use wasminspect_vm::{
-    CallFrame, Executor, FunctionInstance, InstIndex, Instruction, Interceptor, MemoryAddr,
-    ModuleIndex, ProgramCounter, Signal, Store, Trap, WasmValue,
+    read_coredump, write_coredump, CallFrame, ExecutionStats, Executor, FunctionInstance,
+    GlobalAddr, InstIndex, Instruction, InstructionKind, Interceptor, InterruptHandle, MemoryAddr,
+    ModuleIndex, ProgramCounter, Store, StoreSnapshot, Trap, WasmValue,
 };
-use wasminspect_wasi::instantiate_wasi;
-use wasmparser::ModuleReader;
+use wasminspect_wasi::{instantiate_wasi, instantiate_wasi_context, WasiStdio};
+use wasmparser::{ModuleReader, Type};
+
+struct Checkpoint {
+    store: StoreSnapshot,
+    executor: Executor,
+}
+
+struct Watchpoint {
+    memory: MemoryAddr,
+    address: usize,
+    size: usize,
+    /// The bytes seen last time, compared after each executed or reverted instruction
+    value: Vec<u8>,
+}
+
+struct Tracer {
+    style: debugger::TraceStyle,
+    filter: Option<String>,
+    output: Box<dyn Write>,
+    depth: usize,
+}
+
+impl Tracer {
+    fn is_traced(&self, name: &str) -> bool {
+        match self.filter {
+            Some(ref filter) => name.contains(filter.as_str()),
+            None => true,
+        }
+    }
+
+    fn traces_insts(&self) -> bool {
+        match self.style {
+            debugger::TraceStyle::Insts => true,
+            debugger::TraceStyle::Calls => false,
+        }
+    }
+
+    fn trace_inst(&mut self, inst: &Instruction, operands: &[&WasmValue]) {
+        let _ = writeln!(
+            self.output,
+            "0x{:08x}: {:?} {:?}",
+            inst.offset, inst.kind, operands
+        );
+    }
+
+    fn trace_enter(&mut self, name: &str, args: &[WasmValue]) {
+        if let debugger::TraceStyle::Insts = self.style {
+            return;
+        }
+        if self.is_traced(name) {
+            let indent = "  ".repeat(self.depth);
+            let args: Vec<String> = args.iter().map(|v| format!("{:?}", v)).collect();
+            let _ = writeln!(self.output, "{}-> {}({})", indent, name, args.join(", "));
+        }
+        self.depth += 1;
+    }
+
+    fn trace_exit(&mut self, name: &str, results: &[WasmValue]) {
+        if let debugger::TraceStyle::Insts = self.style {
+            return;
+        }
+        self.depth = self.depth.saturating_sub(1);
+        if self.is_traced(name) {
+            let indent = "  ".repeat(self.depth);
+            let _ = writeln!(self.output, "{}<- {}: {:?}", indent, name, results);
+        }
+    }
+}
+
+/// The number of values an instruction pops, which are shown in the instruction trace.
+/// Arguments of calls are shown by the call trace instead.
+fn operand_count(kind: &InstructionKind) -> usize {
+    use InstructionKind::*;
+    match kind {
+        Select | TypedSelect { .. } => 3,
+        I32Store { .. }
+        | I64Store { .. }
+        | F32Store { .. }
+        | F64Store { .. }
+        | I32Store8 { .. }
+        | I32Store16 { .. }
+        | I64Store8 { .. }
+        | I64Store16 { .. }
+        | I64Store32 { .. } => 2,
+        I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU
+        | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU
+        | F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge | F64Eq | F64Ne | F64Lt | F64Gt | F64Le
+        | F64Ge => 2,
+        I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU | I32And | I32Or
+        | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr | I64Add | I64Sub | I64Mul
+        | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor | I64Shl | I64ShrS
+        | I64ShrU | I64Rotl | I64Rotr | F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max
+        | F32Copysign | F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => 2,
+        If { .. }
+        | BrIf { .. }
+        | BrTable { .. }
+        | CallIndirect { .. }
+        | Drop
+        | LocalSet { .. }
+        | LocalTee { .. }
+        | GlobalSet { .. }
+        | MemoryGrow { .. } => 1,
+        I32Load { .. }
+        | I64Load { .. }
+        | F32Load { .. }
+        | F64Load { .. }
+        | I32Load8S { .. }
+        | I32Load8U { .. }
+        | I32Load16S { .. }
+        | I32Load16U { .. }
+        | I64Load8S { .. }
+        | I64Load8U { .. }
+        | I64Load16S { .. }
+        | I64Load16U { .. }
+        | I64Load32S { .. }
+        | I64Load32U { .. } => 1,
+        I32Eqz | I64Eqz | I32Clz | I32Ctz | I32Popcnt | I64Clz | I64Ctz | I64Popcnt | F32Abs
+        | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt | F64Abs | F64Neg
+        | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt => 1,
+        I32WrapI64 | I32TruncF32S | I32TruncF32U | I32TruncF64S | I32TruncF64U | I64ExtendI32S
+        | I64ExtendI32U | I64TruncF32S | I64TruncF32U | I64TruncF64S | I64TruncF64U
+        | F32ConvertI32S | F32ConvertI32U | F32ConvertI64S | F32ConvertI64U | F32DemoteF64
+        | F64ConvertI32S | F64ConvertI32U | F64ConvertI64S | F64ConvertI64U | F64PromoteF32
+        | I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64
+        | I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S
+        | I32TruncSatF32S | I32TruncSatF32U | I32TruncSatF64S | I32TruncSatF64U
+        | I64TruncSatF32S | I64TruncSatF32U | I64TruncSatF64S | I64TruncSatF64U => 1,
+        _ => 0,
+    }
+}
 
 pub struct MainDebugger {
     store: Store,
@@ -16,6 +151,20 @@ pub struct MainDebugger {
     module_index: Option<ModuleIndex>,
 
     function_breakpoints: HashMap<String, debugger::Breakpoint>,
+    watchpoints: RefCell<Vec<Watchpoint>>,
+    checkpoints: HashMap<String, Checkpoint>,
+    tracer: RefCell<Option<Tracer>>,
+    profiler: RefCell<Option<Profiler>>,
+    history_limit: usize,
+    /// The file where a core dump is written when the process traps
+    trap_core_file: Option<String>,
+    /// Statistics of the last finished run
+    last_stats: Option<ExecutionStats>,
+    interrupt: InterruptHandle,
+
+    stdio: debugger::ProcessStdio,
+    /// Removed when the next run starts or the debugger exits
+    output_file: Option<NamedTempFile>,
 }
 
 impl MainDebugger {
@@ -24,12 +173,59 @@ impl MainDebugger {
         self.module_index = Some(self.store.load_parity_module(None, &mut reader)?);
         Ok(())
     }
+    /// Restore the process state from a core dump of the loaded module
+    pub fn load_core(&mut self, core: &[u8]) -> Result<()> {
+        let module_index = self.module_index.ok_or(anyhow!("No module loaded"))?;
+        let mut executor = read_coredump(core, &self.store, module_index)?;
+        executor.set_interrupt_handle(self.interrupt.clone());
+        self.executor = Some(Rc::new(RefCell::new(executor)));
+        self.sync_watchpoints();
+        Ok(())
+    }
+
+    /// Pause the running process by Ctrl-C instead of terminating wasminspect.
+    /// The handler can be installed only once per process.
+    pub fn handle_interrupt_signal(&self) -> Result<()> {
+        let interrupt = self.interrupt.clone();
+        ctrlc::set_handler(move || interrupt.interrupt())
+            .map_err(|e| anyhow!("Failed to install the interrupt handler: {}", e))
+    }
+
+    /// Set the number of instructions recorded to be able to step back. 0 disables recording.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+    }
+
+    /// Write a core dump to the file when the process traps unless the file exists
+    pub fn set_trap_core_file(&mut self, path: String) {
+        self.trap_core_file = Some(path);
+    }
+
+    /// Count executed instructions until `finish_profile` is called
+    pub fn start_profile(&mut self) {
+        *self.profiler.borrow_mut() = Some(Profiler::new());
+    }
+
+    pub fn finish_profile(&mut self) -> Option<Profiler> {
+        self.profiler.borrow_mut().take()
+    }
+
     pub fn new() -> Result<Self> {
         Ok(Self {
             store: Self::instantiate_store(),
             executor: None,
             module_index: None,
             function_breakpoints: HashMap::new(),
+            watchpoints: RefCell::new(Vec::new()),
+            checkpoints: HashMap::new(),
+            tracer: RefCell::new(None),
+            profiler: RefCell::new(None),
+            history_limit: 0,
+            trap_core_file: None,
+            last_stats: None,
+            interrupt: InterruptHandle::new(),
+            stdio: debugger::ProcessStdio::default(),
+            output_file: None,
         })
     }
 
@@ -42,6 +238,51 @@ impl MainDebugger {
         store.load_host_module("wasi_unstable".to_string(), wasi_unstable);
         store
     }
+
+    /// Memory of the module of the current frame, `None` if not running or the module has no memory
+    fn current_memory_addr(&self) -> Result<Option<MemoryAddr>> {
+        let executor = match self.executor {
+            Some(ref executor) => executor.borrow(),
+            None => return Ok(None),
+        };
+        let frame = executor
+            .stack
+            .current_frame()
+            .map_err(|e| anyhow!("Failed to get current frame: {}", e))?;
+        if self.store.memory_count(frame.module_index()) == 0 {
+            return Ok(None);
+        }
+        Ok(Some(MemoryAddr::new_unsafe(frame.module_index(), 0)))
+    }
+
+    fn reset_wasi_context(&mut self) -> Result<()> {
+        let mut stdio = WasiStdio::default();
+        if let Some(ref path) = self.stdio.stdin {
+            stdio.stdin = Some(File::open(path)?);
+        }
+        if let Some(ref path) = self.stdio.stdout {
+            stdio.stdout = Some(File::create(path)?);
+        }
+        if let Some(ref path) = self.stdio.stderr {
+            stdio.stderr = Some(File::create(path)?);
+        }
+        self.output_file = None;
+        if self.stdio.capture {
+            let output_file = tempfile::Builder::new()
+                .prefix("wasminspect-output-")
+                .tempfile()?;
+            if stdio.stdout.is_none() {
+                stdio.stdout = Some(output_file.as_file().try_clone()?);
+            }
+            if stdio.stderr.is_none() {
+                stdio.stderr = Some(output_file.as_file().try_clone()?);
+            }
+            self.output_file = Some(output_file);
+        }
+        self.store
+            .add_embed_context(Box::new(instantiate_wasi_context(stdio)));
+        Ok(())
+    }
 }
 
 impl debugger::Debugger for MainDebugger {
@@ -55,6 +296,17 @@ impl debugger::Debugger for MainDebugger {
         }
     }
 
+    fn set_stdio(&mut self, stdio: debugger::ProcessStdio) {
+        self.stdio = stdio;
+    }
+
+    fn captured_output(&self) -> Result<Vec<u8>> {
+        match self.output_file {
+            Some(ref file) => Ok(std::fs::read(file.path())?),
+            None => Err(anyhow!("Output is not captured")),
+        }
+    }
+
     fn set_breakpoint(&mut self, breakpoint: debugger::Breakpoint) {
         match &breakpoint {
             debugger::Breakpoint::Function { name } => {
@@ -63,16 +315,50 @@ impl debugger::Debugger for MainDebugger {
         }
     }
 
-    fn stack_values(&self) -> Vec<String> {
+    fn set_watchpoint(&mut self, address: usize, size: usize) -> Result<()> {
+        if self.executor.is_none() {
+            return Err(anyhow!("No execution context"));
+        }
+        let memory = self
+            .current_memory_addr()?
+            .ok_or(anyhow!("The current module has no memory"))?;
+        let mut watchpoint = Watchpoint {
+            memory,
+            address,
+            size,
+            value: Vec::new(),
+        };
+        watchpoint.value = self.watched_bytes(&watchpoint);
+        self.watchpoints.borrow_mut().push(watchpoint);
+        Ok(())
+    }
+
+    fn stack_values(&self) -> Vec<WasmValue> {
         if let Some(ref executor) = self.executor {
             let executor = executor.borrow();
             let values = executor.stack.peek_values();
-            values.iter().map(|v| format!("{:?}", v)).collect()
+            values.into_iter().cloned().collect()
         } else {
             Vec::new()
         }
     }
 
+    fn set_stack_value(&mut self, index: usize, value: WasmValue) -> Result<()> {
+        let executor = match self.executor {
+            Some(ref executor) => executor,
+            None => return Err(anyhow!("No execution context")),
+        };
+        let mut executor = executor.borrow_mut();
+        let mut values = executor.stack.peek_values_mut();
+        let slot = values
+            .get_mut(index)
+            .ok_or(anyhow!("Stack value {} is out of range", index))?;
+        check_value_type(slot, &value)?;
+        **slot = value;
+        executor.clear_history();
+        Ok(())
+    }
+
     fn store(&self) -> &Store {
         &self.store
     }
@@ -84,6 +370,60 @@ impl debugger::Debugger for MainDebugger {
             Vec::new()
         }
     }
+    fn set_local(&mut self, index: usize, value: WasmValue) -> Result<()> {
+        let executor = match self.executor {
+            Some(ref executor) => executor,
+            None => return Err(anyhow!("No execution context")),
+        };
+        let mut executor = executor.borrow_mut();
+        let frame = executor
+            .stack
+            .current_frame()
+            .map_err(|e| anyhow!("Failed to get current frame: {}", e))?;
+        let original = frame
+            .locals
+            .get(index)
+            .ok_or(anyhow!("Local {} is out of range", index))?;
+        check_value_type(original, &value)?;
+        executor
+            .stack
+            .set_local(index, value)
+            .map_err(|e| anyhow!("Failed to set local: {}", e))?;
+        executor.clear_history();
+        Ok(())
+    }
+    fn globals(&self) -> Vec<WasmValue> {
+        let module_index = match self.current_frame() {
+            Some(frame) => frame.module_index,
+            None => return Vec::new(),
+        };
+        (0..self.store.global_count(module_index))
+            .map(|index| {
+                let addr = GlobalAddr::new_unsafe(module_index, index);
+                self.store.global(addr).borrow().value()
+            })
+            .collect()
+    }
+    fn set_global(&mut self, index: usize, value: WasmValue) -> Result<()> {
+        let module_index = match self.current_frame() {
+            Some(frame) => frame.module_index,
+            None => return Err(anyhow!("function frame not found")),
+        };
+        if index >= self.store.global_count(module_index) {
+            return Err(anyhow!("Global {} is out of range", index));
+        }
+        let global = self
+            .store
+            .global(GlobalAddr::new_unsafe(module_index, index));
+        let mut global = global.borrow_mut();
+        if !global.is_mutable() {
+            return Err(anyhow!("Global {} is immutable", index));
+        }
+        check_value_type(&global.value(), &value)?;
+        global.set_value(value);
+        self.clear_history();
+        Ok(())
+    }
     fn current_frame(&self) -> Option<debugger::FunctionFrame> {
         let executor = if let Some(ref executor) = self.executor {
             executor
@@ -93,10 +433,17 @@ impl debugger::Debugger for MainDebugger {
         let executor = executor.borrow();
         let frame = executor.stack.current_frame().unwrap();
         let func = self.store.func_global(frame.exec_addr);
+        let frame_values = executor
+            .stack
+            .peek_frame_values()
+            .last()
+            .map(|(_, values)| values.len())
+            .unwrap_or(0);
 
         self.module_index.map(|idx| debugger::FunctionFrame {
             module_index: idx,
-            argument_count: func.ty().params.len(),
+            return_count: func.ty().returns.len(),
+            value_stack_base: executor.stack.peek_values().len() - frame_values,
         })
     }
     fn frame(&self) -> Vec<String> {
@@ -111,18 +458,57 @@ impl debugger::Debugger for MainDebugger {
             Vec::new()
         }
     }
+    fn frame_code_offsets(&self) -> Vec<Option<usize>> {
+        let executor = match self.executor {
+            Some(ref executor) => executor.borrow(),
+            None => return Vec::new(),
+        };
+        let frames = executor.stack.peek_frames();
+        frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let inst_index = match frames.get(i + 1) {
+                    // The return address points to the instruction after the call
+                    Some(callee) => (callee.ret_pc?.inst_index().0 as usize).checked_sub(1)?,
+                    None => executor.pc.inst_index().0 as usize,
+                };
+                let func = self.store.func_global(frame.exec_addr).defined()?;
+                func.instructions().get(inst_index).map(|inst| inst.offset)
+            })
+            .collect()
+    }
     fn memory(&self) -> Result<Vec<u8>> {
-        if let Some(ref executor) = self.executor {
-            let executor = executor.borrow();
-            let frame = executor
-                .stack
-                .current_frame()
-                .map_err(|e| anyhow!("Failed to get current frame: {}", e))?;
-            let addr = MemoryAddr::new_unsafe(frame.module_index(), 0);
-            Ok(self.store.memory(addr).borrow().raw_data().to_vec())
-        } else {
-            Ok(vec![])
+        self.with_memory(|memory| memory.to_vec())
+    }
+
+    fn with_memory<T, F: FnOnce(&[u8]) -> T>(&self, f: F) -> Result<T> {
+        match self.current_memory_addr()? {
+            Some(addr) => {
+                let memory = self.store.memory(addr);
+                let memory = memory.borrow();
+                Ok(f(memory.raw_data()))
+            }
+            // Modules without memory are seen as having an empty one
+            None => Ok(f(&[])),
+        }
+    }
+
+    fn write_memory(&mut self, address: usize, bytes: &[u8]) -> Result<()> {
+        if self.executor.is_none() {
+            return Err(anyhow!("No execution context"));
         }
+        let addr = self
+            .current_memory_addr()?
+            .ok_or(anyhow!("The current module has no memory"))?;
+        self.store
+            .memory(addr)
+            .borrow_mut()
+            .store(address, bytes)
+            .map_err(|e| anyhow!("Failed to write memory: {}", e))?;
+        self.clear_history();
+        self.sync_watchpoints();
+        Ok(())
     }
 
     fn is_running(&self) -> bool {
@@ -141,25 +527,27 @@ impl debugger::Debugger for MainDebugger {
             executor.stack.peek_frames().len()
         }
         match style {
-            StepInstIn => return Ok(executor.borrow_mut().execute_step(&self.store, self)?),
+            StepInstIn => self.execute_step(executor),
             StepInstOver => {
                 let initial_frame_depth = frame_depth(&executor.borrow());
-                let mut last_signal = executor.borrow_mut().execute_step(&self.store, self)?;
+                let mut last_signal = self.execute_step(executor)?;
                 while initial_frame_depth < frame_depth(&executor.borrow()) {
-                    last_signal = executor.borrow_mut().execute_step(&self.store, self)?;
-                    if let Signal::Breakpoint = last_signal {
-                        return Ok(last_signal);
+                    last_signal = self.execute_step(executor)?;
+                    match last_signal {
+                        Signal::Breakpoint | Signal::Interrupted => return Ok(last_signal),
+                        _ => {}
                     }
                 }
                 return Ok(last_signal);
             }
             StepOut => {
                 let initial_frame_depth = frame_depth(&executor.borrow());
-                let mut last_signal = executor.borrow_mut().execute_step(&self.store, self)?;
+                let mut last_signal = self.execute_step(executor)?;
                 while initial_frame_depth <= frame_depth(&executor.borrow()) {
-                    last_signal = executor.borrow_mut().execute_step(&self.store, self)?;
-                    if let Signal::Breakpoint = last_signal {
-                        return Ok(last_signal);
+                    last_signal = self.execute_step(executor)?;
+                    match last_signal {
+                        Signal::Breakpoint | Signal::Interrupted => return Ok(last_signal),
+                        _ => {}
                     }
                 }
                 return Ok(last_signal);
@@ -167,26 +555,236 @@ impl debugger::Debugger for MainDebugger {
         }
     }
 
+    fn step_back(&self, style: debugger::StepStyle) -> Result<Signal> {
+        let executor = if let Some(ref executor) = self.executor {
+            executor
+        } else {
+            return Err(anyhow!("No execution context"));
+        };
+        use debugger::StepStyle::*;
+
+        Self::check_history(&executor.borrow())?;
+        let initial_frame_depth = executor.borrow().stack.frame_depth();
+        loop {
+            if !executor.borrow_mut().step_back(&self.store) {
+                return Ok(Signal::End);
+            }
+            if let Signal::Breakpoint = self.check_watchpoints() {
+                return Ok(Signal::Breakpoint);
+            }
+            let frame_depth = executor.borrow().stack.frame_depth();
+            let done = match style {
+                StepInstIn => true,
+                StepInstOver => frame_depth <= initial_frame_depth,
+                StepOut => frame_depth < initial_frame_depth,
+            };
+            if let Signal::Breakpoint = self.reverse_breakpoint(&executor.borrow())? {
+                return Ok(Signal::Breakpoint);
+            }
+            if done {
+                return Ok(Signal::Next);
+            }
+        }
+    }
+
+    fn previous_inst_offset(&self) -> Option<usize> {
+        let executor = self.executor.as_ref()?.borrow();
+        let frame_depth = executor.stack.frame_depth();
+        let record = executor
+            .history()?
+            .records()
+            .rev()
+            .find(|record| record.frame_depth() <= frame_depth)?;
+        if record.frame_depth() < frame_depth {
+            return None;
+        }
+        let func = self.store.func_global(record.pc().exec_addr()).defined()?;
+        Some(func.inst(record.pc().inst_index()).offset)
+    }
+
+    fn reverse_process(&self) -> Result<Signal> {
+        let executor = if let Some(ref executor) = self.executor {
+            executor
+        } else {
+            return Err(anyhow!("No execution context"));
+        };
+        Self::check_history(&executor.borrow())?;
+        self.interrupt.clear();
+        loop {
+            if self.interrupt.take() {
+                return Ok(Signal::Interrupted);
+            }
+            if !executor.borrow_mut().step_back(&self.store) {
+                return Ok(Signal::End);
+            }
+            if let Signal::Breakpoint = self.check_watchpoints() {
+                return Ok(Signal::Breakpoint);
+            }
+            if let Signal::Breakpoint = self.reverse_breakpoint(&executor.borrow())? {
+                return Ok(Signal::Breakpoint);
+            }
+        }
+    }
+
+    fn force_return(&mut self, values: Vec<String>) -> Result<Signal> {
+        let executor = match self.executor {
+            Some(ref executor) => executor,
+            None => return Err(anyhow!("No execution context")),
+        };
+        let mut executor = executor.borrow_mut();
+        let func = self.store.func_global(executor.pc.exec_addr());
+        let ret_types = &func.ty().returns;
+        if ret_types.len() != values.len() {
+            return Err(anyhow!(
+                "Function returns {} values but {} values are given",
+                ret_types.len(),
+                values.len()
+            ));
+        }
+        let mut results = vec![];
+        for (ty, value) in ret_types.iter().zip(values.iter()) {
+            let ty = match ty {
+                Type::I32 => "i32",
+                Type::I64 => "i64",
+                Type::F32 => "f32",
+                Type::F64 => "f64",
+                _ => return Err(anyhow!("Unsupported return type {:?}", ty)),
+            };
+            results.push(parse_value(ty, value)?);
+        }
+        let signal = executor.force_return(results, &self.store, &*self)?.into();
+        if let Signal::End = signal {
+            self.last_stats = executor.take_stats();
+            drop(executor);
+            self.executor = None;
+        }
+        Ok(signal)
+    }
+
+    fn jump(&mut self, inst_index: usize) -> Result<()> {
+        let executor = match self.executor {
+            Some(ref executor) => executor,
+            None => return Err(anyhow!("No execution context")),
+        };
+        let mut executor = executor.borrow_mut();
+        let insts = executor.current_func_insts(&self.store)?;
+        if inst_index >= insts.len() {
+            return Err(anyhow!("Instruction {} is out of function", inst_index));
+        }
+        executor
+            .jump(InstIndex(inst_index as u32), &self.store, &*self)
+            .map_err(|e| anyhow!("Failed to jump: {}", e))
+    }
+
     fn process(&self) -> Result<Signal> {
         let executor = if let Some(ref executor) = self.executor {
             executor
         } else {
             return Err(anyhow!("No execution context"));
         };
+        self.interrupt.clear();
         loop {
             let result = executor.borrow_mut().execute_step(&self.store, self);
             match result {
-                Ok(Signal::Next) => continue,
-                Ok(Signal::Breakpoint) | Ok(Signal::End) => return Ok(result.ok().unwrap()),
-                Err(err) => return Err(anyhow!("Function exec failure {:?}", err)),
+                Ok(wasminspect_vm::Signal::Next) => {
+                    if let Signal::Breakpoint = self.check_watchpoints() {
+                        return Ok(Signal::Breakpoint);
+                    }
+                }
+                Ok(signal) => return Ok(signal.into()),
+                Err(Trap::OutOfFuel) | Err(Trap::Interrupted) => return Ok(Signal::Interrupted),
+                Err(err) => {
+                    if let Some(ref path) = self.trap_core_file {
+                        match self.write_trap_core(path) {
+                            Ok(_) => println!("Core dump was written to {}", path),
+                            Err(err) => eprintln!("Failed to write core dump: {}", err),
+                        }
+                    }
+                    return Err(anyhow!("Function exec failure {:?}", err));
+                }
             }
         }
     }
 
+    fn fuel(&self) -> Option<u64> {
+        self.executor.as_ref()?.borrow().fuel()
+    }
+
+    fn set_fuel(&self, fuel: Option<u64>) -> Result<()> {
+        match self.executor {
+            Some(ref executor) => executor.borrow_mut().set_fuel(fuel),
+            None => return Err(anyhow!("No execution context")),
+        }
+        Ok(())
+    }
+
+    fn stats(&self) -> Option<ExecutionStats> {
+        match self.executor {
+            Some(ref executor) => executor.borrow().stats().cloned(),
+            None => self.last_stats.clone(),
+        }
+    }
+
+    fn save_core(&self, path: &str) -> Result<()> {
+        std::fs::write(path, self.coredump()?)?;
+        Ok(())
+    }
+
+    fn start_trace(&mut self, config: debugger::TraceConfig) -> Result<()> {
+        let output: Box<dyn Write> = match config.file {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(std::io::stdout()),
+        };
+        *self.tracer.borrow_mut() = Some(Tracer {
+            style: config.style,
+            filter: config.filter,
+            output,
+            depth: 0,
+        });
+        Ok(())
+    }
+
+    fn stop_trace(&mut self) -> Result<()> {
+        match self.tracer.borrow_mut().take() {
+            Some(mut tracer) => Ok(tracer.output.flush()?),
+            None => Err(anyhow!("Trace is not started")),
+        }
+    }
+
+    fn save_checkpoint(&mut self, name: String) -> Result<()> {
+        let executor = match self.executor {
+            Some(ref executor) => executor.borrow().clone(),
+            None => return Err(anyhow!("No execution context")),
+        };
+        let checkpoint = Checkpoint {
+            store: self.store.snapshot(),
+            executor,
+        };
+        self.checkpoints.insert(name, checkpoint);
+        Ok(())
+    }
+
+    fn restore_checkpoint(&mut self, name: &str) -> Result<()> {
+        let checkpoint = self
+            .checkpoints
+            .get(name)
+            .ok_or(anyhow!("Checkpoint {} not found", name))?;
+        if !self.store.restore(&checkpoint.store) {
+            return Err(anyhow!(
+                "Checkpoint {} is incompatible with the process",
+                name
+            ));
+        }
+        self.executor = Some(Rc::new(RefCell::new(checkpoint.executor.clone())));
+        self.sync_watchpoints();
+        Ok(())
+    }
+
     fn run(&mut self, name: Option<String>) -> Result<debugger::RunResult> {
         if self.is_running() {
             self.store = Self::instantiate_store();
         }
+        self.reset_wasi_context()?;
         if let Some(module_index) = self.module_index {
             let module = self.store.module(module_index).defined().unwrap();
             let func_addr = if let Some(func_name) = name {
@@ -225,22 +823,37 @@ impl debugger::Debugger for MainDebugger {
                     let ret_types = &func.ty().returns;
                     let frame = CallFrame::new_from_func(exec_addr, func, vec![], None);
                     let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
-                    let executor = Rc::new(RefCell::new(Executor::new(frame, ret_types.len(), pc)));
+                    let mut executor = Executor::new(frame, ret_types.len(), pc);
+                    if self.history_limit > 0 {
+                        executor.enable_history(self.history_limit);
+                    }
+                    executor
+                        .enable_stats(&self.store)
+                        .map_err(|e| anyhow!("Failed to enable statistics: {}", e))?;
+                    executor.set_interrupt_handle(self.interrupt.clone());
+                    let executor = Rc::new(RefCell::new(executor));
                     self.executor = Some(executor.clone());
+                    self.sync_watchpoints();
+                    self.enter_func(func.name(), &[]);
                     let result = self.process()?;
                     match result {
                         Signal::Next => unreachable!(),
-                        Signal::Breakpoint => return Ok(debugger::RunResult::Breakpoint),
-                        Signal::End => match executor.borrow_mut().pop_result(ret_types.to_vec()) {
-                            Ok(values) => {
-                                self.executor = None;
-                                return Ok(debugger::RunResult::Finish(values));
+                        Signal::Breakpoint => Ok(debugger::RunResult::Breakpoint),
+                        Signal::Interrupted => Ok(debugger::RunResult::Interrupted),
+                        Signal::End => {
+                            self.last_stats = executor.borrow_mut().take_stats();
+                            let result = executor.borrow_mut().pop_result(ret_types.to_vec());
+                            match result {
+                                Ok(values) => {
+                                    self.executor = None;
+                                    return Ok(debugger::RunResult::Finish(values));
+                                }
+                                Err(err) => {
+                                    self.executor = None;
+                                    return Err(anyhow!("Return value failure {:?}", err));
+                                }
                             }
-                            Err(err) => {
-                                self.executor = None;
-                                return Err(anyhow!("Return value failure {:?}", err));
-                            }
-                        },
+                        }
                     }
                 }
             }
@@ -250,17 +863,823 @@ impl debugger::Debugger for MainDebugger {
     }
 }
 
+impl MainDebugger {
+    /// Execute a single instruction. An interrupt request pauses the process before it.
+    fn execute_step(&self, executor: &RefCell<Executor>) -> Result<Signal> {
+        let result = executor.borrow_mut().execute_step(&self.store, self);
+        match result {
+            Err(Trap::OutOfFuel) | Err(Trap::Interrupted) => Ok(Signal::Interrupted),
+            Ok(wasminspect_vm::Signal::Next) => Ok(self.check_watchpoints()),
+            result => Ok(result?.into()),
+        }
+    }
+
+    fn coredump(&self) -> Result<Vec<u8>> {
+        let executor = match self.executor {
+            Some(ref executor) => executor.borrow(),
+            None => return Err(anyhow!("No execution context")),
+        };
+        let module_index = self.module_index.ok_or(anyhow!("No module loaded"))?;
+        Ok(write_coredump(&executor, &self.store, module_index, ""))
+    }
+
+    /// Unlike `save_core`, an existing file is not overwritten
+    fn write_trap_core(&self, path: &str) -> Result<()> {
+        let core = self.coredump()?;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| anyhow!("{}: {}", path, e))?;
+        file.write_all(&core)?;
+        Ok(())
+    }
+
+    fn check_history(executor: &Executor) -> Result<()> {
+        match executor.history() {
+            Some(_) => Ok(()),
+            None => Err(anyhow!(
+                "Execution history is not recorded. Start wasminspect with --history to step back"
+            )),
+        }
+    }
+
+    /// Forget the recorded history, which can't revert modifications by the debugger
+    fn clear_history(&self) {
+        if let Some(ref executor) = self.executor {
+            executor.borrow_mut().clear_history();
+        }
+    }
+
+    fn watched_bytes(&self, watchpoint: &Watchpoint) -> Vec<u8> {
+        if self.store.memory_count(watchpoint.memory.module_index()) == 0 {
+            return Vec::new();
+        }
+        let memory = self.store.memory(watchpoint.memory);
+        let memory = memory.borrow();
+        let end = match watchpoint.address.checked_add(watchpoint.size) {
+            Some(end) => end,
+            None => return Vec::new(),
+        };
+        match memory.raw_data().get(watchpoint.address..end) {
+            Some(bytes) => bytes.to_vec(),
+            // Out of bounds until the memory grows
+            None => Vec::new(),
+        }
+    }
+
+    /// Stop with `Signal::Breakpoint` if watched bytes are modified since the last check
+    fn check_watchpoints(&self) -> Signal {
+        let mut signal = Signal::Next;
+        for watchpoint in self.watchpoints.borrow_mut().iter_mut() {
+            let value = self.watched_bytes(watchpoint);
+            if value != watchpoint.value {
+                println!(
+                    "Watchpoint 0x{:08x} hit: old value: {:02x?}, new value: {:02x?}",
+                    watchpoint.address, watchpoint.value, value
+                );
+                watchpoint.value = value;
+                signal = Signal::Breakpoint;
+            }
+        }
+        signal
+    }
+
+    /// Take the current bytes as watched values without stopping,
+    /// used when the debugger modifies the memory
+    fn sync_watchpoints(&self) {
+        for watchpoint in self.watchpoints.borrow_mut().iter_mut() {
+            watchpoint.value = self.watched_bytes(watchpoint);
+        }
+    }
+
+    /// Function breakpoints hit in reverse when the program counter is back at the function entry
+    fn reverse_breakpoint(&self, executor: &Executor) -> Result<Signal> {
+        if executor.pc.inst_index().0 != 0 {
+            return Ok(Signal::Next);
+        }
+        let func = self.store.func_global(executor.pc.exec_addr());
+        Ok(self.invoke_func(func.name())?.into())
+    }
+}
+
 impl Interceptor for MainDebugger {
-    fn invoke_func(&self, name: &String) -> Result<Signal, Trap> {
+    fn invoke_func(&self, name: &String) -> Result<wasminspect_vm::Signal, Trap> {
         let key = self
             .function_breakpoints
             .keys()
             .filter(|k| name.contains(k.clone()))
             .next();
         if let Some(_) = key {
-            Ok(Signal::Breakpoint)
+            Ok(wasminspect_vm::Signal::Breakpoint)
         } else {
-            Ok(Signal::Next)
+            Ok(wasminspect_vm::Signal::Next)
+        }
+    }
+
+    fn execute_inst(&self, inst: &Instruction, executor: &Executor) {
+        if let Some(profiler) = self.profiler.borrow_mut().as_mut() {
+            profiler.count_inst(inst.offset);
+        }
+        if let Some(tracer) = self.tracer.borrow_mut().as_mut() {
+            if tracer.traces_insts() {
+                let func = self.store.func_global(executor.pc.exec_addr());
+                if tracer.is_traced(func.name()) {
+                    let operands = executor.stack.peek_top_values(operand_count(&inst.kind));
+                    tracer.trace_inst(inst, &operands);
+                }
+            }
+        }
+    }
+
+    fn enter_func(&self, name: &str, args: &[WasmValue]) {
+        if let Some(profiler) = self.profiler.borrow_mut().as_mut() {
+            profiler.enter_func(name);
+        }
+        if let Some(tracer) = self.tracer.borrow_mut().as_mut() {
+            tracer.trace_enter(name, args);
+        }
+    }
+
+    fn exit_func(&self, name: &str, results: &[WasmValue]) {
+        if let Some(profiler) = self.profiler.borrow_mut().as_mut() {
+            profiler.exit_func();
+        }
+        if let Some(tracer) = self.tracer.borrow_mut().as_mut() {
+            tracer.trace_exit(name, results);
+        }
+    }
+}
+
+fn check_value_type(original: &WasmValue, value: &WasmValue) -> Result<()> {
+    if original.value_type() != value.value_type() {
+        return Err(anyhow!(
+            "Type mismatch: expected {} but got {}",
+            value_type_name(original),
+            value_type_name(value)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::command::{Command, CommandContext};
+    use crate::commands::sourcemap::{ColumnType, LineInfo, SourceMap};
+    use crate::commands::subroutine::{
+        EmptySubroutineMap, ExpressionResult, FrameState, InlinedFrame, SubroutineMap, Variable,
+    };
+    use crate::commands::thread::ThreadCommand;
+    use debugger::{Breakpoint, Debugger, RunResult, StepStyle};
+
+    /// `inner` stores 42 at 16 and sets the global to its local
+    const MODULE: &str = r#"
+    (module
+      (memory 1)
+      (global $g (mut i32) (i32.const 0))
+      (func $inner (param i32) (result i32) (local i32)
+        i32.const 16
+        i32.const 42
+        i32.store
+        local.get 0
+        local.set 1
+        local.get 1
+        global.set $g
+        local.get 1)
+      (func $_start (export "_start")
+        i32.const 7
+        call $inner
+        drop))
+    "#;
+
+    fn wat2wasm(wat: &str) -> Vec<u8> {
+        let buf = wast::parser::ParseBuffer::new(wat).unwrap();
+        let mut wat = wast::parser::parse::<wast::Wat>(&buf).unwrap();
+        wat.module.encode().unwrap()
+    }
+
+    /// Stop at the entry of `inner` recording the history
+    fn launch() -> MainDebugger {
+        let mut debugger = MainDebugger::new().unwrap();
+        debugger.load_module(&wat2wasm(MODULE)).unwrap();
+        debugger.set_history_limit(100);
+        debugger.set_breakpoint(Breakpoint::Function {
+            name: "inner".to_string(),
+        });
+        match debugger.run(None).unwrap() {
+            RunResult::Breakpoint => debugger,
+            _ => panic!("inner should be hit"),
+        }
+    }
+
+    fn step_insts(debugger: &MainDebugger, count: usize) {
+        for _ in 0..count {
+            debugger.step(StepStyle::StepInstIn).unwrap();
+        }
+    }
+
+    fn memory_at_16(debugger: &MainDebugger) -> Vec<u8> {
+        debugger.with_memory(|m| m[16..20].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_step_back_reverts_store() {
+        let debugger = launch();
+        step_insts(&debugger, 7);
+        assert_eq!(memory_at_16(&debugger), vec![42, 0, 0, 0]);
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(7)]);
+
+        for _ in 0..6 {
+            match debugger.step_back(StepStyle::StepInstIn).unwrap() {
+                Signal::Next => (),
+                _ => panic!("history should be recorded"),
+            }
+        }
+        // Back at the entry of `inner`
+        match debugger.step_back(StepStyle::StepInstIn).unwrap() {
+            Signal::Breakpoint => (),
+            _ => panic!("breakpoint should be hit in reverse"),
+        }
+        assert_eq!(memory_at_16(&debugger), vec![0, 0, 0, 0]);
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(0)]);
+        assert_eq!(
+            debugger.locals(),
+            vec![WasmValue::I32(7), WasmValue::I32(0)]
+        );
+    }
+
+    #[test]
+    fn test_step_back_reverts_host_write() {
+        let mut debugger = MainDebugger::new().unwrap();
+        let module = wat2wasm(
+            r#"
+            (module
+              (import "wasi_snapshot_preview1" "random_get"
+                (func $random_get (param i32 i32) (result i32)))
+              (memory 1)
+              (func $fill
+                i32.const 32
+                i32.const 16
+                call $random_get
+                drop)
+              (func $_start (export "_start")
+                call $fill))
+            "#,
+        );
+        debugger.load_module(&module).unwrap();
+        debugger.set_history_limit(100);
+        debugger.set_breakpoint(Breakpoint::Function {
+            name: "fill".to_string(),
+        });
+        debugger.run(None).unwrap();
+        step_insts(&debugger, 3);
+        let random = debugger.with_memory(|m| m[32..48].to_vec()).unwrap();
+        assert_ne!(random, vec![0; 16]);
+
+        debugger.step_back(StepStyle::StepInstIn).unwrap();
+        let reverted = debugger.with_memory(|m| m[32..48].to_vec()).unwrap();
+        assert_eq!(reverted, vec![0; 16]);
+    }
+
+    #[test]
+    fn test_edit_then_step_back() {
+        let mut debugger = launch();
+        step_insts(&debugger, 5);
+        assert_eq!(
+            debugger.locals(),
+            vec![WasmValue::I32(7), WasmValue::I32(7)]
+        );
+
+        debugger.set_local(1, WasmValue::I32(100)).unwrap();
+        debugger.write_memory(16, &[1, 2, 3, 4]).unwrap();
+        // The edit can't be reverted, so the history before it is dropped
+        match debugger.step_back(StepStyle::StepInstIn).unwrap() {
+            Signal::End => (),
+            _ => panic!("history should be cleared by the edit"),
+        }
+        assert_eq!(
+            debugger.locals(),
+            vec![WasmValue::I32(7), WasmValue::I32(100)]
+        );
+        assert_eq!(memory_at_16(&debugger), vec![1, 2, 3, 4]);
+
+        // Instructions after the edit are recorded again
+        step_insts(&debugger, 2);
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(100)]);
+        match debugger.step_back(StepStyle::StepInstIn).unwrap() {
+            Signal::Next => (),
+            _ => panic!("history should be recorded after the edit"),
+        }
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(0)]);
+    }
+
+    #[test]
+    fn test_step_out() {
+        let debugger = launch();
+        debugger.step(StepStyle::StepOut).unwrap();
+        assert_eq!(debugger.frame(), vec!["_start"]);
+        // The return value of `inner` is left for `drop`
+        assert_eq!(debugger.stack_values(), vec![WasmValue::I32(7)]);
+        assert_eq!(memory_at_16(&debugger), vec![42, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_write_state() {
+        let mut debugger = launch();
+        step_insts(&debugger, 2);
+        assert_eq!(
+            debugger.stack_values(),
+            vec![WasmValue::I32(16), WasmValue::I32(42)]
+        );
+        debugger.set_stack_value(1, WasmValue::I32(99)).unwrap();
+        debugger.set_local(0, WasmValue::I32(3)).unwrap();
+        step_insts(&debugger, 5);
+        assert_eq!(memory_at_16(&debugger), vec![99, 0, 0, 0]);
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(3)]);
+
+        debugger.set_global(0, WasmValue::I32(-1)).unwrap();
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(-1)]);
+        debugger.write_memory(18, &[1, 2]).unwrap();
+        assert_eq!(memory_at_16(&debugger), vec![99, 0, 1, 2]);
+
+        let err = debugger.set_global(0, WasmValue::I64(1)).unwrap_err();
+        assert_eq!(err.to_string(), "Type mismatch: expected i32 but got i64");
+        assert!(debugger.set_global(1, WasmValue::I32(1)).is_err());
+        assert!(debugger.set_local(2, WasmValue::I32(1)).is_err());
+        assert!(debugger.set_stack_value(5, WasmValue::I32(1)).is_err());
+        assert!(debugger.write_memory(65535, &[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_write_immutable_global() {
+        let mut debugger = MainDebugger::new().unwrap();
+        let module = wat2wasm(
+            r#"
+            (module
+              (global $c i32 (i32.const 1))
+              (func $f)
+              (func $_start (export "_start")
+                call $f))
+            "#,
+        );
+        debugger.load_module(&module).unwrap();
+        debugger.set_breakpoint(Breakpoint::Function {
+            name: "f".to_string(),
+        });
+        debugger.run(None).unwrap();
+        let err = debugger.set_global(0, WasmValue::I32(2)).unwrap_err();
+        assert_eq!(err.to_string(), "Global 0 is immutable");
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(1)]);
+    }
+
+    #[test]
+    fn test_step_back_without_history() {
+        let mut debugger = MainDebugger::new().unwrap();
+        debugger.load_module(&wat2wasm(MODULE)).unwrap();
+        debugger.set_breakpoint(Breakpoint::Function {
+            name: "inner".to_string(),
+        });
+        debugger.run(None).unwrap();
+        assert!(debugger.step_back(StepStyle::StepInstIn).is_err());
+        assert!(debugger.reverse_process().is_err());
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut debugger = launch();
+        debugger.set_watchpoint(16, 4).unwrap();
+        match debugger.process().unwrap() {
+            Signal::Breakpoint => (),
+            _ => panic!("watchpoint should be hit"),
+        }
+        assert_eq!(memory_at_16(&debugger), vec![42, 0, 0, 0]);
+
+        // Modification by the debugger doesn't hit the watchpoint
+        debugger.write_memory(16, &[1, 0, 0, 0]).unwrap();
+        match debugger.process().unwrap() {
+            Signal::End => (),
+            _ => panic!("process should finish"),
+        }
+    }
+
+    #[test]
+    fn test_watchpoint_in_reverse() {
+        let mut debugger = launch();
+        step_insts(&debugger, 7);
+        debugger.set_watchpoint(16, 4).unwrap();
+        match debugger.reverse_process().unwrap() {
+            Signal::Breakpoint => (),
+            _ => panic!("watchpoint should be hit in reverse"),
+        }
+        // Stopped before the store, which is reverted after the later instructions
+        assert_eq!(memory_at_16(&debugger), vec![0, 0, 0, 0]);
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(0)]);
+        assert_eq!(
+            debugger.locals(),
+            vec![WasmValue::I32(7), WasmValue::I32(0)]
+        );
+    }
+
+    #[test]
+    fn test_restore_checkpoint() {
+        let mut debugger = launch();
+        debugger.save_checkpoint("entry".to_string()).unwrap();
+        step_insts(&debugger, 7);
+        assert_eq!(memory_at_16(&debugger), vec![42, 0, 0, 0]);
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(7)]);
+
+        debugger.restore_checkpoint("entry").unwrap();
+        assert_eq!(memory_at_16(&debugger), vec![0, 0, 0, 0]);
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(0)]);
+        assert_eq!(debugger.frame(), vec!["_start", "inner"]);
+        assert_eq!(
+            debugger.locals(),
+            vec![WasmValue::I32(7), WasmValue::I32(0)]
+        );
+
+        // The restored process runs from the checkpoint again
+        debugger.set_local(0, WasmValue::I32(3)).unwrap();
+        step_insts(&debugger, 7);
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(3)]);
+
+        // The checkpoint is kept after it's restored
+        debugger.restore_checkpoint("entry").unwrap();
+        assert_eq!(debugger.globals(), vec![WasmValue::I32(0)]);
+    }
+
+    #[test]
+    fn test_restore_unknown_checkpoint() {
+        let mut debugger = launch();
+        assert!(debugger.restore_checkpoint("unknown").is_err());
+    }
+
+    fn run_trap(core_file: Option<&std::path::Path>) {
+        let mut debugger = MainDebugger::new().unwrap();
+        let module = wat2wasm(r#"(module (func $_start (export "_start") unreachable))"#);
+        debugger.load_module(&module).unwrap();
+        if let Some(path) = core_file {
+            debugger.set_trap_core_file(path.to_str().unwrap().to_string());
         }
+        assert!(debugger.run(None).is_err());
+    }
+
+    #[test]
+    fn test_core_on_trap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.core");
+        run_trap(Some(&path));
+        let core = std::fs::read(&path).unwrap();
+        assert!(core.starts_with(b"\0asm"));
+
+        // Existing files are kept
+        std::fs::write(&path, b"keep").unwrap();
+        run_trap(Some(&path));
+        assert_eq!(std::fs::read(&path).unwrap(), b"keep");
+    }
+
+    #[test]
+    fn test_no_core_by_default() {
+        // Run in an empty working directory, where a relative core file would be written
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        std::env::set_current_dir(dir.path()).unwrap();
+        run_trap(None);
+        std::env::set_current_dir(cwd).unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    fn trace_insts(filter: &str, count: usize) -> Vec<String> {
+        let mut debugger = launch();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.log");
+        debugger
+            .start_trace(debugger::TraceConfig {
+                style: debugger::TraceStyle::Insts,
+                filter: Some(filter.to_string()),
+                file: Some(path.to_str().unwrap().to_string()),
+            })
+            .unwrap();
+        step_insts(&debugger, count);
+        debugger.stop_trace().unwrap();
+        let trace = std::fs::read_to_string(&path).unwrap();
+        trace.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_trace_operands() {
+        let trace = trace_insts("inner", 5);
+        let operands: Vec<&str> = trace
+            .iter()
+            .map(|line| &line[line.find('[').unwrap()..])
+            .collect();
+        // i32.const, i32.const, i32.store, local.get and local.set
+        assert_eq!(
+            operands,
+            vec!["[]", "[]", "[I32(16), I32(42)]", "[]", "[I32(7)]"]
+        );
+    }
+
+    #[test]
+    fn test_trace_filter() {
+        assert!(trace_insts("_start", 5).is_empty());
+    }
+
+    /// `_start` echoes up to 8 bytes of stdin to stdout and stderr
+    const ECHO: &str = r#"
+    (module
+      (import "wasi_snapshot_preview1" "fd_read"
+        (func $fd_read (param i32 i32 i32 i32) (result i32)))
+      (import "wasi_snapshot_preview1" "fd_write"
+        (func $fd_write (param i32 i32 i32 i32) (result i32)))
+      (memory (export "memory") 1)
+      ;; iovec of the buffer at 16
+      (data (i32.const 0) "\10\00\00\00\08\00\00\00")
+      (func $echo (param $fd i32)
+        local.get $fd
+        i32.const 0
+        i32.const 1
+        i32.const 12
+        call $fd_write
+        drop)
+      (func $_start (export "_start")
+        i32.const 0
+        i32.const 0
+        i32.const 1
+        i32.const 8
+        call $fd_read
+        drop
+        ;; Write only the bytes read
+        i32.const 4
+        i32.const 8
+        i32.load
+        i32.store
+        i32.const 1
+        call $echo
+        i32.const 2
+        call $echo))
+    "#;
+
+    fn run_echo(stdio: debugger::ProcessStdio) -> MainDebugger {
+        let mut debugger = MainDebugger::new().unwrap();
+        debugger.load_module(&wat2wasm(ECHO)).unwrap();
+        debugger.set_stdio(stdio);
+        match debugger.run(None).unwrap() {
+            RunResult::Finish(_) => debugger,
+            _ => panic!("process should finish"),
+        }
+    }
+
+    #[test]
+    fn test_stdio_redirection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        std::fs::write(path("stdin"), b"hello\n").unwrap();
+
+        let debugger = run_echo(debugger::ProcessStdio {
+            stdin: Some(path("stdin")),
+            stdout: Some(path("stdout")),
+            stderr: Some(path("stderr")),
+            capture: false,
+        });
+        assert_eq!(std::fs::read(path("stdout")).unwrap(), b"hello\n");
+        assert_eq!(std::fs::read(path("stderr")).unwrap(), b"hello\n");
+        assert!(debugger.captured_output().is_err());
+    }
+
+    #[test]
+    fn test_capture_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        std::fs::write(path("stdin"), b"abc").unwrap();
+
+        // Only stderr, which isn't redirected to a file, is captured
+        let debugger = run_echo(debugger::ProcessStdio {
+            stdin: Some(path("stdin")),
+            stdout: Some(path("stdout")),
+            stderr: None,
+            capture: true,
+        });
+        assert_eq!(std::fs::read(path("stdout")).unwrap(), b"abc");
+        assert_eq!(debugger.captured_output().unwrap(), b"abc");
+
+        let debugger = run_echo(debugger::ProcessStdio {
+            stdin: Some(path("stdin")),
+            stdout: None,
+            stderr: None,
+            capture: true,
+        });
+        assert_eq!(debugger.captured_output().unwrap(), b"abcabc");
+    }
+
+    /// `count` increments the local until it reaches the parameter
+    const LOOP: &str = r#"
+    (module
+      (func $count (param $n i32) (result i32) (local $i i32)
+        loop
+          local.get $i
+          i32.const 1
+          i32.add
+          local.set $i
+          local.get $i
+          local.get $n
+          i32.lt_u
+          br_if 0
+        end
+        local.get $i)
+      (func $_start (export "_start")
+        i32.const 3
+        call $count
+        drop))
+    "#;
+
+    /// Source map which puts instructions on the lines of `lines` in an empty file.
+    /// Other instructions have no line.
+    struct LineMap {
+        filepath: String,
+        lines: HashMap<usize, u64>,
+    }
+
+    impl SourceMap for LineMap {
+        fn find_line_info(&self, offset: usize) -> Option<LineInfo> {
+            Some(LineInfo {
+                filepath: self.filepath.clone(),
+                line: self.lines.get(&offset).copied(),
+                column: ColumnType::LeftEdge,
+            })
+        }
+        fn line_infos(&self) -> Vec<LineInfo> {
+            vec![]
+        }
+        fn set_directory_map(&self, _: String, _: String) {}
+    }
+
+    /// Stop at the entry of `function` of the module, whose instructions are on separate lines
+    fn launch_with_lines(
+        wat: &str,
+        function: &str,
+    ) -> (MainDebugger, CommandContext<'static>, NamedTempFile) {
+        let mut debugger = MainDebugger::new().unwrap();
+        debugger.load_module(&wat2wasm(wat)).unwrap();
+        debugger.set_breakpoint(Breakpoint::Function {
+            name: function.to_string(),
+        });
+        debugger.run(None).unwrap();
+        let source = NamedTempFile::new().unwrap();
+        let (insts, _) = debugger.instructions().unwrap();
+        let lines = insts
+            .iter()
+            .enumerate()
+            .map(|(index, inst)| (inst.offset, index as u64 + 1))
+            .collect();
+        let context = CommandContext {
+            sourcemap: Box::new(LineMap {
+                filepath: source.path().to_str().unwrap().to_string(),
+                lines,
+            }),
+            subroutine: Box::new(EmptySubroutineMap::new()),
+        };
+        (debugger, context, source)
+    }
+
+    fn thread(debugger: &mut MainDebugger, context: &mut CommandContext, args: &str) {
+        let args = std::iter::once("thread").chain(args.split(' ')).collect();
+        ThreadCommand::new().run(debugger, context, args).unwrap();
+    }
+
+    fn next_inst_index(debugger: &MainDebugger) -> usize {
+        debugger.instructions().unwrap().1
+    }
+
+    #[test]
+    fn test_step_over_count() {
+        let (mut debugger, mut context, _source) = launch_with_lines(LOOP, "count");
+        thread(&mut debugger, &mut context, "step-over --count 3");
+        assert_eq!(next_inst_index(&debugger), 3);
+        thread(&mut debugger, &mut context, "step-over");
+        assert_eq!(next_inst_index(&debugger), 4);
+    }
+
+    #[test]
+    fn test_thread_until() {
+        let (mut debugger, mut context, _source) = launch_with_lines(LOOP, "count");
+        // `local.get $i` after the loop
+        thread(&mut debugger, &mut context, "until 11");
+        assert_eq!(debugger.frame(), vec!["_start", "count"]);
+        assert_eq!(next_inst_index(&debugger), 10);
+        assert_eq!(
+            debugger.locals(),
+            vec![WasmValue::I32(3), WasmValue::I32(3)]
+        );
+
+        // Stops when the frame returns before reaching the line
+        thread(&mut debugger, &mut context, "until 2");
+        assert_eq!(debugger.frame(), vec!["_start"]);
+        assert_eq!(debugger.stack_values(), vec![WasmValue::I32(3)]);
+    }
+
+    /// Subroutine map where the instructions in `range` are inlined from `inl`
+    struct InlinedMap {
+        range: std::ops::Range<usize>,
+    }
+
+    impl SubroutineMap for InlinedMap {
+        fn variable_name_list(&self, _: usize) -> Result<Vec<Variable>> {
+            Ok(vec![])
+        }
+        fn display_return_value(&self, _: usize, _: &WasmValue) -> Result<String> {
+            Err(anyhow!("No return type"))
+        }
+        fn evaluate_expression(&self, _: &FrameState, _: &str) -> Result<ExpressionResult> {
+            Err(anyhow!("No variables"))
+        }
+        fn set_max_depth(&self, _: usize) {}
+        fn inlined_frames(&self, code_offset: usize) -> Vec<InlinedFrame> {
+            if !self.range.contains(&code_offset) {
+                return vec![];
+            }
+            vec![InlinedFrame {
+                name: "inl".to_string(),
+                call_file: None,
+                call_line: Some(3),
+            }]
+        }
+    }
+
+    /// Stop at `i32.store` of `inner`, whose `local.get`, `local.set` and `local.get`
+    /// are inlined
+    fn launch_with_inlined() -> (MainDebugger, CommandContext<'static>, NamedTempFile) {
+        let (debugger, mut context, source) = launch_with_lines(MODULE, "inner");
+        let (insts, _) = debugger.instructions().unwrap();
+        context.subroutine = Box::new(InlinedMap {
+            range: insts[3].offset..insts[6].offset,
+        });
+        step_insts(&debugger, 2);
+        (debugger, context, source)
+    }
+
+    #[test]
+    fn test_step_over_inlined() {
+        let (mut debugger, mut context, _source) = launch_with_inlined();
+        thread(&mut debugger, &mut context, "step-over");
+        assert_eq!(next_inst_index(&debugger), 6);
+        assert_eq!(
+            debugger.locals(),
+            vec![WasmValue::I32(7), WasmValue::I32(7)]
+        );
+    }
+
+    #[test]
+    fn test_step_in_and_out_inlined() {
+        let (mut debugger, mut context, _source) = launch_with_inlined();
+        thread(&mut debugger, &mut context, "step-in");
+        assert_eq!(next_inst_index(&debugger), 3);
+        thread(&mut debugger, &mut context, "step-out");
+        // Back to `inner` instead of `_start`
+        assert_eq!(debugger.frame(), vec!["_start", "inner"]);
+        assert_eq!(next_inst_index(&debugger), 6);
+    }
+
+    fn run_stats(wat: &str) -> ExecutionStats {
+        let mut debugger = MainDebugger::new().unwrap();
+        debugger.load_module(&wat2wasm(wat)).unwrap();
+        match debugger.run(None).unwrap() {
+            RunResult::Finish(_) => debugger.stats().unwrap(),
+            _ => panic!("process should finish"),
+        }
+    }
+
+    #[test]
+    fn test_stats_calls() {
+        let debugger = launch();
+        debugger.process().unwrap();
+        let stats = debugger.stats().unwrap();
+        let calls: Vec<(String, u64)> = stats
+            .call_counts()
+            .iter()
+            .map(|(addr, count)| (debugger.store.func_global(*addr).name().clone(), *count))
+            .collect();
+        assert_eq!(calls, vec![("inner".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_stats_peak_memory() {
+        // The initial size is the peak without `memory.grow`
+        let stats = run_stats(r#"(module (memory 2) (func $_start (export "_start")))"#);
+        assert_eq!(stats.memory_grow_count(), 0);
+        assert_eq!(stats.peak_memory_pages(), 2);
+
+        let stats = run_stats(
+            r#"
+            (module
+              (memory 2)
+              (func $_start (export "_start")
+                i32.const 3
+                memory.grow
+                drop))
+            "#,
+        );
+        assert_eq!(stats.memory_grow_count(), 1);
+        assert_eq!(stats.peak_memory_pages(), 5);
     }
 }
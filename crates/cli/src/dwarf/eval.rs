@@ -0,0 +1,1665 @@
+use super::expr::{parse_expression, BinaryOp, Expr, TypeName, UnaryOp};
+use super::format::{
+    format_object, format_pointee, member_offset, read_bit_field, type_name, FormatOptions,
+    MAX_ARRAY_ELEMENTS,
+};
+use super::types::*;
+use super::{
+    evaluate_wasm_location, frame_scopes, LocationPiece, PieceLocation, Reader, Subroutine,
+    VariableContent, WasmLocation,
+};
+use crate::commands::subroutine::{ExpressionResult, FrameState, StateChange};
+use crate::commands::value::value_bytes;
+use anyhow::{anyhow, Result};
+use gimli::AttributeValue;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use wasminspect_vm::WasmValue;
+
+type TypeHash = HashMap<usize, TypeInfo<Reader>>;
+
+const POINTER_SIZE: u64 = 4;
+
+/// Types which don't come from DWARF, e.g. literals and wasm values
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuiltinType {
+    Bool,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+}
+
+impl BuiltinType {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::I32 => "int",
+            Self::U32 => "unsigned int",
+            Self::I64 => "long long",
+            Self::U64 => "unsigned long long",
+            Self::F32 => "float",
+            Self::F64 => "double",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let ty = match name {
+            "bool" | "_Bool" => Self::Bool,
+            "int" | "signed" | "signed int" => Self::I32,
+            "unsigned" | "unsigned int" => Self::U32,
+            "long long" | "long long int" => Self::I64,
+            "unsigned long long" | "unsigned long long int" => Self::U64,
+            "float" => Self::F32,
+            "double" => Self::F64,
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    fn from_wasm_value(value: &WasmValue) -> Self {
+        match value {
+            WasmValue::I32(_) => Self::I32,
+            WasmValue::I64(_) => Self::I64,
+            WasmValue::F32(_) => Self::F32,
+            WasmValue::F64(_) => Self::F64,
+        }
+    }
+
+    fn encoding(&self) -> (gimli::DwAte, u64) {
+        match self {
+            Self::Bool => (gimli::DW_ATE_boolean, 1),
+            Self::I32 => (gimli::DW_ATE_signed, 4),
+            Self::U32 => (gimli::DW_ATE_unsigned, 4),
+            Self::I64 => (gimli::DW_ATE_signed, 8),
+            Self::U64 => (gimli::DW_ATE_unsigned, 8),
+            Self::F32 => (gimli::DW_ATE_float, 4),
+            Self::F64 => (gimli::DW_ATE_float, 8),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ObjectType {
+    Dwarf(usize),
+    /// Pointer type which is not described in DWARF, e.g. the result of `&x`
+    Pointer(Box<ObjectType>),
+    /// Array type which is not described in DWARF, e.g. a row of a multi-dimensional array
+    Array(Box<ObjectType>, Option<u64>),
+    Builtin(BuiltinType),
+}
+
+#[derive(Clone, Debug)]
+pub enum ObjectLocation {
+    Memory(u64),
+    WasmLocal(usize),
+    WasmGlobal(usize),
+    /// Index from the bottom of the operand stack of the current function
+    WasmStack(usize),
+    Value(Vec<u8>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Object {
+    pub ty: ObjectType,
+    pub location: ObjectLocation,
+}
+
+enum TypeKind<'a> {
+    Base {
+        encoding: gimli::DwAte,
+        byte_size: u64,
+    },
+    Pointer(Option<ObjectType>),
+    Array(ObjectType, Option<u64>),
+    /// Struct or union type
+    Struct(&'a StructTypeInfo<Reader>),
+    Function,
+    Void,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Scalar {
+    Signed(i128),
+    Unsigned(u128),
+    Float(f64),
+}
+
+impl Scalar {
+    fn as_i128(self) -> i128 {
+        match self {
+            Self::Signed(v) => v,
+            Self::Unsigned(v) => v as i128,
+            Self::Float(v) => v as i128,
+        }
+    }
+
+    fn as_u128(self) -> u128 {
+        match self {
+            Self::Signed(v) => v as u128,
+            Self::Unsigned(v) => v,
+            Self::Float(v) => v as u128,
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Signed(v) => v as f64,
+            Self::Unsigned(v) => v as f64,
+            Self::Float(v) => v,
+        }
+    }
+
+    fn is_true(self) -> bool {
+        match self {
+            Self::Float(v) => v != 0.0,
+            _ => self.as_u128() != 0,
+        }
+    }
+}
+
+fn bytes_to_u128(bytes: &[u8]) -> u128 {
+    let mut buf = [0; 16];
+    let len = std::cmp::min(bytes.len(), 16);
+    buf[..len].copy_from_slice(&bytes[..len]);
+    u128::from_le_bytes(buf)
+}
+
+fn bytes_to_i128(bytes: &[u8]) -> i128 {
+    if bytes.is_empty() || bytes.len() >= 16 {
+        return bytes_to_u128(bytes) as i128;
+    }
+    let shift = 128 - bytes.len() * 8;
+    ((bytes_to_u128(bytes) << shift) as i128) >> shift
+}
+
+fn decode_scalar(encoding: gimli::DwAte, bytes: &[u8]) -> Result<Scalar> {
+    match encoding {
+        gimli::DW_ATE_float => match bytes.len() {
+            4 => Ok(Scalar::Float(
+                f32::from_bits(bytes_to_u128(bytes) as u32) as f64
+            )),
+            8 => Ok(Scalar::Float(f64::from_bits(bytes_to_u128(bytes) as u64))),
+            size => Err(anyhow!("{} bytes floating point is not supported", size)),
+        },
+        gimli::DW_ATE_signed | gimli::DW_ATE_signed_char => {
+            Ok(Scalar::Signed(bytes_to_i128(bytes)))
+        }
+        gimli::DW_ATE_unsigned
+        | gimli::DW_ATE_unsigned_char
+        | gimli::DW_ATE_boolean
+        | gimli::DW_ATE_UTF => Ok(Scalar::Unsigned(bytes_to_u128(bytes))),
+        _ => Err(anyhow!("{} is not supported", encoding)),
+    }
+}
+
+fn encode_scalar(encoding: gimli::DwAte, byte_size: u64, value: Scalar) -> Result<Vec<u8>> {
+    let bytes = match encoding {
+        gimli::DW_ATE_float => match byte_size {
+            4 => (value.as_f64() as f32).to_le_bytes().to_vec(),
+            8 => value.as_f64().to_le_bytes().to_vec(),
+            size => return Err(anyhow!("{} bytes floating point is not supported", size)),
+        },
+        gimli::DW_ATE_boolean => {
+            let mut bytes = vec![0; byte_size as usize];
+            if value.is_true() && !bytes.is_empty() {
+                bytes[0] = 1;
+            }
+            bytes
+        }
+        _ => {
+            let bytes = value.as_u128().to_le_bytes();
+            bytes.iter().take(byte_size as usize).cloned().collect()
+        }
+    };
+    Ok(bytes)
+}
+
+fn wasm_value_from_bytes(original: &WasmValue, bytes: &[u8]) -> WasmValue {
+    let bits = bytes_to_u128(bytes);
+    match original {
+        WasmValue::I32(_) => WasmValue::I32(bits as u32 as i32),
+        WasmValue::I64(_) => WasmValue::I64(bits as u64 as i64),
+        WasmValue::F32(_) => WasmValue::F32(f32::from_bits(bits as u32)),
+        WasmValue::F64(_) => WasmValue::F64(f64::from_bits(bits as u64)),
+    }
+}
+
+/// Address of the `index`th element of `size` bytes from `base`
+fn element_address(base: i128, index: i128, size: u64) -> Result<u64> {
+    let address = index
+        .checked_mul(size as i128)
+        .and_then(|offset| base.checked_add(offset))
+        .filter(|address| *address >= 0 && *address <= u64::MAX as i128);
+    match address {
+        Some(address) => Ok(address as u64),
+        None => Err(anyhow!("index {} is out of the address space", index)),
+    }
+}
+
+/// Address `index` elements of `size` bytes away from the pointer `base`
+fn pointer_offset(base: i128, index: i128, size: u64) -> Result<i128> {
+    index
+        .checked_mul(size as i128)
+        .and_then(|offset| base.checked_add(offset))
+        .filter(|address| *address >= 0 && *address <= u64::MAX as i128)
+        .ok_or_else(|| anyhow!("address overflow"))
+}
+
+pub struct Evaluator<'a> {
+    /// Subroutines which contain the current code offset, innermost first
+    subroutines: Vec<&'a Subroutine<Reader>>,
+    type_hash: &'a TypeHash,
+    encoding: gimli::Encoding,
+    frame: &'a FrameState<'a>,
+    /// Levels of pointers to follow when formatting the result
+    max_depth: usize,
+    /// Modifications requested by assignments, applied by the caller
+    changes: RefCell<Vec<StateChange>>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(
+        subroutines: &'a [Subroutine<Reader>],
+        type_hash: &'a TypeHash,
+        frame: &'a FrameState<'a>,
+        max_depth: usize,
+    ) -> Self {
+        let subroutines = frame_scopes(subroutines, frame.code_offset as u64);
+        let encoding = match subroutines.first() {
+            Some(subroutine) => subroutine.encoding,
+            None => gimli::Encoding {
+                format: gimli::Format::Dwarf32,
+                version: 4,
+                address_size: POINTER_SIZE as u8,
+            },
+        };
+        Self {
+            subroutines,
+            type_hash,
+            encoding,
+            frame,
+            max_depth,
+            changes: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn evaluate_and_format(&self, input: &str) -> Result<ExpressionResult> {
+        let expr = parse_expression(input, &|name| self.find_type(name).is_some())?;
+        let object = self.evaluate(&expr)?;
+        Ok(ExpressionResult {
+            output: self.format(&object)?,
+            changes: self.changes.replace(Vec::new()),
+        })
+    }
+
+    pub fn evaluate(&self, expr: &Expr) -> Result<Object> {
+        match expr {
+            Expr::Variable(name) => self.variable(name),
+            Expr::WasmLocal(index) => {
+                let value = self
+                    .frame
+                    .locals
+                    .get(*index)
+                    .ok_or(anyhow!("local {} is out of range", index))?;
+                Ok(Object {
+                    ty: ObjectType::Builtin(BuiltinType::from_wasm_value(value)),
+                    location: ObjectLocation::WasmLocal(*index),
+                })
+            }
+            Expr::WasmGlobal(index) => {
+                let value = self
+                    .frame
+                    .globals
+                    .get(*index)
+                    .ok_or(anyhow!("global {} is out of range", index))?;
+                Ok(Object {
+                    ty: ObjectType::Builtin(BuiltinType::from_wasm_value(value)),
+                    location: ObjectLocation::WasmGlobal(*index),
+                })
+            }
+            Expr::Integer {
+                value,
+                unsigned,
+                long,
+            } => {
+                let fits_in_32 = if *unsigned {
+                    *value <= u32::MAX as u64
+                } else {
+                    *value <= i32::MAX as u64
+                };
+                let ty = match (*unsigned, *long || !fits_in_32) {
+                    (false, false) => BuiltinType::I32,
+                    (true, false) => BuiltinType::U32,
+                    (false, true) => BuiltinType::I64,
+                    (true, true) => BuiltinType::U64,
+                };
+                self.builtin_value(ty, Scalar::Unsigned(*value as u128))
+            }
+            Expr::Float { value, single } => {
+                let ty = if *single {
+                    BuiltinType::F32
+                } else {
+                    BuiltinType::F64
+                };
+                self.builtin_value(ty, Scalar::Float(*value))
+            }
+            Expr::Member(base, member) => {
+                let base = self.evaluate(base)?;
+                self.member(&base, member)
+            }
+            Expr::Index(base, index) => {
+                let base = self.evaluate(base)?;
+                let index = self.scalar(&self.evaluate(index)?)?.as_i128();
+                self.index(&base, index)
+            }
+            Expr::Unary(op, operand) => {
+                let operand = self.evaluate(operand)?;
+                self.unary(*op, &operand)
+            }
+            Expr::Binary(op, lhs, rhs) => self.binary(*op, lhs, rhs),
+            Expr::Cast(type_name, operand) => {
+                let ty = self.resolve_type_name(type_name)?;
+                let operand = self.evaluate(operand)?;
+                self.cast(&operand, ty)
+            }
+            Expr::Assign(target, value) => {
+                let target = self.evaluate(target)?;
+                let value = self.evaluate(value)?;
+                self.assign(&target, &value)
+            }
+        }
+    }
+
+    fn assign(&self, target: &Object, value: &Object) -> Result<Object> {
+        let bytes = match self.kind(&target.ty)? {
+            TypeKind::Struct(_) | TypeKind::Array(..) => {
+                if self.type_name(&target.ty)? != self.type_name(&value.ty)? {
+                    return Err(anyhow!(
+                        "can't assign '{}' to '{}'",
+                        self.type_name(&value.ty)?,
+                        self.type_name(&target.ty)?
+                    ));
+                }
+                self.read_bytes(value, self.size_of(&target.ty)?)?
+            }
+            _ => match self.cast(value, target.ty.clone())?.location {
+                ObjectLocation::Value(bytes) => bytes,
+                _ => unreachable!(),
+            },
+        };
+        let change = match target.location {
+            ObjectLocation::Memory(address) => StateChange::Memory {
+                address: address as usize,
+                bytes: bytes.clone(),
+            },
+            ObjectLocation::WasmLocal(index) => StateChange::Local {
+                index,
+                value: wasm_value_from_bytes(&self.frame.locals[index], &bytes),
+            },
+            ObjectLocation::WasmGlobal(index) => StateChange::Global {
+                index,
+                value: wasm_value_from_bytes(&self.frame.globals[index], &bytes),
+            },
+            ObjectLocation::WasmStack(index) => StateChange::Stack {
+                index,
+                value: wasm_value_from_bytes(&self.frame.stack[index], &bytes),
+            },
+            ObjectLocation::Value(_) => return Err(anyhow!("expression is not assignable")),
+        };
+        self.changes.borrow_mut().push(change);
+        Ok(Object {
+            ty: target.ty.clone(),
+            location: ObjectLocation::Value(bytes),
+        })
+    }
+
+    fn builtin_value(&self, ty: BuiltinType, value: Scalar) -> Result<Object> {
+        let (encoding, byte_size) = ty.encoding();
+        Ok(Object {
+            ty: ObjectType::Builtin(ty),
+            location: ObjectLocation::Value(encode_scalar(encoding, byte_size, value)?),
+        })
+    }
+
+    fn variable(&self, name: &str) -> Result<Object> {
+        let var = self
+            .subroutines
+            .iter()
+            .flat_map(|s| s.variables.iter().map(move |v| (*s, v)))
+            .find(|(_, v)| v.name.as_ref().map(|n| n == name).unwrap_or(false));
+        let (subroutine, var) = match var {
+            Some(var) => var,
+            None => return Err(anyhow!("'{}' is not valid variable name", name)),
+        };
+        let ty = match var.ty_offset {
+            Some(offset) => ObjectType::Dwarf(offset),
+            None => return Err(anyhow!("'{}' has no explicit type", name)),
+        };
+        let location = match &var.content {
+            VariableContent::Location(AttributeValue::Exprloc(expr)) => {
+                self.expression_location(name, subroutine, expr.clone())?
+            }
+            VariableContent::LocationList(entries) => {
+                let offset = self.frame.code_offset as u64;
+                let entry = entries
+                    .iter()
+                    .find(|entry| entry.range.begin <= offset && offset < entry.range.end)
+                    .ok_or(anyhow!("'{}' is optimized out", name))?;
+                self.expression_location(name, subroutine, entry.data.clone())?
+            }
+            VariableContent::Location(attr) => {
+                return Err(anyhow!("unsupported location attribute {:?}", attr))
+            }
+            VariableContent::ConstValue(bytes) => ObjectLocation::Value(bytes.clone()),
+            VariableContent::Unknown { debug_info } => {
+                return Err(anyhow!("Unknown variable content found {}", debug_info))
+            }
+        };
+        Ok(Object { ty, location })
+    }
+
+    fn expression_location(
+        &self,
+        name: &str,
+        subroutine: &Subroutine<Reader>,
+        expr: gimli::Expression<Reader>,
+    ) -> Result<ObjectLocation> {
+        let frame_base = self.frame_base(subroutine)?;
+        let read_wasm = |location| Ok(self.wasm_location(name, location)?.1);
+        let pieces = evaluate_wasm_location(subroutine.encoding, frame_base, expr, &read_wasm)?;
+        match pieces.as_slice() {
+            [] => Err(anyhow!("'{}' is optimized out", name)),
+            [piece] if piece.size_in_bits.is_none() => self.piece_location(name, piece),
+            // Composite location is assembled into a value
+            _ => {
+                let mut bytes = vec![];
+                for piece in &pieces {
+                    let size_in_bits = piece.size_in_bits.unwrap_or(0);
+                    if size_in_bits % 8 != 0 || piece.bit_offset.unwrap_or(0) != 0 {
+                        return Err(anyhow!("'{}' has a piece which is not byte aligned", name));
+                    }
+                    let location = self.piece_location(name, piece)?;
+                    bytes.extend(self.read_location(&location, size_in_bits / 8)?);
+                }
+                Ok(ObjectLocation::Value(bytes))
+            }
+        }
+    }
+
+    fn piece_location(&self, name: &str, piece: &LocationPiece<Reader>) -> Result<ObjectLocation> {
+        match &piece.location {
+            PieceLocation::Wasm(location) => Ok(self.wasm_location(name, *location)?.0),
+            PieceLocation::Dwarf(gimli::Location::Address { address }) => {
+                Ok(ObjectLocation::Memory(*address))
+            }
+            PieceLocation::Dwarf(gimli::Location::Value { value }) => Ok(ObjectLocation::Value(
+                value.to_u64(!0)?.to_le_bytes().to_vec(),
+            )),
+            PieceLocation::Dwarf(gimli::Location::Empty) => {
+                Err(anyhow!("'{}' is optimized out", name))
+            }
+            location => Err(anyhow!("unsupported variable location {:?}", location)),
+        }
+    }
+
+    /// Evaluate `DW_AT_frame_base` of the subroutine which `DW_OP_fbreg` refers to
+    fn frame_base(&self, subroutine: &Subroutine<Reader>) -> Result<Option<u64>> {
+        let expr = match &subroutine.frame_base {
+            Some(expr) => expr.clone(),
+            None => return Ok(None),
+        };
+        let read_wasm = |location| Ok(self.wasm_location("frame base", location)?.1);
+        let pieces = evaluate_wasm_location(subroutine.encoding, None, expr, &read_wasm)?;
+        match pieces.into_iter().next().map(|piece| piece.location) {
+            Some(PieceLocation::Wasm(location)) => Ok(Some(read_wasm(location)?)),
+            Some(PieceLocation::Dwarf(gimli::Location::Address { address })) => Ok(Some(address)),
+            Some(PieceLocation::Dwarf(gimli::Location::Value { value })) => {
+                Ok(Some(value.to_u64(!0)?))
+            }
+            location => Err(anyhow!("unsupported frame base location {:?}", location)),
+        }
+    }
+
+    /// Storage referred by `DW_OP_WASM_location` and the value stored in it
+    fn wasm_location(&self, name: &str, location: WasmLocation) -> Result<(ObjectLocation, u64)> {
+        let (object_location, value) = match location {
+            WasmLocation::Local(index) => (
+                ObjectLocation::WasmLocal(index),
+                self.frame.locals.get(index),
+            ),
+            WasmLocation::Global(index) => (
+                ObjectLocation::WasmGlobal(index),
+                self.frame.globals.get(index),
+            ),
+            WasmLocation::Stack(index) => (
+                ObjectLocation::WasmStack(index),
+                self.frame.stack.get(index),
+            ),
+        };
+        let value = value.ok_or(anyhow!(
+            "'{}' is in {:?} which doesn't exist",
+            name,
+            location
+        ))?;
+        Ok((object_location, bytes_to_u128(&value_bytes(value)) as u64))
+    }
+
+    fn member(&self, base: &Object, name: &str) -> Result<Object> {
+        let struct_type = match self.kind(&base.ty)? {
+            TypeKind::Struct(struct_type) => struct_type,
+            _ => {
+                return Err(anyhow!(
+                    "'{}' is not a struct type",
+                    self.type_name(&base.ty)?
+                ))
+            }
+        };
+        if struct_type.declaration {
+            return Err(anyhow!(
+                "'{}' is an incomplete type",
+                self.type_name(&base.ty)?
+            ));
+        }
+        let member = struct_type
+            .members
+            .iter()
+            .find(|m| m.name.as_ref().map(|n| n == name).unwrap_or(false))
+            .ok_or(anyhow!(
+                "'{}' has no member named '{}'",
+                self.type_name(&base.ty)?,
+                name
+            ))?;
+        let offset = member_offset(member, self.encoding)?;
+        let ty = ObjectType::Dwarf(member.ty);
+        if let Some(bit_size) = member.bit_size {
+            let bit_offset = offset * 8 + member.data_bit_offset.unwrap_or(0);
+            let bytes = self.read_bytes(base, struct_type.byte_size)?;
+            let value = read_bit_field(&bytes, bit_offset, bit_size)?;
+            let value = match self.kind(&ty)? {
+                TypeKind::Base { encoding, .. }
+                    if encoding == gimli::DW_ATE_signed
+                        || encoding == gimli::DW_ATE_signed_char =>
+                {
+                    let shift = 128 - bit_size;
+                    Scalar::Signed(((value << shift) as i128) >> shift)
+                }
+                _ => Scalar::Unsigned(value),
+            };
+            // Bit-fields are not addressable, so the result is a plain value
+            return self.typed_value(ty, value);
+        }
+        let location = match &base.location {
+            ObjectLocation::Memory(address) => ObjectLocation::Memory(
+                address
+                    .checked_add(offset)
+                    .ok_or(anyhow!("member address overflows"))?,
+            ),
+            ObjectLocation::Value(bytes) => {
+                let size = self.size_of(&ty)?;
+                let bytes = bytes
+                    .iter()
+                    .skip(offset as usize)
+                    .take(size as usize)
+                    .cloned()
+                    .collect();
+                ObjectLocation::Value(bytes)
+            }
+            _ => return Err(anyhow!("wasm value can't have members")),
+        };
+        Ok(Object { ty, location })
+    }
+
+    fn index(&self, base: &Object, index: i128) -> Result<Object> {
+        match self.kind(&base.ty)? {
+            TypeKind::Pointer(Some(pointee)) => {
+                let address = self.scalar(base)?.as_i128();
+                let size = self.size_of(&pointee)?;
+                Ok(Object {
+                    ty: pointee,
+                    location: ObjectLocation::Memory(element_address(address, index, size)?),
+                })
+            }
+            TypeKind::Array(element, count) => {
+                let size = self.size_of(&element)?;
+                let location = match &base.location {
+                    ObjectLocation::Memory(address) => {
+                        ObjectLocation::Memory(element_address(*address as i128, index, size)?)
+                    }
+                    ObjectLocation::Value(bytes) => {
+                        let in_bounds = match count {
+                            Some(count) => index >= 0 && (index as u64) < count,
+                            None => false,
+                        };
+                        if !in_bounds {
+                            return Err(anyhow!("array index {} is out of bounds", index));
+                        }
+                        let begin = (index as u64)
+                            .checked_mul(size)
+                            .ok_or(anyhow!("array index {} is out of bounds", index))?;
+                        let element = begin
+                            .checked_add(size)
+                            .and_then(|end| bytes.get(begin as usize..end as usize))
+                            .ok_or(anyhow!("array index {} is out of bounds", index))?;
+                        ObjectLocation::Value(element.to_vec())
+                    }
+                    _ => return Err(anyhow!("wasm value can't be subscripted")),
+                };
+                Ok(Object {
+                    ty: element,
+                    location,
+                })
+            }
+            _ => Err(anyhow!(
+                "subscripted value '{}' is not an array or pointer",
+                self.type_name(&base.ty)?
+            )),
+        }
+    }
+
+    fn unary(&self, op: UnaryOp, operand: &Object) -> Result<Object> {
+        match op {
+            UnaryOp::Deref => match self.kind(&operand.ty)? {
+                TypeKind::Pointer(Some(pointee)) => {
+                    let address = self.scalar(operand)?.as_u128() as u64;
+                    Ok(Object {
+                        ty: pointee,
+                        location: ObjectLocation::Memory(address),
+                    })
+                }
+                TypeKind::Pointer(None) => Err(anyhow!("can't dereference void pointer")),
+                TypeKind::Array(..) => self.index(operand, 0),
+                _ => Err(anyhow!(
+                    "can't dereference non-pointer type '{}'",
+                    self.type_name(&operand.ty)?
+                )),
+            },
+            UnaryOp::AddrOf => match operand.location {
+                ObjectLocation::Memory(address) => Ok(Object {
+                    ty: ObjectType::Pointer(Box::new(operand.ty.clone())),
+                    location: ObjectLocation::Value((address as u32).to_le_bytes().to_vec()),
+                }),
+                _ => Err(anyhow!("can't take address of value not in linear memory")),
+            },
+            UnaryOp::Not => {
+                let value = self.scalar(operand)?;
+                self.builtin_value(
+                    BuiltinType::Bool,
+                    Scalar::Unsigned(!value.is_true() as u128),
+                )
+            }
+            UnaryOp::Neg | UnaryOp::BitNot => {
+                let ty = self.arithmetic_type(&operand.ty, &operand.ty)?;
+                let value = self.scalar(operand)?;
+                let result = match (op, value) {
+                    (UnaryOp::Neg, Scalar::Float(v)) => Scalar::Float(-v),
+                    (UnaryOp::Neg, v) => Scalar::Signed(v.as_i128().wrapping_neg()),
+                    (UnaryOp::BitNot, Scalar::Float(_)) => {
+                        return Err(anyhow!("invalid operand to bitwise not"))
+                    }
+                    (_, v) => Scalar::Unsigned(!v.as_u128()),
+                };
+                self.typed_value(ty, result)
+            }
+        }
+    }
+
+    fn binary(&self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> Result<Object> {
+        let lhs = self.evaluate(lhs)?;
+        if op == BinaryOp::And || op == BinaryOp::Or {
+            let lhs_value = self.scalar(&lhs)?.is_true();
+            let result = match (op, lhs_value) {
+                (BinaryOp::And, false) => false,
+                (BinaryOp::Or, true) => true,
+                _ => self.scalar(&self.evaluate(rhs)?)?.is_true(),
+            };
+            return self.builtin_value(BuiltinType::Bool, Scalar::Unsigned(result as u128));
+        }
+        let rhs = self.evaluate(rhs)?;
+        let lhs_pointee = match self.kind(&lhs.ty)? {
+            TypeKind::Pointer(pointee) => Some(pointee),
+            _ => None,
+        };
+        let rhs_pointee = match self.kind(&rhs.ty)? {
+            TypeKind::Pointer(pointee) => Some(pointee),
+            _ => None,
+        };
+        let lhs_value = self.scalar(&lhs)?;
+        let rhs_value = self.scalar(&rhs)?;
+
+        // Pointer arithmetic
+        match (op, &lhs_pointee, &rhs_pointee) {
+            (BinaryOp::Add, Some(pointee), None) | (BinaryOp::Sub, Some(pointee), None) => {
+                let size = self.pointee_size(pointee)?;
+                let index = if op == BinaryOp::Add {
+                    rhs_value.as_i128()
+                } else {
+                    rhs_value
+                        .as_i128()
+                        .checked_neg()
+                        .ok_or_else(|| anyhow!("address overflow"))?
+                };
+                let address = pointer_offset(lhs_value.as_i128(), index, size)?;
+                return self.typed_value(lhs.ty.clone(), Scalar::Signed(address));
+            }
+            (BinaryOp::Add, None, Some(pointee)) => {
+                let size = self.pointee_size(pointee)?;
+                let address = pointer_offset(rhs_value.as_i128(), lhs_value.as_i128(), size)?;
+                return self.typed_value(rhs.ty.clone(), Scalar::Signed(address));
+            }
+            (BinaryOp::Sub, Some(pointee), Some(_)) => {
+                let size = self.pointee_size(pointee)? as i128;
+                if size == 0 {
+                    return Err(anyhow!("subtraction of pointers to a zero-sized type"));
+                }
+                let diff = lhs_value
+                    .as_i128()
+                    .checked_sub(rhs_value.as_i128())
+                    .ok_or_else(|| anyhow!("address overflow"))?
+                    / size;
+                return self.builtin_value(BuiltinType::I32, Scalar::Signed(diff));
+            }
+            _ => (),
+        }
+
+        let is_comparison = matches!(
+            op,
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge | BinaryOp::Eq | BinaryOp::Ne
+        );
+        if is_comparison {
+            use std::cmp::Ordering;
+            let ordering = if lhs_pointee.is_some() || rhs_pointee.is_some() {
+                lhs_value.as_u128().partial_cmp(&rhs_value.as_u128())
+            } else {
+                match self.arithmetic_value(&lhs.ty, &rhs.ty, lhs_value, rhs_value)? {
+                    (Scalar::Float(l), Scalar::Float(r)) => l.partial_cmp(&r),
+                    (Scalar::Unsigned(l), Scalar::Unsigned(r)) => l.partial_cmp(&r),
+                    (l, r) => l.as_i128().partial_cmp(&r.as_i128()),
+                }
+            };
+            let result = match ordering {
+                Some(ordering) => match op {
+                    BinaryOp::Lt => ordering == Ordering::Less,
+                    BinaryOp::Gt => ordering == Ordering::Greater,
+                    BinaryOp::Le => ordering != Ordering::Greater,
+                    BinaryOp::Ge => ordering != Ordering::Less,
+                    BinaryOp::Eq => ordering == Ordering::Equal,
+                    _ => ordering != Ordering::Equal,
+                },
+                // NaN is unordered
+                None => op == BinaryOp::Ne,
+            };
+            return self.builtin_value(BuiltinType::Bool, Scalar::Unsigned(result as u128));
+        }
+
+        if lhs_pointee.is_some() || rhs_pointee.is_some() {
+            return Err(anyhow!("invalid operands to binary expression"));
+        }
+        let (ty, l, r) = match op {
+            // The type of shift result is the promoted type of the left operand
+            BinaryOp::Shl | BinaryOp::Shr => {
+                let ty = self.arithmetic_type(&lhs.ty, &lhs.ty)?;
+                (ty, lhs_value, rhs_value)
+            }
+            _ => {
+                let ty = self.arithmetic_type(&lhs.ty, &rhs.ty)?;
+                let (l, r) = self.arithmetic_value(&lhs.ty, &rhs.ty, lhs_value, rhs_value)?;
+                (ty, l, r)
+            }
+        };
+        let result = match (l, r) {
+            (Scalar::Float(l), Scalar::Float(r)) => match op {
+                BinaryOp::Add => Scalar::Float(l + r),
+                BinaryOp::Sub => Scalar::Float(l - r),
+                BinaryOp::Mul => Scalar::Float(l * r),
+                BinaryOp::Div => Scalar::Float(l / r),
+                _ => return Err(anyhow!("invalid operands to binary expression")),
+            },
+            (Scalar::Float(_), _) | (_, Scalar::Float(_)) => {
+                return Err(anyhow!("invalid operands to binary expression"))
+            }
+            (l, r) => {
+                let is_signed = matches!(l, Scalar::Signed(_));
+                let (lv, rv) = (l.as_i128(), r.as_i128());
+                let (lu, ru) = (l.as_u128(), r.as_u128());
+                if (op == BinaryOp::Div || op == BinaryOp::Rem) && ru == 0 {
+                    return Err(anyhow!("division by zero"));
+                }
+                match op {
+                    BinaryOp::Add => Scalar::Unsigned(lu.wrapping_add(ru)),
+                    BinaryOp::Sub => Scalar::Unsigned(lu.wrapping_sub(ru)),
+                    BinaryOp::Mul => Scalar::Unsigned(lu.wrapping_mul(ru)),
+                    BinaryOp::Div if is_signed => Scalar::Signed(lv.wrapping_div(rv)),
+                    BinaryOp::Div => Scalar::Unsigned(lu / ru),
+                    BinaryOp::Rem if is_signed => Scalar::Signed(lv.wrapping_rem(rv)),
+                    BinaryOp::Rem => Scalar::Unsigned(lu % ru),
+                    BinaryOp::Shl => Scalar::Unsigned(lu.wrapping_shl(ru as u32)),
+                    BinaryOp::Shr if is_signed => Scalar::Signed(lv.wrapping_shr(ru as u32)),
+                    BinaryOp::Shr => Scalar::Unsigned(lu.wrapping_shr(ru as u32)),
+                    BinaryOp::BitAnd => Scalar::Unsigned(lu & ru),
+                    BinaryOp::BitXor => Scalar::Unsigned(lu ^ ru),
+                    BinaryOp::BitOr => Scalar::Unsigned(lu | ru),
+                    _ => unreachable!(),
+                }
+            }
+        };
+        self.typed_value(ty, result)
+    }
+
+    fn pointee_size(&self, pointee: &Option<ObjectType>) -> Result<u64> {
+        match pointee {
+            Some(ty) => self.size_of(ty),
+            None => Err(anyhow!("arithmetic on a pointer to void")),
+        }
+    }
+
+    /// Determines the result type by the usual arithmetic conversions
+    fn arithmetic_type(&self, lhs: &ObjectType, rhs: &ObjectType) -> Result<ObjectType> {
+        let rank = |ty: &ObjectType| -> Result<(bool, u64, bool)> {
+            match self.kind(ty)? {
+                TypeKind::Base {
+                    encoding,
+                    byte_size,
+                } => Ok((
+                    encoding == gimli::DW_ATE_float,
+                    byte_size,
+                    encoding != gimli::DW_ATE_signed && encoding != gimli::DW_ATE_signed_char,
+                )),
+                _ => Err(anyhow!(
+                    "'{}' is not an arithmetic type",
+                    self.type_name(ty)?
+                )),
+            }
+        };
+        let (lhs_float, lhs_size, lhs_unsigned) = rank(lhs)?;
+        let (rhs_float, rhs_size, rhs_unsigned) = rank(rhs)?;
+        let (ty, is_float, size) = if lhs_float != rhs_float {
+            if lhs_float {
+                (lhs, lhs_float, lhs_size)
+            } else {
+                (rhs, rhs_float, rhs_size)
+            }
+        } else if lhs_size != rhs_size {
+            if lhs_size > rhs_size {
+                (lhs, lhs_float, lhs_size)
+            } else {
+                (rhs, rhs_float, rhs_size)
+            }
+        } else if rhs_unsigned && !lhs_unsigned {
+            (rhs, rhs_float, rhs_size)
+        } else {
+            (lhs, lhs_float, lhs_size)
+        };
+        // Integer promotion
+        if !is_float && size < 4 {
+            return Ok(ObjectType::Builtin(BuiltinType::I32));
+        }
+        Ok(ty.clone())
+    }
+
+    /// Converts both operands into the class of their common type
+    fn arithmetic_value(
+        &self,
+        lhs_ty: &ObjectType,
+        rhs_ty: &ObjectType,
+        lhs: Scalar,
+        rhs: Scalar,
+    ) -> Result<(Scalar, Scalar)> {
+        let ty = self.arithmetic_type(lhs_ty, rhs_ty)?;
+        let values = match self.kind(&ty)? {
+            TypeKind::Base { encoding, .. } if encoding == gimli::DW_ATE_float => {
+                (Scalar::Float(lhs.as_f64()), Scalar::Float(rhs.as_f64()))
+            }
+            TypeKind::Base { encoding, .. }
+                if encoding == gimli::DW_ATE_signed || encoding == gimli::DW_ATE_signed_char =>
+            {
+                (Scalar::Signed(lhs.as_i128()), Scalar::Signed(rhs.as_i128()))
+            }
+            _ => (
+                Scalar::Unsigned(lhs.as_u128()),
+                Scalar::Unsigned(rhs.as_u128()),
+            ),
+        };
+        Ok(values)
+    }
+
+    fn typed_value(&self, ty: ObjectType, value: Scalar) -> Result<Object> {
+        let bytes = match self.kind(&ty)? {
+            TypeKind::Base {
+                encoding,
+                byte_size,
+            } => encode_scalar(encoding, byte_size, value)?,
+            TypeKind::Pointer(_) => encode_scalar(gimli::DW_ATE_unsigned, POINTER_SIZE, value)?,
+            _ => return Err(anyhow!("'{}' is not a scalar type", self.type_name(&ty)?)),
+        };
+        Ok(Object {
+            ty,
+            location: ObjectLocation::Value(bytes),
+        })
+    }
+
+    fn cast(&self, operand: &Object, ty: ObjectType) -> Result<Object> {
+        let value = self.scalar(operand)?;
+        let value = match self.kind(&ty)? {
+            TypeKind::Base { encoding, .. } if encoding == gimli::DW_ATE_float => {
+                Scalar::Float(value.as_f64())
+            }
+            _ => value,
+        };
+        self.typed_value(ty, value)
+    }
+
+    fn scalar(&self, object: &Object) -> Result<Scalar> {
+        match self.kind(&object.ty)? {
+            TypeKind::Base {
+                encoding,
+                byte_size,
+            } => decode_scalar(encoding, &self.read_bytes(object, byte_size)?),
+            TypeKind::Pointer(_) => Ok(Scalar::Unsigned(bytes_to_u128(
+                &self.read_bytes(object, POINTER_SIZE)?,
+            ))),
+            _ => Err(anyhow!(
+                "'{}' is not a scalar type",
+                self.type_name(&object.ty)?
+            )),
+        }
+    }
+
+    fn read_bytes(&self, object: &Object, size: u64) -> Result<Vec<u8>> {
+        self.read_location(&object.location, size)
+    }
+
+    fn read_location(&self, location: &ObjectLocation, size: u64) -> Result<Vec<u8>> {
+        let mut bytes = match location {
+            ObjectLocation::Memory(address) => {
+                let bytes = address
+                    .checked_add(size)
+                    .and_then(|end| self.frame.memory.get(*address as usize..end as usize));
+                match bytes {
+                    Some(bytes) => bytes.to_vec(),
+                    None => return Err(anyhow!("failed to read memory at 0x{:x}", address)),
+                }
+            }
+            ObjectLocation::WasmLocal(index) => value_bytes(
+                self.frame
+                    .locals
+                    .get(*index)
+                    .ok_or(anyhow!("local {} is out of range", index))?,
+            ),
+            ObjectLocation::WasmGlobal(index) => value_bytes(
+                self.frame
+                    .globals
+                    .get(*index)
+                    .ok_or(anyhow!("global {} is out of range", index))?,
+            ),
+            ObjectLocation::WasmStack(index) => value_bytes(
+                self.frame
+                    .stack
+                    .get(*index)
+                    .ok_or(anyhow!("stack value {} is out of range", index))?,
+            ),
+            ObjectLocation::Value(bytes) => bytes.clone(),
+        };
+        bytes.resize(size as usize, 0);
+        Ok(bytes)
+    }
+
+    fn kind(&self, ty: &ObjectType) -> Result<TypeKind<'a>> {
+        let offset = match ty {
+            ObjectType::Dwarf(offset) => *offset,
+            ObjectType::Pointer(pointee) => {
+                return Ok(TypeKind::Pointer(Some(pointee.as_ref().clone())))
+            }
+            ObjectType::Array(element, count) => {
+                return Ok(TypeKind::Array(element.as_ref().clone(), *count))
+            }
+            ObjectType::Builtin(builtin) => {
+                let (encoding, byte_size) = builtin.encoding();
+                return Ok(TypeKind::Base {
+                    encoding,
+                    byte_size,
+                });
+            }
+        };
+        let type_hash: &'a TypeHash = self.type_hash;
+        let kind = match type_hash.get(&offset) {
+            Some(TypeInfo::BaseType(base_type)) => TypeKind::Base {
+                encoding: base_type.encoding,
+                byte_size: base_type.byte_size,
+            },
+            Some(TypeInfo::StructType(struct_type)) | Some(TypeInfo::UnionType(struct_type)) => {
+                TypeKind::Struct(struct_type)
+            }
+            Some(TypeInfo::ArrayType(array_type)) => {
+                let mut element = match array_type.ty {
+                    Some(ty) => ObjectType::Dwarf(ty),
+                    None => return Err(anyhow!("array element type is unknown")),
+                };
+                // The first dimension is the outermost one
+                let count = array_type.dimensions.first().cloned().flatten();
+                for inner_count in array_type.dimensions.iter().skip(1).rev() {
+                    element = ObjectType::Array(Box::new(element), *inner_count);
+                }
+                TypeKind::Array(element, count)
+            }
+            Some(TypeInfo::SubroutineType(_)) => TypeKind::Function,
+            Some(TypeInfo::TypeDef(type_def)) => match type_def.ty {
+                Some(ty) => self.kind(&ObjectType::Dwarf(ty))?,
+                None => TypeKind::Void,
+            },
+            Some(TypeInfo::EnumerationType(enum_type)) => match enum_type.ty {
+                Some(ty) => self.kind(&ObjectType::Dwarf(ty))?,
+                None => TypeKind::Base {
+                    encoding: gimli::DW_ATE_signed,
+                    byte_size: 4,
+                },
+            },
+            Some(TypeInfo::ModifiedType(mod_type)) => match mod_type.kind {
+                ModifierKind::Pointer | ModifierKind::Reference | ModifierKind::RvalueReference => {
+                    TypeKind::Pointer(mod_type.content_ty_offset.map(ObjectType::Dwarf))
+                }
+                _ => match mod_type.content_ty_offset {
+                    Some(ty) => self.kind(&ObjectType::Dwarf(ty))?,
+                    None => TypeKind::Void,
+                },
+            },
+            None => return Err(anyhow!("Failed to get type from offset '{}'", offset)),
+        };
+        Ok(kind)
+    }
+
+    fn size_of(&self, ty: &ObjectType) -> Result<u64> {
+        match self.kind(ty)? {
+            TypeKind::Base { byte_size, .. } => Ok(byte_size),
+            TypeKind::Pointer(_) => Ok(POINTER_SIZE),
+            TypeKind::Array(element, Some(count)) => Ok(self.size_of(&element)? * count),
+            TypeKind::Array(_, None) => Err(anyhow!(
+                "'{}' is an incomplete array type",
+                self.type_name(ty)?
+            )),
+            TypeKind::Struct(struct_type) => Ok(struct_type.byte_size),
+            TypeKind::Function => Err(anyhow!("function type has no size")),
+            TypeKind::Void => Err(anyhow!("void has no size")),
+        }
+    }
+
+    fn type_name(&self, ty: &ObjectType) -> Result<String> {
+        match ty {
+            ObjectType::Dwarf(offset) => type_name(Some(*offset), self.type_hash),
+            ObjectType::Pointer(pointee) => Ok(format!("{}*", self.type_name(pointee)?)),
+            ObjectType::Array(..) => {
+                let mut ty = ty;
+                let mut dimensions = String::new();
+                while let ObjectType::Array(element, count) = ty {
+                    match count {
+                        Some(count) => dimensions.push_str(&format!("[{}]", count)),
+                        None => dimensions.push_str("[]"),
+                    }
+                    ty = element;
+                }
+                Ok(format!("{}{}", self.type_name(ty)?, dimensions))
+            }
+            ObjectType::Builtin(builtin) => Ok(builtin.name().to_string()),
+        }
+    }
+
+    fn find_type(&self, name: &str) -> Option<ObjectType> {
+        let name = ["struct ", "union ", "enum ", "class "]
+            .iter()
+            .fold(name, |name, prefix| name.trim_start_matches(prefix));
+        let mut offsets: Vec<usize> = self
+            .type_hash
+            .iter()
+            .filter(|(_, ty)| match ty {
+                TypeInfo::BaseType(base_type) => base_type.name == name,
+                TypeInfo::StructType(struct_type) | TypeInfo::UnionType(struct_type) => {
+                    !struct_type.declaration && struct_type.name.as_deref() == Some(name)
+                }
+                TypeInfo::EnumerationType(enum_type) => enum_type.name.as_deref() == Some(name),
+                TypeInfo::TypeDef(type_def) => type_def.name.as_deref() == Some(name),
+                TypeInfo::ModifiedType(_)
+                | TypeInfo::ArrayType(_)
+                | TypeInfo::SubroutineType(_) => false,
+            })
+            .map(|(offset, _)| *offset)
+            .collect();
+        offsets.sort();
+        match offsets.first() {
+            Some(offset) => Some(ObjectType::Dwarf(*offset)),
+            None => BuiltinType::from_name(name).map(ObjectType::Builtin),
+        }
+    }
+
+    fn resolve_type_name(&self, type_name: &TypeName) -> Result<ObjectType> {
+        let mut ty = self
+            .find_type(&type_name.name)
+            .ok_or(anyhow!("unknown type name '{}'", type_name.name))?;
+        for _ in 0..type_name.pointer_depth {
+            ty = ObjectType::Pointer(Box::new(ty));
+        }
+        Ok(ty)
+    }
+
+    fn format_options(&self) -> FormatOptions<'_> {
+        FormatOptions {
+            linear_memory: Some(self.frame.memory),
+            max_depth: self.max_depth,
+        }
+    }
+
+    pub fn format(&self, object: &Object) -> Result<String> {
+        match &object.ty {
+            ObjectType::Dwarf(offset) => {
+                let size = self.size_of(&object.ty).unwrap_or(0);
+                let value_bytes;
+                let bytes = match object.location {
+                    // Pass the rest of memory because format_object slices it by itself
+                    ObjectLocation::Memory(address) => self
+                        .frame
+                        .memory
+                        .get(address as usize..)
+                        .ok_or(anyhow!("failed to read memory at 0x{:x}", address))?,
+                    _ => {
+                        value_bytes = self.read_bytes(object, size)?;
+                        &value_bytes
+                    }
+                };
+                format_object(
+                    *offset,
+                    bytes,
+                    self.encoding,
+                    self.type_hash,
+                    &self.format_options(),
+                )
+            }
+            ObjectType::Pointer(pointee) => {
+                let address = self.scalar(object)?.as_u128() as u64;
+                let result = format!("{}* (0x{:x})", self.type_name(pointee)?, address);
+                let pointee = match pointee.as_ref() {
+                    ObjectType::Dwarf(offset) => format_pointee(
+                        Some(*offset),
+                        address,
+                        self.encoding,
+                        self.type_hash,
+                        &self.format_options(),
+                    )?,
+                    _ => None,
+                };
+                match pointee {
+                    Some(pointee) => Ok(format!("{} {}", result, pointee)),
+                    None => Ok(result),
+                }
+            }
+            ObjectType::Array(_, count) => {
+                let count = count.ok_or(anyhow!("array length is unknown"))?;
+                let mut elements = vec![];
+                for index in 0..std::cmp::min(count, MAX_ARRAY_ELEMENTS as u64) {
+                    elements.push(self.format(&self.index(object, index as i128)?)?);
+                }
+                if count > MAX_ARRAY_ELEMENTS as u64 {
+                    elements.push("...".to_string());
+                }
+                Ok(format!("[{}]", elements.join(", ")))
+            }
+            ObjectType::Builtin(builtin) => {
+                let value = match self.scalar(object)? {
+                    Scalar::Signed(v) => format!("{}", v),
+                    Scalar::Unsigned(v) if *builtin == BuiltinType::Bool => format!("{}", v != 0),
+                    Scalar::Unsigned(v) => format!("{}", v),
+                    Scalar::Float(v) if *builtin == BuiltinType::F32 => format!("{}", v as f32),
+                    Scalar::Float(v) => format!("{}", v),
+                };
+                Ok(format!("{}({})", builtin.name(), value))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{SubroutineKind, SymbolVariable};
+    use super::*;
+    use gimli::{EndianRcSlice, LittleEndian};
+    use std::rc::Rc;
+
+    const INT: usize = 1;
+    const CHAR: usize = 2;
+    const STRUCT_S: usize = 3;
+    const INT_ARRAY: usize = 4;
+    const S_POINTER: usize = 5;
+    const STRUCT_E: usize = 6;
+
+    fn base_type(name: &str, byte_size: u64, encoding: gimli::DwAte) -> TypeInfo<Reader> {
+        TypeInfo::BaseType(BaseTypeInfo {
+            name: name.to_string(),
+            byte_size,
+            encoding,
+        })
+    }
+
+    fn member(name: &str, ty: usize, offset: u64) -> Member<Reader> {
+        Member {
+            name: Some(name.to_string()),
+            ty,
+            location: MemberLocation::ConstOffset(offset),
+            data_bit_offset: None,
+            bit_size: None,
+        }
+    }
+
+    /// `struct S { int a; char b; } s; int arr[3]; struct S *p = &s; struct E {};`
+    fn type_hash() -> TypeHash {
+        let mut type_hash = HashMap::new();
+        type_hash.insert(INT, base_type("int", 4, gimli::DW_ATE_signed));
+        type_hash.insert(CHAR, base_type("char", 1, gimli::DW_ATE_signed_char));
+        type_hash.insert(
+            STRUCT_S,
+            TypeInfo::StructType(StructTypeInfo {
+                name: Some("S".to_string()),
+                members: vec![member("a", INT, 0), member("b", CHAR, 4)],
+                byte_size: 8,
+                declaration: false,
+            }),
+        );
+        type_hash.insert(
+            INT_ARRAY,
+            TypeInfo::ArrayType(ArrayTypeInfo {
+                ty: Some(INT),
+                dimensions: vec![Some(3)],
+            }),
+        );
+        type_hash.insert(
+            S_POINTER,
+            TypeInfo::ModifiedType(ModifiedTypeInfo {
+                content_ty_offset: Some(STRUCT_S),
+                kind: ModifierKind::Pointer,
+            }),
+        );
+        type_hash.insert(
+            STRUCT_E,
+            TypeInfo::StructType(StructTypeInfo {
+                name: Some("E".to_string()),
+                members: vec![],
+                byte_size: 0,
+                declaration: false,
+            }),
+        );
+        type_hash
+    }
+
+    const S_ADDRESS: &[u8] = &[gimli::DW_OP_constu.0, 0x10];
+    const ARR_ADDRESS: &[u8] = &[gimli::DW_OP_constu.0, 0x20];
+    const P_ADDRESS: &[u8] = &[gimli::DW_OP_constu.0, 0x30];
+    const DW_OP_WASM_LOCATION: u8 = 0xed;
+
+    fn variable(name: &str, ty: usize, location: &'static [u8]) -> SymbolVariable<Reader> {
+        let expr = gimli::Expression(EndianRcSlice::new(Rc::from(location), LittleEndian));
+        SymbolVariable {
+            name: Some(name.to_string()),
+            content: VariableContent::Location(AttributeValue::Exprloc(expr)),
+            ty_offset: Some(ty),
+        }
+    }
+
+    /// Variable which is at `location` while the code offset is in `range`
+    fn location_list(
+        name: &str,
+        ty: usize,
+        entries: &[(std::ops::Range<u64>, &'static [u8])],
+    ) -> SymbolVariable<Reader> {
+        let entries = entries
+            .iter()
+            .map(|(range, location)| gimli::LocationListEntry {
+                range: gimli::Range {
+                    begin: range.start,
+                    end: range.end,
+                },
+                data: gimli::Expression(EndianRcSlice::new(Rc::from(*location), LittleEndian)),
+            })
+            .collect();
+        SymbolVariable {
+            name: Some(name.to_string()),
+            content: VariableContent::LocationList(entries),
+            ty_offset: Some(ty),
+        }
+    }
+
+    fn subroutines() -> Vec<Subroutine<Reader>> {
+        vec![Subroutine {
+            name: Some("main".to_string()),
+            kind: SubroutineKind::Function,
+            depth: 0,
+            pc: 0..0x100,
+            variables: vec![
+                variable("s", STRUCT_S, S_ADDRESS),
+                variable("arr", INT_ARRAY, ARR_ADDRESS),
+                variable("p", S_POINTER, P_ADDRESS),
+                // `arr[0]` until 0x8 and `arr[2]` after that
+                location_list(
+                    "moved",
+                    INT,
+                    &[
+                        (0..0x8, &[gimli::DW_OP_constu.0, 0x20]),
+                        (0x8..0x20, &[gimli::DW_OP_constu.0, 0x28]),
+                    ],
+                ),
+                location_list("dead", INT, &[(0..0x8, &[gimli::DW_OP_constu.0, 0x20])]),
+                variable("in_local", INT, &[DW_OP_WASM_LOCATION, 0x00, 0x00]),
+                // Global index encoded in uleb128 and u32
+                variable("in_global", INT, &[DW_OP_WASM_LOCATION, 0x01, 0x01]),
+                variable(
+                    "in_global_u32",
+                    INT,
+                    &[DW_OP_WASM_LOCATION, 0x03, 0x01, 0x00, 0x00, 0x00],
+                ),
+                variable(
+                    "in_stack",
+                    INT,
+                    &[DW_OP_WASM_LOCATION, 0x02, 0x00, gimli::DW_OP_stack_value.0],
+                ),
+                // `s.b` addressed by the global plus 4
+                variable(
+                    "from_global",
+                    CHAR,
+                    &[
+                        DW_OP_WASM_LOCATION,
+                        0x01,
+                        0x01,
+                        gimli::DW_OP_plus_uconst.0,
+                        0x04,
+                    ],
+                ),
+                // `s.a` in the local and `s.b` in the memory
+                variable(
+                    "split",
+                    STRUCT_S,
+                    &[
+                        DW_OP_WASM_LOCATION,
+                        0x00,
+                        0x00,
+                        gimli::DW_OP_piece.0,
+                        0x04,
+                        gimli::DW_OP_constu.0,
+                        0x14,
+                        gimli::DW_OP_piece.0,
+                        0x04,
+                    ],
+                ),
+                // Lower half from `arr[0]` and upper half from the local
+                variable(
+                    "halves",
+                    INT,
+                    &[
+                        gimli::DW_OP_constu.0,
+                        0x20,
+                        gimli::DW_OP_piece.0,
+                        0x02,
+                        DW_OP_WASM_LOCATION,
+                        0x00,
+                        0x00,
+                        gimli::DW_OP_piece.0,
+                        0x02,
+                    ],
+                ),
+                variable(
+                    "partially_optimized_out",
+                    INT,
+                    &[
+                        gimli::DW_OP_piece.0,
+                        0x02,
+                        gimli::DW_OP_constu.0,
+                        0x20,
+                        gimli::DW_OP_piece.0,
+                        0x02,
+                    ],
+                ),
+                variable(
+                    "wasm_location_in_middle",
+                    INT,
+                    &[gimli::DW_OP_lit0.0, DW_OP_WASM_LOCATION, 0x00, 0x00],
+                ),
+            ],
+            encoding: gimli::Encoding {
+                format: gimli::Format::Dwarf32,
+                version: 4,
+                address_size: 4,
+            },
+            ty_offset: None,
+            frame_base: None,
+            call_file: None,
+            call_line: None,
+        }]
+    }
+
+    fn memory() -> Vec<u8> {
+        let mut memory = vec![0; 0x40];
+        memory[0x10..0x14].copy_from_slice(&7i32.to_le_bytes());
+        memory[0x14] = b'x';
+        for (i, value) in [1i32, 2, 3].iter().enumerate() {
+            memory[0x20 + i * 4..0x24 + i * 4].copy_from_slice(&value.to_le_bytes());
+        }
+        memory[0x30..0x34].copy_from_slice(&0x10u32.to_le_bytes());
+        memory
+    }
+
+    fn evaluate(input: &str) -> Result<ExpressionResult> {
+        evaluate_in(&subroutines(), input)
+    }
+
+    fn evaluate_in(subroutines: &[Subroutine<Reader>], input: &str) -> Result<ExpressionResult> {
+        let type_hash = type_hash();
+        let memory = memory();
+        let locals = [WasmValue::I32(5)];
+        let globals = [WasmValue::I64(-9), WasmValue::I32(0x10)];
+        let stack = [WasmValue::I32(11)];
+        let frame = FrameState {
+            code_offset: 0x10,
+            locals: &locals,
+            globals: &globals,
+            stack: &stack,
+            memory: &memory,
+        };
+        Evaluator::new(subroutines, &type_hash, &frame, 0).evaluate_and_format(input)
+    }
+
+    fn output(input: &str) -> String {
+        match evaluate(input) {
+            Ok(result) => result.output,
+            Err(err) => panic!("failed to evaluate '{}': {}", input, err),
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(output("1 + 2 * 3"), "int(7)");
+        assert_eq!(output("(1 + 2) * 3"), "int(9)");
+        assert_eq!(output("-7 / 2"), "int(-3)");
+        assert_eq!(output("1 << 40"), "int(0)");
+        assert_eq!(output("3000000000"), "long long(3000000000)");
+        assert_eq!(output("1 < 2 && 2 > 3"), "bool(false)");
+        assert_eq!(output("1.5 + 1"), "double(2.5)");
+        assert_eq!(output("$local0 * 2"), "int(10)");
+        assert_eq!(output("$global0"), "long long(-9)");
+    }
+
+    #[test]
+    fn test_cast() {
+        assert_eq!(output("(unsigned int)-1"), "unsigned int(4294967295)");
+        assert_eq!(output("(char)65"), "char('A')");
+        assert_eq!(output("(int)2.9"), "int(2)");
+        assert_eq!(output("(S *)0x10"), "S* (0x10)");
+        assert_eq!(output("((S *)0x10)->a"), "int(7)");
+    }
+
+    #[test]
+    fn test_member_and_index() {
+        assert_eq!(output("s.a"), "int(7)");
+        assert_eq!(output("s.b"), "char('x')");
+        assert_eq!(output("p->a + 1"), "int(8)");
+        assert_eq!(output("(*p).b"), "char('x')");
+        assert_eq!(output("arr[2]"), "int(3)");
+        assert_eq!(output("arr"), "[int(1), int(2), int(3)]");
+        assert_eq!(output("*&arr[1]"), "int(2)");
+        assert_eq!(output("&s"), "S* (0x10)");
+    }
+
+    #[test]
+    fn test_pointer_arithmetic() {
+        assert_eq!(output("p + 1"), "S* (0x18)");
+        assert_eq!(output("2 + p"), "S* (0x20)");
+        assert_eq!(output("p - 1"), "S* (0x8)");
+        assert_eq!(output("(p + 3) - p"), "int(3)");
+        assert_eq!(output("&arr[2] - &arr[0]"), "int(2)");
+        // Out of the address space
+        assert!(evaluate("p - 3").is_err());
+        assert!(evaluate("p + 0x7fffffffffffffff").is_err());
+        // Elements of `E` have no size
+        match evaluate("(E *)0x10 - (E *)0x8") {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "subtraction of pointers to a zero-sized type"
+            ),
+            Ok(_) => panic!("pointer difference of a zero-sized type should fail"),
+        }
+    }
+
+    #[test]
+    fn test_location_list() {
+        // The frame is at 0x10
+        assert_eq!(output("moved"), "int(3)");
+        assert_eq!(output("moved + 1"), "int(4)");
+        match evaluate("dead") {
+            Err(err) => assert_eq!(err.to_string(), "'dead' is optimized out"),
+            Ok(_) => panic!("'dead' should be optimized out"),
+        }
+    }
+
+    #[test]
+    fn test_composite_location() {
+        assert_eq!(output("split.a"), "int(5)");
+        assert_eq!(output("split.b"), "char('x')");
+        assert_eq!(output("halves"), format!("int({})", 5 << 16 | 1));
+        match evaluate("partially_optimized_out") {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "'partially_optimized_out' is optimized out"
+            ),
+            Ok(_) => panic!("a piece of 'partially_optimized_out' should be optimized out"),
+        }
+    }
+
+    #[test]
+    fn test_wasm_location() {
+        assert_eq!(output("in_local"), "int(5)");
+        assert_eq!(output("in_global"), "int(16)");
+        assert_eq!(output("in_global_u32"), "int(16)");
+        assert_eq!(output("in_stack"), "int(11)");
+        assert_eq!(output("from_global"), "char('x')");
+        let result = evaluate("in_stack = 3").unwrap();
+        match result.changes.as_slice() {
+            [StateChange::Stack {
+                index: 0,
+                value: WasmValue::I32(3),
+            }] => (),
+            _ => panic!("unexpected changes"),
+        }
+        assert!(evaluate("wasm_location_in_middle").is_err());
+    }
+
+    #[test]
+    fn test_frame_base() {
+        // Each frame base is 0x20, so that `on_frame` is `arr[1]`
+        let frame_bases: &[&'static [u8]] = &[
+            &[gimli::DW_OP_constu.0, 0x20],
+            &[
+                DW_OP_WASM_LOCATION,
+                0x00,
+                0x00,
+                gimli::DW_OP_plus_uconst.0,
+                0x1b,
+            ],
+            &[
+                DW_OP_WASM_LOCATION,
+                0x01,
+                0x01,
+                gimli::DW_OP_plus_uconst.0,
+                0x10,
+            ],
+            &[
+                DW_OP_WASM_LOCATION,
+                0x02,
+                0x00,
+                gimli::DW_OP_plus_uconst.0,
+                0x15,
+            ],
+            &[
+                DW_OP_WASM_LOCATION,
+                0x03,
+                0x01,
+                0x00,
+                0x00,
+                0x00,
+                gimli::DW_OP_lit16.0,
+                gimli::DW_OP_plus.0,
+            ],
+        ];
+        for frame_base in frame_bases {
+            let mut subroutines = subroutines();
+            subroutines[0].frame_base = Some(gimli::Expression(EndianRcSlice::new(
+                Rc::from(*frame_base),
+                LittleEndian,
+            )));
+            subroutines[0]
+                .variables
+                .push(variable("on_frame", INT, &[gimli::DW_OP_fbreg.0, 0x04]));
+            match evaluate_in(&subroutines, "on_frame") {
+                Ok(result) => assert_eq!(result.output, "int(2)", "{:x?}", frame_base),
+                Err(err) => panic!("failed with the frame base {:x?}: {}", frame_base, err),
+            }
+        }
+
+        let mut subroutines = subroutines();
+        subroutines[0]
+            .variables
+            .push(variable("on_frame", INT, &[gimli::DW_OP_fbreg.0, 0x04]));
+        assert!(evaluate_in(&subroutines, "on_frame").is_err());
+    }
+
+    #[test]
+    fn test_assignment() {
+        let result = evaluate("$local0 = 3").unwrap();
+        assert_eq!(result.output, "int(3)");
+        match result.changes.as_slice() {
+            [StateChange::Local {
+                index: 0,
+                value: WasmValue::I32(3),
+            }] => (),
+            _ => panic!("unexpected changes"),
+        }
+        let result = evaluate("s.a = 0x101").unwrap();
+        match result.changes.as_slice() {
+            [StateChange::Memory { address, bytes }] => {
+                assert_eq!(*address, 0x10);
+                assert_eq!(bytes, &[1, 1, 0, 0]);
+            }
+            _ => panic!("unexpected changes"),
+        }
+        assert!(evaluate("1 = 2").is_err());
+    }
+
+    #[test]
+    fn test_error() {
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("unknown").is_err());
+        assert!(evaluate("s.unknown").is_err());
+        assert!(evaluate("arr.a").is_err());
+        assert!(evaluate("$local1").is_err());
+        // Out of the linear memory or the address space
+        assert!(evaluate("arr[100]").is_err());
+        assert!(evaluate("arr[-100]").is_err());
+        assert!(evaluate("p[0x7fffffffffffffff]").is_err());
+        assert!(evaluate("((S *)0xfffffffffffffff0)->b").is_err());
+    }
+}
@@ -0,0 +1,527 @@
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Variable(String),
+    WasmLocal(usize),
+    WasmGlobal(usize),
+    Integer {
+        value: u64,
+        unsigned: bool,
+        long: bool,
+    },
+    Float {
+        value: f64,
+        single: bool,
+    },
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Cast(TypeName, Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeName {
+    pub name: String,
+    pub pointer_depth: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+    Deref,
+    AddrOf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    WasmRef(String),
+    Integer {
+        value: u64,
+        unsigned: bool,
+        long: bool,
+    },
+    Float {
+        value: f64,
+        single: bool,
+    },
+    Punct(&'static str),
+}
+
+// Longer punctuators must come first to be matched greedily
+const PUNCTUATORS: &[&str] = &[
+    "->", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "=",
+    "&", "|", "^", "!", "~", "(", ")", "[", "]", ".",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut pos = 0;
+    let is_ident_head = |c: char| c.is_ascii_alphabetic() || c == '_';
+    let is_ident_tail = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if is_ident_head(c) || c == '$' {
+            let start = pos;
+            pos += 1;
+            loop {
+                if pos < chars.len() && is_ident_tail(chars[pos]) {
+                    pos += 1;
+                } else if pos + 1 < chars.len() && chars[pos] == ':' && chars[pos + 1] == ':' {
+                    // C++ qualified names
+                    pos += 2;
+                } else {
+                    break;
+                }
+            }
+            let ident: String = chars[start..pos].iter().collect();
+            if c == '$' {
+                tokens.push(Token::WasmRef(ident[1..].to_string()));
+            } else {
+                tokens.push(Token::Ident(ident));
+            }
+        } else if c.is_ascii_digit() {
+            let (token, next) = tokenize_number(&chars, pos)?;
+            tokens.push(token);
+            pos = next;
+        } else {
+            let rest: String = chars[pos..].iter().collect();
+            match PUNCTUATORS.iter().find(|p| rest.starts_with(*p)) {
+                Some(punct) => {
+                    tokens.push(Token::Punct(punct));
+                    pos += punct.len();
+                }
+                None => return Err(anyhow!("Unexpected character '{}'", c)),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn tokenize_number(chars: &[char], start: usize) -> Result<(Token, usize)> {
+    let mut pos = start;
+    let is_hex = chars[pos] == '0'
+        && pos + 1 < chars.len()
+        && (chars[pos + 1] == 'x' || chars[pos + 1] == 'X');
+    let mut is_float = false;
+    if is_hex {
+        pos += 2;
+        while pos < chars.len() && chars[pos].is_ascii_hexdigit() {
+            pos += 1;
+        }
+    } else {
+        while pos < chars.len() && chars[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        if pos < chars.len() && chars[pos] == '.' {
+            is_float = true;
+            pos += 1;
+            while pos < chars.len() && chars[pos].is_ascii_digit() {
+                pos += 1;
+            }
+        }
+        if pos < chars.len() && (chars[pos] == 'e' || chars[pos] == 'E') {
+            is_float = true;
+            pos += 1;
+            if pos < chars.len() && (chars[pos] == '+' || chars[pos] == '-') {
+                pos += 1;
+            }
+            while pos < chars.len() && chars[pos].is_ascii_digit() {
+                pos += 1;
+            }
+        }
+    }
+    let literal: String = chars[start..pos].iter().collect();
+    let suffix_start = pos;
+    while pos < chars.len() && chars[pos].is_ascii_alphabetic() {
+        pos += 1;
+    }
+    let suffix: String = chars[suffix_start..pos]
+        .iter()
+        .collect::<String>()
+        .to_lowercase();
+    if is_float {
+        let single = match suffix.as_str() {
+            "" | "l" => false,
+            "f" => true,
+            _ => return Err(anyhow!("Invalid suffix '{}' on floating literal", suffix)),
+        };
+        let value = literal
+            .parse::<f64>()
+            .map_err(|_| anyhow!("Invalid floating literal '{}'", literal))?;
+        return Ok((Token::Float { value, single }, pos));
+    }
+    if suffix.chars().any(|c| c != 'u' && c != 'l') {
+        return Err(anyhow!("Invalid suffix '{}' on integer literal", suffix));
+    }
+    let value = if is_hex {
+        u64::from_str_radix(&literal[2..], 16)
+    } else {
+        literal.parse::<u64>()
+    }
+    .map_err(|_| anyhow!("Invalid integer literal '{}'", literal))?;
+    let token = Token::Integer {
+        value,
+        unsigned: suffix.contains('u'),
+        long: suffix.contains('l'),
+    };
+    Ok((token, pos))
+}
+
+fn binary_op(punct: &str) -> Option<(BinaryOp, u8)> {
+    let op = match punct {
+        "||" => (BinaryOp::Or, 1),
+        "&&" => (BinaryOp::And, 2),
+        "|" => (BinaryOp::BitOr, 3),
+        "^" => (BinaryOp::BitXor, 4),
+        "&" => (BinaryOp::BitAnd, 5),
+        "==" => (BinaryOp::Eq, 6),
+        "!=" => (BinaryOp::Ne, 6),
+        "<" => (BinaryOp::Lt, 7),
+        ">" => (BinaryOp::Gt, 7),
+        "<=" => (BinaryOp::Le, 7),
+        ">=" => (BinaryOp::Ge, 7),
+        "<<" => (BinaryOp::Shl, 8),
+        ">>" => (BinaryOp::Shr, 8),
+        "+" => (BinaryOp::Add, 9),
+        "-" => (BinaryOp::Sub, 9),
+        "*" => (BinaryOp::Mul, 10),
+        "/" => (BinaryOp::Div, 10),
+        "%" => (BinaryOp::Rem, 10),
+        _ => return None,
+    };
+    Some(op)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    is_type_name: &'a dyn Fn(&str) -> bool,
+}
+
+/// Parses C-like expression. `is_type_name` is used to distinguish a cast
+/// from a parenthesized expression.
+pub fn parse_expression(input: &str, is_type_name: &dyn Fn(&str) -> bool) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        is_type_name,
+    };
+    let expr = parser.parse_assignment()?;
+    match parser.peek() {
+        Some(token) => Err(anyhow!("Unexpected token {:?}", token)),
+        None => Ok(expr),
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn consume_punct(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Some(Token::Punct(p)) if *p == punct => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<()> {
+        if self.consume_punct(punct) {
+            Ok(())
+        } else {
+            Err(anyhow!("Expected '{}' but got {:?}", punct, self.peek()))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(ident),
+            token => Err(anyhow!("Expected identifier but got {:?}", token)),
+        }
+    }
+
+    /// Assignment is right associative and has the lowest precedence
+    fn parse_assignment(&mut self) -> Result<Expr> {
+        let lhs = self.parse_binary(0)?;
+        if self.consume_punct("=") {
+            let rhs = self.parse_assignment()?;
+            return Ok(Expr::Assign(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(Token::Punct(punct)) = self.peek() {
+            let (op, precedence) = match binary_op(punct) {
+                Some(op) => op,
+                None => break,
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.parse_binary(precedence + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        let op = match self.peek() {
+            Some(Token::Punct("-")) => Some(UnaryOp::Neg),
+            Some(Token::Punct("!")) => Some(UnaryOp::Not),
+            Some(Token::Punct("~")) => Some(UnaryOp::BitNot),
+            Some(Token::Punct("*")) => Some(UnaryOp::Deref),
+            Some(Token::Punct("&")) => Some(UnaryOp::AddrOf),
+            Some(Token::Punct("+")) => {
+                self.pos += 1;
+                return self.parse_unary();
+            }
+            Some(Token::Punct("(")) => {
+                if let Some(type_name) = self.try_parse_cast_type() {
+                    let operand = self.parse_unary()?;
+                    return Ok(Expr::Cast(type_name, Box::new(operand)));
+                }
+                None
+            }
+            _ => None,
+        };
+        match op {
+            Some(op) => {
+                self.pos += 1;
+                let operand = self.parse_unary()?;
+                Ok(Expr::Unary(op, Box::new(operand)))
+            }
+            None => self.parse_postfix(),
+        }
+    }
+
+    /// Consumes `(type-name)` if the parenthesized tokens form a known type name
+    fn try_parse_cast_type(&mut self) -> Option<TypeName> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut words = vec![];
+        while let Some(Token::Ident(word)) = self.peek() {
+            words.push(word.clone());
+            self.pos += 1;
+        }
+        let mut pointer_depth = 0;
+        while self.consume_punct("*") {
+            pointer_depth += 1;
+        }
+        if !words.is_empty() && self.consume_punct(")") {
+            let name = words.join(" ");
+            if (self.is_type_name)(&name) {
+                return Some(TypeName {
+                    name,
+                    pointer_depth,
+                });
+            }
+        }
+        self.pos = start;
+        None
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            if self.consume_punct("[") {
+                let index = self.parse_assignment()?;
+                self.expect_punct("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else if self.consume_punct(".") {
+                let member = self.expect_ident()?;
+                expr = Expr::Member(Box::new(expr), member);
+            } else if self.consume_punct("->") {
+                let member = self.expect_ident()?;
+                let base = Expr::Unary(UnaryOp::Deref, Box::new(expr));
+                expr = Expr::Member(Box::new(base), member);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Ident(name)) => Ok(Expr::Variable(name)),
+            Some(Token::WasmRef(name)) => parse_wasm_ref(&name),
+            Some(Token::Integer {
+                value,
+                unsigned,
+                long,
+            }) => Ok(Expr::Integer {
+                value,
+                unsigned,
+                long,
+            }),
+            Some(Token::Float { value, single }) => Ok(Expr::Float { value, single }),
+            Some(Token::Punct("(")) => {
+                let expr = self.parse_assignment()?;
+                self.expect_punct(")")?;
+                Ok(expr)
+            }
+            Some(token) => Err(anyhow!("Unexpected token {:?}", token)),
+            None => Err(anyhow!("Unexpected end of expression")),
+        }
+    }
+}
+
+fn parse_wasm_ref(name: &str) -> Result<Expr> {
+    let parse_index =
+        |prefix: &str| -> Option<usize> { name.strip_prefix(prefix)?.parse::<usize>().ok() };
+    if let Some(index) = parse_index("local") {
+        Ok(Expr::WasmLocal(index))
+    } else if let Some(index) = parse_index("global") {
+        Ok(Expr::WasmGlobal(index))
+    } else {
+        Err(anyhow!(
+            "'${}' is not valid, use '$local<index>' or '$global<index>'",
+            name
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<String> {
+        let is_type_name = |name: &str| name == "int" || name == "unsigned int" || name == "S";
+        Ok(show(&parse_expression(input, &is_type_name)?))
+    }
+
+    /// Prints the tree as an S-expression to compare the structure
+    fn show(expr: &Expr) -> String {
+        match expr {
+            Expr::Variable(name) => name.clone(),
+            Expr::WasmLocal(index) => format!("$local{}", index),
+            Expr::WasmGlobal(index) => format!("$global{}", index),
+            Expr::Integer { value, .. } => format!("{}", value),
+            Expr::Float { value, .. } => format!("{:?}", value),
+            Expr::Member(base, member) => format!("(. {} {})", show(base), member),
+            Expr::Index(base, index) => format!("([] {} {})", show(base), show(index)),
+            Expr::Unary(op, operand) => format!("({:?} {})", op, show(operand)),
+            Expr::Binary(op, lhs, rhs) => format!("({:?} {} {})", op, show(lhs), show(rhs)),
+            Expr::Cast(ty, operand) => format!(
+                "(cast {}{} {})",
+                ty.name,
+                "*".repeat(ty.pointer_depth),
+                show(operand)
+            ),
+            Expr::Assign(lhs, rhs) => format!("(= {} {})", show(lhs), show(rhs)),
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(parse("1 + 2 * 3").unwrap(), "(Add 1 (Mul 2 3))");
+        assert_eq!(parse("(1 + 2) * 3").unwrap(), "(Mul (Add 1 2) 3)");
+        assert_eq!(parse("1 - 2 - 3").unwrap(), "(Sub (Sub 1 2) 3)");
+        assert_eq!(parse("1 << 2 + 3").unwrap(), "(Shl 1 (Add 2 3))");
+        assert_eq!(
+            parse("a || b && c | d").unwrap(),
+            "(Or a (And b (BitOr c d)))"
+        );
+        assert_eq!(parse("a == b < c").unwrap(), "(Eq a (Lt b c))");
+        assert_eq!(parse("-a * !b").unwrap(), "(Mul (Neg a) (Not b))");
+        assert_eq!(parse("a = b = 1").unwrap(), "(= a (= b 1))");
+    }
+
+    #[test]
+    fn test_cast() {
+        assert_eq!(parse("(int)x").unwrap(), "(cast int x)");
+        assert_eq!(
+            parse("(unsigned int)-1").unwrap(),
+            "(cast unsigned int (Neg 1))"
+        );
+        assert_eq!(parse("(S **)p").unwrap(), "(cast S** p)");
+        // Parenthesized variable which is not a type name
+        assert_eq!(parse("(x) + 1").unwrap(), "(Add x 1)");
+        assert_eq!(parse("(int)a.b").unwrap(), "(cast int (. a b))");
+    }
+
+    #[test]
+    fn test_postfix() {
+        assert_eq!(parse("s.a").unwrap(), "(. s a)");
+        assert_eq!(parse("p->a").unwrap(), "(. (Deref p) a)");
+        assert_eq!(parse("arr[1][i + 1]").unwrap(), "([] ([] arr 1) (Add i 1))");
+        assert_eq!(parse("*p.next").unwrap(), "(Deref (. p next))");
+        assert_eq!(parse("&s.a[2]").unwrap(), "(AddrOf ([] (. s a) 2))");
+        assert_eq!(
+            parse("$local3 + $global0").unwrap(),
+            "(Add $local3 $global0)"
+        );
+        assert_eq!(parse("ns::value").unwrap(), "ns::value");
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(parse("0x1F").unwrap(), "31");
+        assert_eq!(parse("1.5e2").unwrap(), "150.0");
+        match parse_expression("10ul", &|_| false).unwrap() {
+            Expr::Integer {
+                value: 10,
+                unsigned: true,
+                long: true,
+            } => (),
+            expr => panic!("unexpected {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(parse("").is_err());
+        assert!(parse("1 +").is_err());
+        assert!(parse("(1 + 2").is_err());
+        assert!(parse("a[1").is_err());
+        assert!(parse("s.").is_err());
+        assert!(parse("1 2").is_err());
+        assert!(parse("a @ b").is_err());
+        assert!(parse("12abc").is_err());
+        assert!(parse("99999999999999999999").is_err());
+        assert!(parse("$foo").is_err());
+    }
+}
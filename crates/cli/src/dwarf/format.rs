@@ -5,9 +5,23 @@ use std::collections::HashMap;
 
 use anyhow::{anyhow, Result};
 
-pub fn type_name<'input>(
+/// Options to format objects which may refer to other objects in the linear memory
+#[derive(Clone, Copy)]
+pub struct FormatOptions<'a> {
+    /// Whole linear memory to follow pointers, `None` if it's not available
+    pub linear_memory: Option<&'a [u8]>,
+    /// Levels of pointers to follow
+    pub max_depth: usize,
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 1;
+const MAX_C_STRING_LENGTH: usize = 256;
+/// Elements printed for an array, the rest are elided as `...`
+pub const MAX_ARRAY_ELEMENTS: usize = 256;
+
+pub fn type_name(
     ty_offset: Option<usize>,
-    type_hash: &HashMap<usize, TypeInfo<Reader<'input>>>,
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
 ) -> Result<String> {
     let ty_offset = match ty_offset {
         Some(o) => o,
@@ -22,6 +36,25 @@ pub fn type_name<'input>(
             .name
             .clone()
             .unwrap_or("struct <<not parsed yet>>".to_string()),
+        TypeInfo::UnionType(union_type) => union_type
+            .name
+            .clone()
+            .unwrap_or("union <<not parsed yet>>".to_string()),
+        TypeInfo::ArrayType(array_type) => format!(
+            "{}{}",
+            type_name(array_type.ty, type_hash)?,
+            array_type
+                .dimensions
+                .iter()
+                .map(|count| match count {
+                    Some(count) => format!("[{}]", count),
+                    None => "[]".to_string(),
+                })
+                .collect::<String>()
+        ),
+        TypeInfo::SubroutineType(subroutine_type) => {
+            subroutine_type_name(subroutine_type, "", type_hash)?
+        }
         TypeInfo::EnumerationType(enum_type) => enum_type
             .name
             .clone()
@@ -47,9 +80,15 @@ pub fn type_name<'input>(
                 "packed {}",
                 type_name(mod_type.content_ty_offset, type_hash)?
             ),
-            ModifierKind::Pointer => {
-                format!("{}*", type_name(mod_type.content_ty_offset, type_hash)?)
-            }
+            ModifierKind::Pointer => match mod_type
+                .content_ty_offset
+                .and_then(|offset| type_hash.get(&offset))
+            {
+                Some(TypeInfo::SubroutineType(subroutine_type)) => {
+                    subroutine_type_name(subroutine_type, "(*)", type_hash)?
+                }
+                _ => format!("{}*", type_name(mod_type.content_ty_offset, type_hash)?),
+            },
             ModifierKind::Reference => {
                 format!("{}&", type_name(mod_type.content_ty_offset, type_hash)?)
             }
@@ -73,61 +112,397 @@ pub fn type_name<'input>(
     Ok(result)
 }
 
-pub fn format_object<'input>(
-    ty_offset: usize,
-    memory: &[u8],
-    encoding: gimli::Encoding,
-    type_hash: &HashMap<usize, TypeInfo<Reader<'input>>>,
+fn subroutine_type_name(
+    subroutine_type: &SubroutineTypeInfo<Reader>,
+    declarator: &str,
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
 ) -> Result<String> {
+    let mut params = vec![];
+    for param in &subroutine_type.parameters {
+        params.push(type_name(*param, type_hash)?);
+    }
+    if subroutine_type.variadic {
+        params.push("...".to_string());
+    }
+    Ok(format!(
+        "{} {}({})",
+        type_name(subroutine_type.ty, type_hash)?,
+        declarator,
+        params.join(", ")
+    ))
+}
+
+/// Looks through typedefs, enumerations and non-pointer modifiers for the underlying base type
+fn underlying_base_type(
+    ty_offset: usize,
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
+) -> Option<&BaseTypeInfo> {
+    match type_hash.get(&ty_offset)? {
+        TypeInfo::BaseType(base_type) => Some(base_type),
+        TypeInfo::TypeDef(type_def) => underlying_base_type(type_def.ty?, type_hash),
+        TypeInfo::EnumerationType(enum_type) => underlying_base_type(enum_type.ty?, type_hash),
+        TypeInfo::ModifiedType(mod_type) => match mod_type.kind {
+            ModifierKind::Pointer | ModifierKind::Reference | ModifierKind::RvalueReference => None,
+            _ => underlying_base_type(mod_type.content_ty_offset?, type_hash),
+        },
+        _ => None,
+    }
+}
+
+fn base_type_bytes<'a>(base_type: &BaseTypeInfo, memory: &'a [u8]) -> Result<&'a [u8]> {
+    let size = base_type.byte_size as usize;
+    if size == 0 || size > 16 {
+        return Err(anyhow!(
+            "{} bytes '{}' is not supported",
+            size,
+            base_type.name
+        ));
+    }
+    memory.get(0..size).ok_or(anyhow!(
+        "failed to read {} bytes of '{}'",
+        size,
+        base_type.name
+    ))
+}
+
+/// Reads an integer value of the base type, sign-extending signed encodings
+fn read_integer(base_type: &BaseTypeInfo, memory: &[u8]) -> Result<i128> {
+    let bytes = base_type_bytes(base_type, memory)?;
+    let mut buf = [0; 16];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    let value = u128::from_le_bytes(buf);
+    match base_type.encoding {
+        gimli::DW_ATE_signed | gimli::DW_ATE_signed_char => {
+            let shift = 128 - bytes.len() * 8;
+            Ok(((value << shift) as i128) >> shift)
+        }
+        _ => Ok(value as i128),
+    }
+}
+
+/// Decodes IEEE 754 binary floating point numbers which Rust doesn't support natively
+fn decode_ieee754(bits: u128, exponent_bits: u32, mantissa_bits: u32) -> f64 {
+    let bias = (1 << (exponent_bits - 1)) - 1;
+    let exponent = ((bits >> mantissa_bits) & ((1 << exponent_bits) - 1)) as i32;
+    let fraction = (bits & ((1 << mantissa_bits) - 1)) as f64 / 2f64.powi(mantissa_bits as i32);
+    let magnitude = if exponent == 0 {
+        fraction * 2f64.powi(1 - bias)
+    } else if exponent == (1 << exponent_bits) - 1 {
+        if fraction == 0.0 {
+            f64::INFINITY
+        } else {
+            f64::NAN
+        }
+    } else {
+        (1.0 + fraction) * 2f64.powi(exponent - bias)
+    };
+    if (bits >> (exponent_bits + mantissa_bits)) & 1 == 1 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+fn format_float(bytes: &[u8]) -> Result<String> {
+    let mut buf = [0; 16];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    let bits = u128::from_le_bytes(buf);
+    match bytes.len() {
+        2 => Ok(format!("{}", decode_ieee754(bits, 5, 10))),
+        4 => Ok(format!("{}", f32::from_bits(bits as u32))),
+        8 => Ok(format!("{}", f64::from_bits(bits as u64))),
+        // long double of wasm32 is IEEE binary128
+        16 => Ok(format!("{}", decode_ieee754(bits, 15, 112))),
+        size => Err(anyhow!("{} bytes floating point is not supported", size)),
+    }
+}
+
+fn format_char(code: u32) -> String {
+    match std::char::from_u32(code) {
+        Some(c) => format!("{:?}", c),
+        None => format!("'\\u{{{:x}}}'", code),
+    }
+}
+
+/// Formats the value of a base type by its DWARF encoding
+fn format_base_value(base_type: &BaseTypeInfo, memory: &[u8]) -> Result<String> {
+    let bytes = base_type_bytes(base_type, memory)?;
+    let value = match base_type.encoding {
+        gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char if bytes.len() == 1 => {
+            format_char(bytes[0] as u32)
+        }
+        gimli::DW_ATE_signed | gimli::DW_ATE_signed_char => {
+            format!("{}", read_integer(base_type, memory)?)
+        }
+        gimli::DW_ATE_unsigned | gimli::DW_ATE_unsigned_char => {
+            format!("{}", read_integer(base_type, memory)? as u128)
+        }
+        gimli::DW_ATE_UTF => format_char(read_integer(base_type, memory)? as u32),
+        gimli::DW_ATE_boolean => format!("{}", bytes.iter().any(|b| *b != 0)),
+        gimli::DW_ATE_float => format_float(bytes)?,
+        gimli::DW_ATE_complex_float => {
+            let (real, imag) = bytes.split_at(bytes.len() / 2);
+            format!("{} + {}i", format_float(real)?, format_float(imag)?)
+        }
+        gimli::DW_ATE_address => format!("0x{:x}", read_integer(base_type, memory)?),
+        encoding => {
+            return Err(anyhow!(
+                "'{}' has unsupported encoding {}",
+                base_type.name,
+                encoding
+            ))
+        }
+    };
+    Ok(value)
+}
+
+fn byte_size(ty_offset: usize, type_hash: &HashMap<usize, TypeInfo<Reader>>) -> Result<u64> {
     let ty = type_hash
         .get(&ty_offset)
         .ok_or(anyhow!("Failed to get type from offset '{}'", ty_offset))?;
     match ty {
-        TypeInfo::BaseType(base_type) => {
-            let type_name: &str = &base_type.name;
-            match type_name {
-                "int" => {
-                    let mut bytes: [u8; 4] = Default::default();
-                    bytes.copy_from_slice(&memory[0..(base_type.byte_size as usize)]);
-                    Ok(format!("{}({})", base_type.name, i32::from_le_bytes(bytes)))
-                }
-                "long unsigned int" => {
-                    let mut bytes: [u8; 4] = Default::default();
-                    bytes.copy_from_slice(&memory[0..(base_type.byte_size as usize)]);
-                    Ok(format!("{}({})", base_type.name, u32::from_le_bytes(bytes)))
-                }
-                "long long unsigned int" => {
-                    let mut bytes: [u8; 8] = Default::default();
-                    bytes.copy_from_slice(&memory[0..(base_type.byte_size as usize)]);
-                    Ok(format!("{}({})", base_type.name, u64::from_le_bytes(bytes)))
-                }
-                "unsigned __int128" => {
-                    let mut bytes: [u8; 16] = Default::default();
-                    bytes.copy_from_slice(&memory[0..(base_type.byte_size as usize)]);
-                    Ok(format!(
-                        "{}({})",
-                        base_type.name,
-                        u128::from_le_bytes(bytes)
-                    ))
+        TypeInfo::BaseType(base_type) => Ok(base_type.byte_size),
+        TypeInfo::StructType(struct_type) | TypeInfo::UnionType(struct_type) => {
+            Ok(struct_type.byte_size)
+        }
+        TypeInfo::ArrayType(array_type) => {
+            let mut size = match array_type.ty {
+                Some(offset) => byte_size(offset, type_hash)?,
+                None => return Err(anyhow!("array element type is unknown")),
+            };
+            for count in &array_type.dimensions {
+                match count {
+                    Some(count) => size *= count,
+                    None => return Err(anyhow!("array length is unknown")),
                 }
-                "char" => Ok(String::from_utf8(vec![memory[0]])
-                    .unwrap_or("<<invalid utf8 char>>".to_string())),
-                _ => unimplemented!(),
+            }
+            Ok(size)
+        }
+        TypeInfo::SubroutineType(_) => Err(anyhow!("function type has no size")),
+        TypeInfo::TypeDef(type_def) => match type_def.ty {
+            Some(offset) => byte_size(offset, type_hash),
+            None => Err(anyhow!("void has no size")),
+        },
+        TypeInfo::EnumerationType(enum_type) => match enum_type.ty {
+            Some(offset) => byte_size(offset, type_hash),
+            None => Ok(4),
+        },
+        TypeInfo::ModifiedType(mod_type) => match mod_type.kind {
+            ModifierKind::Pointer | ModifierKind::Reference | ModifierKind::RvalueReference => {
+                Ok(4)
+            }
+            _ => match mod_type.content_ty_offset {
+                Some(offset) => byte_size(offset, type_hash),
+                None => Err(anyhow!("void has no size")),
+            },
+        },
+    }
+}
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("  {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn member_offset(member: &Member<Reader>, encoding: gimli::Encoding) -> Result<u64> {
+    match &member.location {
+        MemberLocation::ConstOffset(offset) => Ok(*offset),
+        MemberLocation::LocationDescription(expr) => {
+            let pieces = evaluate_variable_location(encoding, Some(0), None, expr.clone())?;
+            match pieces.first().map(|p| &p.location) {
+                Some(gimli::Location::Address { address }) => Ok(*address),
+                _ => Err(anyhow!("unsupported member location")),
             }
         }
-        TypeInfo::StructType(struct_type) => {
+    }
+}
+
+fn format_array(
+    elem_ty_offset: usize,
+    dimensions: &[Option<u64>],
+    memory: &[u8],
+    encoding: gimli::Encoding,
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
+    options: &FormatOptions,
+) -> Result<String> {
+    let (count, inner_dimensions) = match dimensions.split_first() {
+        Some((Some(count), inner_dimensions)) => (*count as usize, inner_dimensions),
+        Some((None, _)) => return Ok("[<<unknown length>>]".to_string()),
+        None => return format_object(elem_ty_offset, memory, encoding, type_hash, options),
+    };
+    let is_char = match underlying_base_type(elem_ty_offset, type_hash) {
+        Some(base_type) => {
+            base_type.encoding == gimli::DW_ATE_signed_char
+                || base_type.encoding == gimli::DW_ATE_unsigned_char
+        }
+        None => false,
+    };
+    if is_char && inner_dimensions.is_empty() {
+        let bytes = memory
+            .get(0..count)
+            .ok_or(anyhow!("failed to read {} bytes of char array", count))?;
+        let len = bytes.iter().position(|b| *b == 0).unwrap_or(count);
+        return Ok(format!("{:?}", String::from_utf8_lossy(&bytes[0..len])));
+    }
+    let mut stride = byte_size(elem_ty_offset, type_hash)? as usize;
+    for inner_count in inner_dimensions {
+        let inner_count = inner_count.ok_or(anyhow!("array length is unknown"))? as usize;
+        stride = stride
+            .checked_mul(inner_count)
+            .ok_or(anyhow!("array is too large"))?;
+    }
+    let mut elements = vec![];
+    for index in 0..std::cmp::min(count, MAX_ARRAY_ELEMENTS) {
+        let memory = index
+            .checked_mul(stride)
+            .and_then(|offset| memory.get(offset..))
+            .ok_or(anyhow!("failed to read array element at {}", index))?;
+        elements.push(format_array(
+            elem_ty_offset,
+            inner_dimensions,
+            memory,
+            encoding,
+            type_hash,
+            options,
+        )?);
+    }
+    if count > MAX_ARRAY_ELEMENTS {
+        elements.push("...".to_string());
+    }
+    // Print aggregate elements as a tree
+    if elements.iter().any(|e| e.contains('\n')) {
+        Ok(format!("[\n{}\n]", indent(&elements.join(",\n"))))
+    } else {
+        Ok(format!("[{}]", elements.join(", ")))
+    }
+}
+
+/// Reads an unsigned bit-field value of `bit_size` bits at `bit_offset` bits from the beginning of `memory`
+pub fn read_bit_field(memory: &[u8], bit_offset: u64, bit_size: u64) -> Result<u128> {
+    if bit_size == 0 || bit_size > 64 {
+        return Err(anyhow!("unsupported bit-field size {}", bit_size));
+    }
+    let begin = (bit_offset / 8) as usize;
+    let end = (bit_offset + bit_size).div_ceil(8) as usize;
+    let bytes = memory
+        .get(begin..end)
+        .ok_or(anyhow!("failed to read bit-field at bit {}", bit_offset))?;
+    let mut value: u128 = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        value |= (*byte as u128) << (i * 8);
+    }
+    Ok((value >> (bit_offset % 8)) & ((1u128 << bit_size) - 1))
+}
+
+fn format_bit_field(
+    ty_offset: usize,
+    bit_offset: u64,
+    bit_size: u64,
+    memory: &[u8],
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
+) -> Result<String> {
+    let value = read_bit_field(memory, bit_offset, bit_size)?;
+    let is_signed = match underlying_base_type(ty_offset, type_hash) {
+        Some(base_type) => {
+            base_type.encoding == gimli::DW_ATE_signed
+                || base_type.encoding == gimli::DW_ATE_signed_char
+        }
+        None => false,
+    };
+    let value = if is_signed {
+        // Sign-extend from the most significant bit of the field
+        let shift = 128 - bit_size;
+        format!("{}", ((value << shift) as i128) >> shift)
+    } else {
+        format!("{}", value)
+    };
+    Ok(format!(
+        "{}({})",
+        type_name(Some(ty_offset), type_hash)?,
+        value
+    ))
+}
+
+/// Formats the object pointed by `address`, `None` if the pointer should not be followed
+pub fn format_pointee(
+    pointee_ty_offset: Option<usize>,
+    address: u64,
+    encoding: gimli::Encoding,
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
+    options: &FormatOptions,
+) -> Result<Option<String>> {
+    let (pointee_ty_offset, linear_memory) = match (pointee_ty_offset, options.linear_memory) {
+        (Some(offset), Some(linear_memory)) if address != 0 => (offset, linear_memory),
+        _ => return Ok(None),
+    };
+    let memory = match linear_memory.get(address as usize..) {
+        Some(memory) => memory,
+        None => return Ok(Some("<<out of bounds>>".to_string())),
+    };
+    let is_char = match underlying_base_type(pointee_ty_offset, type_hash) {
+        Some(base_type) => {
+            base_type.byte_size == 1
+                && (base_type.encoding == gimli::DW_ATE_signed_char
+                    || base_type.encoding == gimli::DW_ATE_unsigned_char)
+        }
+        None => false,
+    };
+    // C strings are shown regardless of the depth like other debuggers do
+    if is_char {
+        let bytes = &memory[..std::cmp::min(memory.len(), MAX_C_STRING_LENGTH)];
+        return Ok(Some(match bytes.iter().position(|b| *b == 0) {
+            Some(len) => format!("{:?}", String::from_utf8_lossy(&bytes[..len])),
+            None => format!("{:?}...", String::from_utf8_lossy(bytes)),
+        }));
+    }
+    if options.max_depth == 0 {
+        return Ok(None);
+    }
+    if let Some(TypeInfo::SubroutineType(_)) = type_hash.get(&pointee_ty_offset) {
+        return Ok(None);
+    }
+    let options = FormatOptions {
+        max_depth: options.max_depth - 1,
+        ..*options
+    };
+    let pointee = match format_object(pointee_ty_offset, memory, encoding, type_hash, &options) {
+        Ok(pointee) => pointee,
+        Err(err) => format!("<<{}>>", err),
+    };
+    Ok(Some(format!("-> {}", pointee)))
+}
+
+pub fn format_object(
+    ty_offset: usize,
+    memory: &[u8],
+    encoding: gimli::Encoding,
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
+    options: &FormatOptions,
+) -> Result<String> {
+    let ty = type_hash
+        .get(&ty_offset)
+        .ok_or(anyhow!("Failed to get type from offset '{}'", ty_offset))?;
+    match ty {
+        TypeInfo::BaseType(base_type) => Ok(format!(
+            "{}({})",
+            base_type.name,
+            format_base_value(base_type, memory)?
+        )),
+        TypeInfo::StructType(struct_type) | TypeInfo::UnionType(struct_type) => {
             if let Some(type_name) = struct_type.name.clone() {
                 let type_name: &str = &type_name;
                 // For Swift Support
                 match type_name {
                     "UnsafeRawPointer" | "UnsafeMutableRawPointer" => {
-                    let mut bytes: [u8; 4] = Default::default();
-                    bytes.copy_from_slice(&memory[0..4]);
-                    return Ok(format!(
-                        "{} (0x{:x})",
-                        type_name,
-                        u32::from_le_bytes(bytes)
-                    ));
+                        let mut bytes: [u8; 4] = Default::default();
+                        bytes.copy_from_slice(
+                            memory
+                                .get(0..4)
+                                .ok_or(anyhow!("failed to read 4 bytes of '{}'", type_name))?,
+                        );
+                        return Ok(format!("{} (0x{:x})", type_name, u32::from_le_bytes(bytes)));
                     }
                     _ => (),
                 }
@@ -135,18 +510,20 @@ pub fn format_object<'input>(
 
             let mut members_str = vec![];
             for member in &struct_type.members {
-                let offset: usize = match member.location {
-                    MemberLocation::ConstOffset(offset) => offset as usize,
-                    MemberLocation::LocationDescription(expr) => {
-                        let pieces = evaluate_variable_location(encoding, 0, expr)?;
-                        let piece = match pieces.iter().next() {
-                            Some(p) => p,
-                            None => panic!(),
-                        };
-                        match piece.location {
-                            gimli::Location::Address { address } => address as usize,
-                            _ => unimplemented!(),
-                        }
+                let offset = member_offset(member, encoding)? as usize;
+                let value = match member.bit_size {
+                    Some(bit_size) => format_bit_field(
+                        member.ty,
+                        offset as u64 * 8 + member.data_bit_offset.unwrap_or(0),
+                        bit_size,
+                        memory,
+                        type_hash,
+                    )?,
+                    None => {
+                        let memory = memory
+                            .get(offset..)
+                            .ok_or(anyhow!("failed to read member at offset {}", offset))?;
+                        format_object(member.ty, memory, encoding, type_hash, options)?
                     }
                 };
                 members_str.push(format!(
@@ -155,44 +532,59 @@ pub fn format_object<'input>(
                         .name
                         .clone()
                         .unwrap_or("<<not parsed yet>>".to_string()),
-                    format_object(member.ty, &memory[offset..], encoding, type_hash)?
+                    value
                 ))
             }
+            let name = struct_type
+                .name
+                .clone()
+                .unwrap_or("<<not parsed yet>>".to_string());
+            if members_str.is_empty() {
+                return Ok(format!("{} {{}}", name));
+            }
             Ok(format!(
                 "{} {{\n{}\n}}",
-                struct_type
-                    .name
-                    .clone()
-                    .unwrap_or("<<not parsed yet>>".to_string()),
-                members_str.join(",\n"),
+                name,
+                indent(&members_str.join(",\n")),
             ))
         }
+        TypeInfo::ArrayType(array_type) => match array_type.ty {
+            Some(elem_ty_offset) => format_array(
+                elem_ty_offset,
+                &array_type.dimensions,
+                memory,
+                encoding,
+                type_hash,
+                options,
+            ),
+            None => Err(anyhow!("array element type is unknown")),
+        },
+        TypeInfo::SubroutineType(_) => type_name(Some(ty_offset), type_hash),
         TypeInfo::EnumerationType(enum_type) => {
-            if let Some(offset) = enum_type.ty {
-                match type_hash.get(&offset) {
-                    Some(TypeInfo::BaseType(base_ty)) => {
-                        if base_ty.name != "int" {
-                            return Err(anyhow!(
-                                "{} is not supported as enum content type",
-                                base_ty.name
-                            ));
-                        }
-                    }
-                    Some(_) => {
-                        return Err(anyhow!(
-                            "enum content type '{}' should be base type",
-                            type_name(Some(offset), type_hash)?
-                        ))
-                    }
-                    None => return Err(anyhow!("failed to get enum content type")),
-                }
-            }
-            let mut bytes: [u8; 4] = Default::default();
-            bytes.copy_from_slice(&memory[0..4]);
-            let value = i32::from_le_bytes(bytes);
+            // Enumerations without DW_AT_type are int by the C rule
+            let int_type = BaseTypeInfo {
+                name: "int".to_string(),
+                byte_size: 4,
+                encoding: gimli::DW_ATE_signed,
+            };
+            let base_type = match enum_type.ty {
+                Some(offset) => underlying_base_type(offset, type_hash).ok_or(anyhow!(
+                    "enum content type '{}' should be base type",
+                    type_name(Some(offset), type_hash)?
+                ))?,
+                None => &int_type,
+            };
+            let value = read_integer(base_type, memory)?;
+            let bits = base_type.byte_size * 8;
             for enumerator in &enum_type.enumerators {
                 if let Some(const_value) = enumerator.value {
-                    if (const_value as i32) == value {
+                    // Small constant forms are read as signed even for unsigned enumerations
+                    let truncated = if bits < 64 {
+                        (const_value as u64 & ((1 << bits) - 1)) as i128
+                    } else {
+                        const_value as i128
+                    };
+                    if const_value as i128 == value || truncated == value {
                         return Ok(format!(
                             "{} ({})",
                             enum_type
@@ -207,7 +599,14 @@ pub fn format_object<'input>(
                     }
                 }
             }
-            Err(anyhow!("Failed to find enumerator case for '{}'"))
+            Ok(format!(
+                "{} ({})",
+                enum_type
+                    .name
+                    .clone()
+                    .unwrap_or("<<not parsed yet>>".to_string()),
+                value
+            ))
         }
         TypeInfo::TypeDef(type_def) => {
             if let Some(ty_offset) = type_def.ty {
@@ -217,7 +616,7 @@ pub fn format_object<'input>(
                         .name
                         .clone()
                         .unwrap_or("<<not parsed yet>>".to_string()),
-                    format_object(ty_offset, &memory, encoding, type_hash)?
+                    format_object(ty_offset, memory, encoding, type_hash, options)?
                 ))
             } else {
                 Ok(format!(
@@ -230,27 +629,36 @@ pub fn format_object<'input>(
             }
         }
         TypeInfo::ModifiedType(mod_type) => match mod_type.kind {
-            ModifierKind::Pointer | ModifierKind::Reference => {
-                let modifier = match mod_type.kind {
-                    ModifierKind::Pointer => "*",
-                    ModifierKind::Reference => "&",
-                    _ => unreachable!(),
-                };
+            ModifierKind::Pointer | ModifierKind::Reference | ModifierKind::RvalueReference => {
                 let mut bytes: [u8; 4] = Default::default();
-                bytes.copy_from_slice(&memory[0..4]);
-                Ok(format!(
-                    "{}{} (0x{:x})",
-                    type_name(mod_type.content_ty_offset, type_hash)?,
-                    modifier,
-                    u32::from_le_bytes(bytes)
-                ))
+                bytes.copy_from_slice(
+                    memory
+                        .get(0..4)
+                        .ok_or(anyhow!("failed to read pointer value"))?,
+                );
+                let address = u32::from_le_bytes(bytes);
+                let result = format!(
+                    "{} (0x{:x})",
+                    type_name(Some(ty_offset), type_hash)?,
+                    address
+                );
+                match format_pointee(
+                    mod_type.content_ty_offset,
+                    address as u64,
+                    encoding,
+                    type_hash,
+                    options,
+                )? {
+                    Some(pointee) => Ok(format!("{} {}", result, pointee)),
+                    None => Ok(result),
+                }
             }
             _ => {
                 if let Some(offset) = mod_type.content_ty_offset {
                     return Ok(format!(
                         "{}({})",
                         type_name(Some(ty_offset), type_hash)?,
-                        format_object(offset, memory, encoding, type_hash)?
+                        format_object(offset, memory, encoding, type_hash, options)?
                     ));
                 } else {
                     return Ok(format!(
@@ -262,3 +670,290 @@ pub fn format_object<'input>(
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding() -> gimli::Encoding {
+        gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 4,
+        }
+    }
+
+    fn format_base(encoding: gimli::DwAte, bytes: &[u8]) -> Result<String> {
+        let base_type = BaseTypeInfo {
+            name: "t".to_string(),
+            byte_size: bytes.len() as u64,
+            encoding,
+        };
+        format_base_value(&base_type, bytes)
+    }
+
+    #[test]
+    fn test_base_value() {
+        let f128_one = 0x3fffu128 << 112;
+        let f128_minus_half = (0xbffeu128) << 112;
+        let cases: Vec<(gimli::DwAte, Vec<u8>, &str)> = vec![
+            (gimli::DW_ATE_signed, vec![0xff], "-1"),
+            (gimli::DW_ATE_signed, (-2i16).to_le_bytes().to_vec(), "-2"),
+            (gimli::DW_ATE_signed, (-3i32).to_le_bytes().to_vec(), "-3"),
+            (
+                gimli::DW_ATE_signed,
+                i64::MIN.to_le_bytes().to_vec(),
+                "-9223372036854775808",
+            ),
+            (gimli::DW_ATE_signed, (-5i128).to_le_bytes().to_vec(), "-5"),
+            (gimli::DW_ATE_unsigned, vec![0xff], "255"),
+            (
+                gimli::DW_ATE_unsigned,
+                u16::MAX.to_le_bytes().to_vec(),
+                "65535",
+            ),
+            (
+                gimli::DW_ATE_unsigned,
+                u32::MAX.to_le_bytes().to_vec(),
+                "4294967295",
+            ),
+            (
+                gimli::DW_ATE_unsigned,
+                u64::MAX.to_le_bytes().to_vec(),
+                "18446744073709551615",
+            ),
+            (
+                gimli::DW_ATE_unsigned,
+                u128::MAX.to_le_bytes().to_vec(),
+                "340282366920938463463374607431768211455",
+            ),
+            // f16
+            (gimli::DW_ATE_float, 0x3e00u16.to_le_bytes().to_vec(), "1.5"),
+            (gimli::DW_ATE_float, 0xc000u16.to_le_bytes().to_vec(), "-2"),
+            (gimli::DW_ATE_float, 0x7c00u16.to_le_bytes().to_vec(), "inf"),
+            (gimli::DW_ATE_float, 0x7e00u16.to_le_bytes().to_vec(), "NaN"),
+            // Subnormal 2^-24
+            (
+                gimli::DW_ATE_float,
+                0x0001u16.to_le_bytes().to_vec(),
+                "0.00000005960464477539063",
+            ),
+            (gimli::DW_ATE_float, 0.25f32.to_le_bytes().to_vec(), "0.25"),
+            (
+                gimli::DW_ATE_float,
+                (-2.5f64).to_le_bytes().to_vec(),
+                "-2.5",
+            ),
+            // f128
+            (gimli::DW_ATE_float, f128_one.to_le_bytes().to_vec(), "1"),
+            (
+                gimli::DW_ATE_float,
+                f128_minus_half.to_le_bytes().to_vec(),
+                "-0.5",
+            ),
+            (
+                gimli::DW_ATE_complex_float,
+                [1f32.to_le_bytes(), 2f32.to_le_bytes()].concat(),
+                "1 + 2i",
+            ),
+            (gimli::DW_ATE_boolean, vec![0], "false"),
+            (gimli::DW_ATE_boolean, vec![1], "true"),
+            (gimli::DW_ATE_boolean, vec![0, 0, 0, 1], "true"),
+            (gimli::DW_ATE_UTF, vec![0x41], "'A'"),
+            (gimli::DW_ATE_UTF, 0x20acu16.to_le_bytes().to_vec(), "'€'"),
+            (gimli::DW_ATE_UTF, 0x1f600u32.to_le_bytes().to_vec(), "'😀'"),
+            // Lone surrogate
+            (
+                gimli::DW_ATE_UTF,
+                0xd800u32.to_le_bytes().to_vec(),
+                "'\\u{d800}'",
+            ),
+            (gimli::DW_ATE_signed_char, vec![0x41], "'A'"),
+            (gimli::DW_ATE_unsigned_char, vec![0xe9], "'é'"),
+            // Wider characters are integers
+            (gimli::DW_ATE_signed_char, vec![0xff, 0xff], "-1"),
+            (gimli::DW_ATE_unsigned_char, vec![0xff, 0xff], "65535"),
+            (gimli::DW_ATE_address, vec![0x10, 0, 0, 0], "0x10"),
+        ];
+        for (encoding, bytes, expected) in cases {
+            match format_base(encoding, &bytes) {
+                Ok(formatted) => assert_eq!(formatted, expected, "{} with {:?}", encoding, bytes),
+                Err(err) => panic!("failed to format {} with {:?}: {}", encoding, bytes, err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_base_value() {
+        assert!(format_base(gimli::DW_ATE_signed, &[0; 32]).is_err());
+        assert!(format_base(gimli::DW_ATE_signed, &[]).is_err());
+        assert!(format_base(gimli::DW_ATE_float, &[0; 3]).is_err());
+        assert!(format_base(gimli::DW_ATE_decimal_float, &[0; 4]).is_err());
+        // Memory shorter than the type
+        let base_type = BaseTypeInfo {
+            name: "int".to_string(),
+            byte_size: 4,
+            encoding: gimli::DW_ATE_signed,
+        };
+        match format_base_value(&base_type, &[0, 0]) {
+            Err(err) => assert_eq!(err.to_string(), "failed to read 4 bytes of 'int'"),
+            Ok(formatted) => panic!("short memory is formatted as {}", formatted),
+        }
+    }
+
+    const INT: usize = 1;
+    const NODE: usize = 2;
+    const NODE_POINTER: usize = 3;
+    const CHAR: usize = 4;
+    const CHAR_POINTER: usize = 5;
+
+    /// `struct N { int v; struct N *next; }` and `char *`
+    fn pointer_types() -> HashMap<usize, TypeInfo<Reader>> {
+        let member = |name: &str, ty, offset| Member {
+            name: Some(name.to_string()),
+            ty,
+            location: MemberLocation::ConstOffset(offset),
+            data_bit_offset: None,
+            bit_size: None,
+        };
+        let mut type_hash = HashMap::new();
+        type_hash.insert(
+            INT,
+            TypeInfo::BaseType(BaseTypeInfo {
+                name: "int".to_string(),
+                byte_size: 4,
+                encoding: gimli::DW_ATE_signed,
+            }),
+        );
+        type_hash.insert(
+            NODE,
+            TypeInfo::StructType(StructTypeInfo {
+                name: Some("N".to_string()),
+                members: vec![member("v", INT, 0), member("next", NODE_POINTER, 4)],
+                byte_size: 8,
+                declaration: false,
+            }),
+        );
+        type_hash.insert(
+            NODE_POINTER,
+            TypeInfo::ModifiedType(ModifiedTypeInfo {
+                content_ty_offset: Some(NODE),
+                kind: ModifierKind::Pointer,
+            }),
+        );
+        type_hash.insert(
+            CHAR,
+            TypeInfo::BaseType(BaseTypeInfo {
+                name: "char".to_string(),
+                byte_size: 1,
+                encoding: gimli::DW_ATE_signed_char,
+            }),
+        );
+        type_hash.insert(
+            CHAR_POINTER,
+            TypeInfo::ModifiedType(ModifiedTypeInfo {
+                content_ty_offset: Some(CHAR),
+                kind: ModifierKind::Pointer,
+            }),
+        );
+        type_hash
+    }
+
+    /// The node at 0x10 points to the node at 0x18, which points back to the first one.
+    /// C strings are at 0x20 and at the end.
+    fn linked_memory() -> Vec<u8> {
+        let mut memory = vec![0; 0x30];
+        memory[0x10..0x14].copy_from_slice(&1i32.to_le_bytes());
+        memory[0x14..0x18].copy_from_slice(&0x18u32.to_le_bytes());
+        memory[0x18..0x1c].copy_from_slice(&2i32.to_le_bytes());
+        memory[0x1c..0x20].copy_from_slice(&0x10u32.to_le_bytes());
+        memory[0x20..0x23].copy_from_slice(b"hi\0");
+        memory[0x2e..0x30].copy_from_slice(b"ab");
+        memory
+    }
+
+    fn format_pointer(ty: usize, address: u32, max_depth: usize) -> String {
+        let memory = linked_memory();
+        let options = FormatOptions {
+            linear_memory: Some(&memory),
+            max_depth,
+        };
+        format_object(
+            ty,
+            &address.to_le_bytes(),
+            encoding(),
+            &pointer_types(),
+            &options,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_follow_pointer() {
+        assert_eq!(format_pointer(NODE_POINTER, 0x10, 0), "N* (0x10)");
+        assert_eq!(
+            format_pointer(NODE_POINTER, 0x10, 1),
+            "N* (0x10) -> N {\n  v: int(1),\n  next: N* (0x18)\n}"
+        );
+        // The cycle is followed only up to the depth
+        assert_eq!(
+            format_pointer(NODE_POINTER, 0x10, 3),
+            [
+                "N* (0x10) -> N {",
+                "  v: int(1),",
+                "  next: N* (0x18) -> N {",
+                "    v: int(2),",
+                "    next: N* (0x10) -> N {",
+                "      v: int(1),",
+                "      next: N* (0x18)",
+                "    }",
+                "  }",
+                "}",
+            ]
+            .join("\n")
+        );
+        assert_eq!(format_pointer(NODE_POINTER, 0, 3), "N* (0x0)");
+        assert_eq!(
+            format_pointer(NODE_POINTER, 0x1000, 3),
+            "N* (0x1000) <<out of bounds>>"
+        );
+    }
+
+    #[test]
+    fn test_c_string() {
+        // Strings are shown even if no pointer is followed
+        assert_eq!(format_pointer(CHAR_POINTER, 0x20, 0), "char* (0x20) \"hi\"");
+        // Unterminated at the end of the memory
+        assert_eq!(
+            format_pointer(CHAR_POINTER, 0x2e, 0),
+            "char* (0x2e) \"ab\"..."
+        );
+    }
+
+    #[test]
+    fn test_swift_raw_pointer() {
+        let mut type_hash = HashMap::new();
+        type_hash.insert(
+            1,
+            TypeInfo::StructType(StructTypeInfo {
+                name: Some("UnsafeRawPointer".to_string()),
+                members: vec![],
+                byte_size: 4,
+                declaration: false,
+            }),
+        );
+        let options = FormatOptions {
+            linear_memory: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+        };
+        let formatted = format_object(1, &[0x10, 0, 0, 0], encoding(), &type_hash, &options);
+        assert_eq!(formatted.unwrap(), "UnsafeRawPointer (0x10)");
+        match format_object(1, &[0x10, 0], encoding(), &type_hash, &options) {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "failed to read 4 bytes of 'UnsafeRawPointer'"
+            ),
+            Ok(formatted) => panic!("short memory is formatted as {}", formatted),
+        }
+    }
+}
@@ -1,80 +1,149 @@
 use anyhow::{anyhow, Result};
 use gimli::{
-    AttributeValue, DebugAbbrev, DebugAddr, DebugInfo, DebugLine, DebugLineStr, DebugLoc,
-    DebugLocLists, DebugRanges, DebugRngLists, DebugStr, DebugStrOffsets, DebugTypes,
-    DebuggingInformationEntry, EndianSlice, LineRow, LittleEndian, LocationLists, RangeLists, Unit,
+    AttributeValue, DebugLine, DebuggingInformationEntry, EndianRcSlice, LineRow, LittleEndian,
+    Reader as _, Unit,
 };
 use log::trace;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
 use wasmparser::{ModuleReader, SectionCode};
 
+mod eval;
+mod expr;
 mod format;
 mod types;
 mod utils;
 
 use utils::*;
 
-type Reader<'input> = gimli::EndianSlice<'input, LittleEndian>;
-pub type Dwarf<'input> = gimli::Dwarf<Reader<'input>>;
+// Sections are copied out of the module so that the debug info doesn't borrow it
+type Reader = EndianRcSlice<LittleEndian>;
+pub type Dwarf = gimli::Dwarf<Reader>;
+type DwarfPackage = gimli::DwarfPackage<Reader>;
 
-pub fn parse_dwarf<'a>(module: &'a [u8]) -> Result<Dwarf<'a>> {
-    const EMPTY_SECTION: &[u8] = &[];
+fn custom_sections(module: &[u8]) -> Result<HashMap<&str, &[u8]>> {
     let mut reader = ModuleReader::new(module)?;
     let mut sections = HashMap::new();
     while !reader.eof() {
-        let section = reader.read().expect("section");
+        let section = reader.read()?;
         match section.code {
             SectionCode::Custom { name, kind: _ } => {
                 let mut reader = section.get_binary_reader();
                 let len = reader.bytes_remaining();
-                sections.insert(name, reader.read_bytes(len).expect("bytes"));
+                sections.insert(name, reader.read_bytes(len)?);
             }
             _ => (),
         }
     }
-    let endian = LittleEndian;
-    let debug_str = DebugStr::new(sections.get(".debug_str").unwrap(), endian);
-    let debug_abbrev = DebugAbbrev::new(sections.get(".debug_abbrev").unwrap(), endian);
-    let debug_info = DebugInfo::new(sections.get(".debug_info").unwrap(), endian);
-    let debug_line = DebugLine::new(sections.get(".debug_line").unwrap(), endian);
-    let debug_addr = DebugAddr::from(EndianSlice::new(EMPTY_SECTION, endian));
-    let debug_line_str = DebugLineStr::from(EndianSlice::new(EMPTY_SECTION, endian));
-    let debug_str_sup = DebugStr::from(EndianSlice::new(EMPTY_SECTION, endian));
-    let debug_ranges = match sections.get(".debug_ranges") {
-        Some(section) => DebugRanges::new(section, endian),
-        None => DebugRanges::new(EMPTY_SECTION, endian),
+    Ok(sections)
+}
+
+/// Stripping tools may leave only a part of sections, so treat missing ones as empty
+fn load_section(sections: &HashMap<&str, &[u8]>, name: Option<&str>) -> Reader {
+    let bytes = name.and_then(|name| sections.get(name)).copied();
+    EndianRcSlice::new(Rc::from(bytes.unwrap_or_default()), LittleEndian)
+}
+
+pub fn parse_dwarf(module: &[u8]) -> Result<Dwarf> {
+    let sections = custom_sections(module)?;
+    if !sections.contains_key(".debug_info") {
+        return Err(anyhow!("no DWARF debug information"));
+    }
+    Dwarf::load(|id| -> Result<_> { Ok(load_section(&sections, Some(id.name()))) })
+}
+
+/// Path to the separated DWARF file recorded in `external_debug_info` section
+pub fn external_debug_info(module: &[u8]) -> Result<Option<String>> {
+    match custom_sections(module)?.get("external_debug_info") {
+        Some(bytes) => {
+            let mut reader = wasmparser::BinaryReader::new(bytes);
+            Ok(Some(reader.read_string()?.to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Load the split DWARF object referred by a skeleton unit of `-gsplit-dwarf`
+fn load_dwo(parent: &Dwarf, path: &Path) -> Result<Dwarf> {
+    let bytes =
+        std::fs::read(path).map_err(|err| anyhow!("failed to read {}: {}", path.display(), err))?;
+    let sections = custom_sections(&bytes)?;
+    let mut dwo = Dwarf::load(|id| -> Result<_> { Ok(load_section(&sections, id.dwo_name())) })?;
+    dwo.make_dwo(parent);
+    Ok(dwo)
+}
+
+/// Load the DWARF package `<path>.dwp` built by `llvm-dwp` if it exists
+fn load_dwarf_package(path: &Path) -> Result<Option<DwarfPackage>> {
+    let mut dwp_path = path.as_os_str().to_owned();
+    dwp_path.push(".dwp");
+    let dwp_path = Path::new(&dwp_path);
+    let bytes = match std::fs::read(dwp_path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(anyhow!("failed to read {}: {}", dwp_path.display(), err)),
+    };
+    let sections = custom_sections(&bytes)?;
+    let package = DwarfPackage::load(
+        |id| -> Result<_> { Ok(load_section(&sections, id.dwo_name())) },
+        load_section(&sections, None),
+    )?;
+    Ok(Some(package))
+}
+
+/// Find the split unit of a skeleton unit in the DWARF package, or in the `.dwo` file
+/// recorded in the skeleton
+fn load_split_unit(
+    dwarf: &Dwarf,
+    skeleton: &Unit<Reader>,
+    package: Option<&DwarfPackage>,
+) -> Result<Option<(Dwarf, Unit<Reader>)>> {
+    let dwo_id = match skeleton.dwo_id {
+        Some(dwo_id) => dwo_id,
+        None => return Ok(None),
     };
-    let debug_rnglists = DebugRngLists::new(EMPTY_SECTION, endian);
-    let ranges = RangeLists::new(debug_ranges, debug_rnglists);
-    let debug_loc = match sections.get(".debug_loc") {
-        Some(section) => DebugLoc::new(section, endian),
-        None => DebugLoc::new(EMPTY_SECTION, endian),
+    let dwo = match package {
+        Some(package) => package.find_cu(dwo_id, dwarf)?,
+        None => None,
     };
-    let debug_loclists = DebugLocLists::new(EMPTY_SECTION, endian);
-    let locations = LocationLists::new(debug_loc, debug_loclists);
-    let debug_str_offsets = DebugStrOffsets::from(EndianSlice::new(EMPTY_SECTION, endian));
-    let debug_types = DebugTypes::from(EndianSlice::new(EMPTY_SECTION, endian));
-
-    Ok(Dwarf {
-        debug_abbrev,
-        debug_addr,
-        debug_info,
-        debug_line,
-        debug_line_str,
-        debug_str,
-        debug_str_offsets,
-        debug_str_sup,
-        debug_types,
-        locations,
-        ranges,
-    })
+    let dwo = match dwo {
+        Some(dwo) => dwo,
+        None => {
+            let dwo_name = match skeleton.dwo_name()? {
+                Some(attr) => clone_string_attribute(dwarf, skeleton, attr)?,
+                None => return Err(anyhow!("split unit {:#x} is not found", dwo_id.0)),
+            };
+            let path = match &skeleton.comp_dir {
+                Some(comp_dir) => Path::new(comp_dir.to_string_lossy()?.as_ref()).join(dwo_name),
+                None => Path::new(&dwo_name).to_path_buf(),
+            };
+            load_dwo(dwarf, &path)?
+        }
+    };
+    let mut headers = dwo.units();
+    while let Some(header) = headers.next()? {
+        let mut unit = dwo.unit(header)?;
+        if unit.dwo_id == Some(dwo_id) {
+            unit.copy_relocated_attributes(skeleton);
+            return Ok(Some((dwo, unit)));
+        }
+    }
+    Err(anyhow!("split unit {:#x} is not found", dwo_id.0))
 }
 
-pub struct DwarfDebugInfo<'a> {
+/// Read DWARF of the module at `path`. Split units are looked up in `<path>.dwp` first,
+/// and then in `.dwo` files.
+pub fn load_debug_info(path: &Path, module: &[u8]) -> Result<DwarfDebugInfo> {
+    let dwarf = parse_dwarf(module)?;
+    let package = load_dwarf_package(path)?;
+    transform_dwarf(dwarf, package.as_ref())
+}
+
+pub struct DwarfDebugInfo {
     pub sourcemap: DwarfSourceMap,
-    pub subroutine: DwarfSubroutineMap<'a>,
+    pub subroutine: DwarfSubroutineMap,
 }
-pub fn transform_dwarf<'a>(dwarf: Dwarf<'a>) -> Result<DwarfDebugInfo<'a>> {
+fn transform_dwarf(dwarf: Dwarf, package: Option<&DwarfPackage>) -> Result<DwarfDebugInfo> {
     let mut headers = dwarf.units();
     let mut sourcemaps = Vec::new();
     let mut subroutines = Vec::new();
@@ -87,13 +156,25 @@ pub fn transform_dwarf<'a>(dwarf: Dwarf<'a>) -> Result<DwarfDebugInfo<'a>> {
             Some((_, entry)) => entry,
             None => continue,
         };
-        sourcemaps.push(transform_debug_line(
-            &unit,
-            root,
-            &dwarf,
-            &dwarf.debug_line,
-        )?);
-        subroutines.append(&mut transform_subprogram(&dwarf, &unit)?);
+        // Units without line programs have nothing to map
+        let unit_sourcemap = if root.attr_value(gimli::DW_AT_stmt_list)?.is_some() {
+            sourcemaps.push(transform_debug_line(
+                &unit,
+                root,
+                &dwarf,
+                &dwarf.debug_line,
+            )?);
+            sourcemaps.last()
+        } else {
+            None
+        };
+        // Skeleton unit of split DWARF has only the line program and the rest is in .dwo
+        if let Some((dwo, dwo_unit)) = load_split_unit(&dwarf, &unit, package)? {
+            subroutines.append(&mut transform_subprogram(&dwo, &dwo_unit, unit_sourcemap)?);
+            get_types(&dwo, &dwo_unit, &mut type_hash)?;
+            continue;
+        }
+        subroutines.append(&mut transform_subprogram(&dwarf, &unit, unit_sourcemap)?);
         get_types(&dwarf, &unit, &mut type_hash)?;
     }
     Ok(DwarfDebugInfo {
@@ -101,6 +182,7 @@ pub fn transform_dwarf<'a>(dwarf: Dwarf<'a>) -> Result<DwarfDebugInfo<'a>> {
         subroutine: DwarfSubroutineMap {
             subroutines,
             type_hash,
+            max_depth: Cell::new(format::DEFAULT_MAX_DEPTH),
         },
     })
 }
@@ -118,25 +200,69 @@ where
 #[derive(Clone)]
 enum VariableContent<R: gimli::Reader> {
     Location(gimli::AttributeValue<R>),
+    /// Locations which depend on the code offset
+    LocationList(Vec<gimli::LocationListEntry<R>>),
     ConstValue(Vec<u8>),
     Unknown { debug_info: String },
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SubroutineKind {
+    Function,
+    LexicalBlock,
+    /// Inlined instance of a function, shown as a virtual frame
+    Inlined,
+}
+
+/// Scope which has a code range. A scope with `DW_AT_ranges` is split into one per range
+#[derive(Clone)]
 pub struct Subroutine<R: gimli::Reader> {
     pub name: Option<String>,
+    pub kind: SubroutineKind,
+    /// Number of enclosing scopes, used to find the innermost one
+    pub depth: usize,
     pub pc: std::ops::Range<u64>,
     pub variables: Vec<SymbolVariable<R>>,
     pub encoding: gimli::Encoding,
+    /// Return type of the function. Always `None` for lexical blocks
+    pub ty_offset: Option<R::Offset>,
+    /// `DW_AT_frame_base` of the function. Lexical blocks and inlined subroutines inherit it
+    /// from the function
+    pub frame_base: Option<Expression<R>>,
+    /// Source location of the call site, only for inlined subroutines
+    pub call_file: Option<String>,
+    pub call_line: Option<u64>,
+}
+
+/// Scopes of the innermost (possibly inlined) frame at `offset`, innermost first
+pub fn frame_scopes<R: gimli::Reader>(
+    subroutines: &[Subroutine<R>],
+    offset: u64,
+) -> Vec<&Subroutine<R>> {
+    let mut scopes: Vec<_> = subroutines
+        .iter()
+        .filter(|s| s.pc.contains(&offset))
+        .collect();
+    scopes.sort_by_key(|s| std::cmp::Reverse(s.depth));
+    match scopes
+        .iter()
+        .position(|s| s.kind != SubroutineKind::LexicalBlock)
+    {
+        Some(frame) => scopes.truncate(frame + 1),
+        None => scopes.clear(),
+    }
+    scopes
 }
 
 pub fn transform_subprogram<R: gimli::Reader>(
     dwarf: &gimli::Dwarf<R>,
     unit: &Unit<R, R::Offset>,
+    sourcemap: Option<&DwarfUnitSourceMap>,
 ) -> Result<Vec<Subroutine<R>>> {
     let mut tree = unit.entries_tree(None)?;
     let root = tree.root()?;
     let mut subroutines = vec![];
-    transform_subprogram_rec(root, dwarf, unit, &mut subroutines)?;
+    transform_subprogram_rec(root, dwarf, unit, None, sourcemap, 0, &mut subroutines)?;
     Ok(subroutines)
 }
 
@@ -144,40 +270,95 @@ pub fn transform_subprogram_rec<R: gimli::Reader>(
     node: gimli::EntriesTreeNode<R>,
     dwarf: &gimli::Dwarf<R>,
     unit: &Unit<R, R::Offset>,
+    frame_base: Option<Expression<R>>,
+    sourcemap: Option<&DwarfUnitSourceMap>,
+    depth: usize,
     out_subroutines: &mut Vec<Subroutine<R>>,
 ) -> Result<()> {
     let mut subroutine = None;
-    match node.entry().tag() {
-        gimli::DW_TAG_subprogram | gimli::DW_TAG_lexical_block => {
-            let name = match node.entry().attr_value(gimli::DW_AT_name)? {
-                Some(attr) => Some(clone_string_attribute(dwarf, unit, attr)?),
+    let mut ranges = vec![];
+    let frame_base = match node.entry().attr_value(gimli::DW_AT_frame_base)? {
+        Some(AttributeValue::Exprloc(expr)) => Some(expr),
+        _ => frame_base,
+    };
+    let kind = match node.entry().tag() {
+        gimli::DW_TAG_subprogram => Some(SubroutineKind::Function),
+        gimli::DW_TAG_lexical_block => Some(SubroutineKind::LexicalBlock),
+        gimli::DW_TAG_inlined_subroutine => Some(SubroutineKind::Inlined),
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        let entry = node.entry();
+        let name = match attr_value_with_origin(unit, entry, gimli::DW_AT_name)? {
+            Some(attr) => Some(clone_string_attribute(dwarf, unit, attr)?),
+            None => None,
+        };
+
+        let ty = match attr_value_with_origin(unit, entry, gimli::DW_AT_type)? {
+            Some(AttributeValue::UnitRef(ref offset)) => Some(offset.0),
+            _ => None,
+        };
+
+        let low_pc_attr = entry.attr_value(gimli::DW_AT_low_pc)?;
+        trace!("low_pc_attr: {:?}", low_pc_attr);
+        let high_pc_attr = entry.attr_value(gimli::DW_AT_high_pc)?;
+        trace!("high_pc_attr: {:?}", high_pc_attr);
+        let low_pc = match low_pc_attr {
+            Some(attr) => attr_address(dwarf, unit, attr)?,
+            None => None,
+        };
+        if let Some(low_pc) = low_pc {
+            let high_pc = match high_pc_attr {
+                Some(attr) => match attr_address(dwarf, unit, attr.clone())? {
+                    Some(high_pc) => Some(high_pc),
+                    // Constant forms are the size of the range. Ignore other malformed forms.
+                    None => attr.udata_value().map(|size| low_pc + size),
+                },
                 None => None,
             };
-
-            let low_pc_attr = node.entry().attr_value(gimli::DW_AT_low_pc)?;
-            trace!("low_pc_attr: {:?}", low_pc_attr);
-            let high_pc_attr = node.entry().attr_value(gimli::DW_AT_high_pc)?;
-            trace!("high_pc_attr: {:?}", high_pc_attr);
-            if let Some(AttributeValue::Addr(low_pc)) = low_pc_attr {
-                let high_pc = match high_pc_attr {
-                    Some(AttributeValue::Udata(size)) => Some(low_pc + size),
-                    Some(AttributeValue::Addr(high_pc)) => Some(high_pc),
-                    Some(x) => unreachable!("high_pc can't be {:?}", x),
-                    None => None,
-                };
-                if let Some(high_pc) = high_pc {
-                    subroutine = Some(Subroutine {
-                        pc: low_pc..high_pc,
-                        name,
-                        encoding: unit.encoding(),
-                        variables: vec![],
-                    });
+            if let Some(high_pc) = high_pc {
+                ranges.push(low_pc..high_pc);
+            }
+        }
+        // Inlined subroutines and blocks are often split into several ranges by optimization
+        if let Some(attr) = entry.attr_value(gimli::DW_AT_ranges)? {
+            if let Some(offset) = dwarf.attr_ranges_offset(unit, attr)? {
+                let mut iter = dwarf.ranges(unit, offset)?;
+                while let Some(range) = iter.next()? {
+                    ranges.push(range.begin..range.end);
                 }
             }
         }
-        _ => (),
+
+        let call_file = match entry.attr_value(gimli::DW_AT_call_file)? {
+            Some(AttributeValue::FileIndex(index)) => {
+                sourcemap.and_then(|sourcemap| sourcemap.file_path(index))
+            }
+            _ => None,
+        };
+        let call_line = entry
+            .attr_value(gimli::DW_AT_call_line)?
+            .and_then(|attr| attr.udata_value());
+        if let Some(range) = ranges.first() {
+            subroutine = Some(Subroutine {
+                pc: range.clone(),
+                name,
+                kind,
+                depth,
+                encoding: unit.encoding(),
+                variables: vec![],
+                ty_offset: ty,
+                frame_base: frame_base.clone(),
+                call_file,
+                call_line,
+            });
+        }
     }
 
+    let child_depth = match subroutine {
+        Some(_) => depth + 1,
+        None => depth,
+    };
     let mut children = node.children();
     while let Some(child) = children.next()? {
         match child.entry().tag() {
@@ -188,12 +369,26 @@ pub fn transform_subprogram_rec<R: gimli::Reader>(
                 }
             }
             _ => {
-                transform_subprogram_rec(child, dwarf, unit, out_subroutines)?;
+                transform_subprogram_rec(
+                    child,
+                    dwarf,
+                    unit,
+                    frame_base.clone(),
+                    sourcemap,
+                    child_depth,
+                    out_subroutines,
+                )?;
             }
         }
     }
 
     if let Some(subroutine) = subroutine.take() {
+        for range in ranges.iter().skip(1) {
+            out_subroutines.push(Subroutine {
+                pc: range.clone(),
+                ..subroutine.clone()
+            });
+        }
         out_subroutines.push(subroutine);
     }
 
@@ -210,32 +405,51 @@ fn transform_variable<R: gimli::Reader>(
     };
     let mut has_explicit_location = false;
     if let Some(location) = entry.attr_value(gimli::DW_AT_location)? {
-        content = VariableContent::Location(location);
+        content = match dwarf.attr_locations(unit, location.clone())? {
+            Some(mut entries) => {
+                let mut list = vec![];
+                while let Some(entry) = entries.next()? {
+                    list.push(entry);
+                }
+                VariableContent::LocationList(list)
+            }
+            None => VariableContent::Location(location),
+        };
         has_explicit_location = true;
     }
     if let Some(constant) = entry.attr_value(gimli::DW_AT_const_value)? {
         if !has_explicit_location {
             // TODO: support big endian
-            let bytes = match constant {
-                AttributeValue::Block(block) => block.to_slice()?.to_vec(),
-                AttributeValue::Data1(b) => vec![b],
-                AttributeValue::Data2(b) => b.to_le_bytes().to_vec(),
-                AttributeValue::Data4(b) => b.to_le_bytes().to_vec(),
-                AttributeValue::Data8(b) => b.to_le_bytes().to_vec(),
-                AttributeValue::Sdata(b) => b.to_le_bytes().to_vec(),
-                AttributeValue::Udata(b) => b.to_le_bytes().to_vec(),
-                AttributeValue::String(b) => b.to_slice()?.to_vec(),
-                _ => unimplemented!(),
+            content = match constant {
+                AttributeValue::Block(block) => {
+                    VariableContent::ConstValue(block.to_slice()?.to_vec())
+                }
+                AttributeValue::Data1(b) => VariableContent::ConstValue(vec![b]),
+                AttributeValue::Data2(b) => VariableContent::ConstValue(b.to_le_bytes().to_vec()),
+                AttributeValue::Data4(b) => VariableContent::ConstValue(b.to_le_bytes().to_vec()),
+                AttributeValue::Data8(b) => VariableContent::ConstValue(b.to_le_bytes().to_vec()),
+                AttributeValue::Sdata(b) => VariableContent::ConstValue(b.to_le_bytes().to_vec()),
+                AttributeValue::Udata(b) => VariableContent::ConstValue(b.to_le_bytes().to_vec()),
+                AttributeValue::String(_)
+                | AttributeValue::DebugStrRef(_)
+                | AttributeValue::DebugStrRefSup(_)
+                | AttributeValue::DebugLineStrRef(_)
+                | AttributeValue::DebugStrOffsetsIndex(_) => VariableContent::ConstValue(
+                    dwarf.attr_string(unit, constant)?.to_slice()?.to_vec(),
+                ),
+                // Reported when the variable is evaluated not to fail loading the whole debug info
+                attr => VariableContent::Unknown {
+                    debug_info: format!("unsupported constant form {:?}", attr),
+                },
             };
-            content = VariableContent::ConstValue(bytes);
         }
     }
-    let name = match entry.attr_value(gimli::DW_AT_name)? {
+    let name = match attr_value_with_origin(unit, entry, gimli::DW_AT_name)? {
         Some(name_attr) => Some(clone_string_attribute(dwarf, unit, name_attr)?),
         None => None,
     };
 
-    let ty = match entry.attr_value(gimli::DW_AT_type)? {
+    let ty = match attr_value_with_origin(unit, entry, gimli::DW_AT_type)? {
         Some(AttributeValue::UnitRef(ref offset)) => Some(offset.0),
         _ => None,
     };
@@ -249,19 +463,139 @@ fn transform_variable<R: gimli::Reader>(
 use gimli::Expression;
 fn evaluate_variable_location<R: gimli::Reader>(
     encoding: gimli::Encoding,
-    rbp: u32,
+    initial_value: Option<u64>,
+    frame_base: Option<u64>,
     expr: Expression<R>,
 ) -> Result<Vec<gimli::Piece<R>>> {
     let mut evaluation = expr.evaluation(encoding);
-    evaluation.set_initial_value(rbp.into());
-    let result = evaluation.evaluate()?;
+    if let Some(initial_value) = initial_value {
+        evaluation.set_initial_value(initial_value);
+    }
+    let mut result = evaluation.evaluate()?;
     use gimli::EvaluationResult;
-    match result {
-        EvaluationResult::Complete => Ok(evaluation.result()),
-        x => unimplemented!("{:?}", x),
+    loop {
+        match result {
+            EvaluationResult::Complete => return Ok(evaluation.result()),
+            EvaluationResult::RequiresFrameBase => {
+                let frame_base = frame_base.ok_or(anyhow!("failed to get frame base"))?;
+                result = evaluation.resume_with_frame_base(frame_base)?;
+            }
+            x => return Err(anyhow!("unsupported evaluation requirement {:?}", x)),
+        }
     }
 }
 
+/// Storage referred by the `DW_OP_WASM_location` extension
+#[derive(Clone, Copy, Debug)]
+enum WasmLocation {
+    Local(usize),
+    Global(usize),
+    /// Index from the bottom of the operand stack of the current function
+    Stack(usize),
+}
+
+#[derive(Debug)]
+enum PieceLocation<R: gimli::Reader> {
+    /// The piece itself lives in the wasm storage
+    Wasm(WasmLocation),
+    Dwarf(gimli::Location<R>),
+}
+
+#[derive(Debug)]
+struct LocationPiece<R: gimli::Reader> {
+    /// `None` unless the location is composed of `DW_OP_piece`s
+    size_in_bits: Option<u64>,
+    /// Offset from the least significant bit of the location
+    bit_offset: Option<u64>,
+    location: PieceLocation<R>,
+}
+
+/// Evaluate a location description which may contain `DW_OP_WASM_location`.
+/// gimli parses the operator but can't evaluate it, so each piece starting with it is
+/// resolved here with the value returned by `read_wasm`, and the rest is left to gimli.
+fn evaluate_wasm_location<R: gimli::Reader>(
+    encoding: gimli::Encoding,
+    frame_base: Option<u64>,
+    expr: Expression<R>,
+    read_wasm: &dyn Fn(WasmLocation) -> Result<u64>,
+) -> Result<Vec<LocationPiece<R>>> {
+    let mut pieces = vec![];
+    let mut rest = expr.0;
+    while !rest.is_empty() {
+        // A piece ends with `DW_OP_piece` or at the end of the expression
+        let mut ops = rest.clone();
+        let mut wasm_location = None;
+        let mut head_len = None;
+        let mut only_storage = true;
+        let mut size_in_bits = None;
+        let mut bit_offset = None;
+        while !ops.is_empty() {
+            let is_head = head_len.is_none();
+            let location = match gimli::Operation::parse(&mut ops, encoding)? {
+                gimli::Operation::WasmLocal { index } => Some(WasmLocation::Local(index as usize)),
+                gimli::Operation::WasmGlobal { index } => {
+                    Some(WasmLocation::Global(index as usize))
+                }
+                gimli::Operation::WasmStack { index } => Some(WasmLocation::Stack(index as usize)),
+                gimli::Operation::Piece {
+                    size_in_bits: size,
+                    bit_offset: offset,
+                } => {
+                    size_in_bits = Some(size);
+                    bit_offset = offset;
+                    break;
+                }
+                gimli::Operation::StackValue => None,
+                _ => {
+                    only_storage = false;
+                    None
+                }
+            };
+            if location.is_some() {
+                if !is_head {
+                    return Err(anyhow!(
+                        "DW_OP_WASM_location is supported only at the head of a piece"
+                    ));
+                }
+                wasm_location = location;
+            }
+            if is_head {
+                head_len = Some(ops.offset_from(&rest));
+            }
+        }
+        let mut piece = rest.split(ops.offset_from(&rest))?;
+        match wasm_location {
+            Some(location) if only_storage => pieces.push(LocationPiece {
+                size_in_bits,
+                bit_offset,
+                location: PieceLocation::Wasm(location),
+            }),
+            _ => {
+                // The rest of operations compute a location from the value in the wasm storage
+                let initial_value = match wasm_location {
+                    Some(location) => {
+                        piece.skip(head_len.unwrap())?;
+                        Some(read_wasm(location)?)
+                    }
+                    None => None,
+                };
+                let evaluated = evaluate_variable_location(
+                    encoding,
+                    initial_value,
+                    frame_base,
+                    Expression(piece),
+                )?;
+                pieces.extend(evaluated.into_iter().map(|piece| LocationPiece {
+                    size_in_bits: piece.size_in_bits,
+                    bit_offset: piece.bit_offset,
+                    location: PieceLocation::Dwarf(piece.location),
+                }));
+            }
+        }
+    }
+    Ok(pieces)
+}
+
 use std::path::Path;
 
 pub fn transform_debug_line<R: gimli::Reader>(
@@ -277,9 +611,7 @@ pub fn transform_debug_line<R: gimli::Reader>(
         }
     };
 
-    let program = debug_line
-        .program(offset, unit.header.address_size(), None, None)
-        .expect("parsable debug_line");
+    let program = debug_line.program(offset, unit.header.address_size(), None, None)?;
 
     let header = program.header();
 
@@ -296,13 +628,21 @@ pub fn transform_debug_line<R: gimli::Reader>(
     }
     let mut files = Vec::new();
     for file_entry in header.file_names() {
-        let dir = dirs[file_entry.directory_index() as usize].clone();
-        let dir_path = Path::new(&dir);
-        let mut path = dir_path.join(clone_string_attribute(dwarf, unit, file_entry.path_name())?);
+        let name = clone_string_attribute(dwarf, unit, file_entry.path_name())?;
+        let dir = dirs
+            .get(file_entry.directory_index() as usize)
+            .ok_or_else(|| {
+                anyhow!(
+                    "directory index {} of '{}' is out of range",
+                    file_entry.directory_index(),
+                    name
+                )
+            })?;
+        let mut path = Path::new(dir).join(name);
         if !path.is_absolute() {
             if let Some(comp_dir) = unit.comp_dir.clone() {
-                let comp_dir = String::from_utf8(comp_dir.to_slice()?.to_vec()).unwrap();
-                path = Path::new(&comp_dir).join(path);
+                let comp_dir = comp_dir.to_string()?;
+                path = Path::new(comp_dir.as_ref()).join(path);
             }
         }
         files.push(path);
@@ -329,20 +669,24 @@ pub struct DwarfUnitSourceMap {
 
 use super::commands::sourcemap;
 impl DwarfUnitSourceMap {
+    fn file_path(&self, file_index: u64) -> Option<String> {
+        let index = (file_index as usize).checked_sub(self.sequence_base_index)?;
+        Some(self.paths.get(index)?.to_str()?.to_string())
+    }
+
     fn transform_lineinfo(&self, row: &LineRow) -> sourcemap::LineInfo {
-        let filepath = self.paths[row.file_index() as usize - self.sequence_base_index].clone();
         sourcemap::LineInfo {
-            filepath: filepath.to_str().unwrap().to_string(),
-            line: row.line(),
+            filepath: self.file_path(row.file_index()).unwrap(),
+            line: row.line().map(|line| line.get()),
             column: match row.column() {
-                gimli::ColumnType::Column(c) => sourcemap::ColumnType::Column(c),
+                gimli::ColumnType::Column(c) => sourcemap::ColumnType::Column(c.get()),
                 gimli::ColumnType::LeftEdge => sourcemap::ColumnType::LeftEdge,
             },
         }
     }
 }
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 pub struct DwarfSourceMap {
     address_sorted_rows: Vec<(u64, sourcemap::LineInfo)>,
     directory_map: RefCell<HashMap<String, String>>,
@@ -362,6 +706,13 @@ impl DwarfSourceMap {
             directory_map: RefCell::new(HashMap::new()),
         }
     }
+
+    fn map_directory(&self, mut line_info: sourcemap::LineInfo) -> sourcemap::LineInfo {
+        for (from, to) in self.directory_map.borrow().iter() {
+            line_info.filepath = line_info.filepath.replace(from, to);
+        }
+        line_info
+    }
 }
 
 impl sourcemap::SourceMap for DwarfSourceMap {
@@ -369,7 +720,7 @@ impl sourcemap::SourceMap for DwarfSourceMap {
         self.directory_map.borrow_mut().insert(from, to);
     }
     fn find_line_info(&self, offset: usize) -> Option<sourcemap::LineInfo> {
-        let mut line_info = match self
+        let line_info = match self
             .address_sorted_rows
             .binary_search_by_key(&(offset as u64), |i| i.0)
         {
@@ -382,35 +733,43 @@ impl sourcemap::SourceMap for DwarfSourceMap {
                 }
             }
         };
-        for (from, to) in self.directory_map.borrow().iter() {
-            line_info.filepath = line_info.filepath.replace(from, to);
-        }
-        Some(line_info)
+        Some(self.map_directory(line_info))
+    }
+
+    fn line_infos(&self) -> Vec<sourcemap::LineInfo> {
+        self.address_sorted_rows
+            .iter()
+            .map(|(_, line_info)| self.map_directory(line_info.clone()))
+            .collect()
     }
 }
 
 use super::commands::subroutine;
+use super::commands::value::value_bytes;
 use types::*;
-pub struct DwarfSubroutineMap<'input> {
-    pub subroutines: Vec<Subroutine<Reader<'input>>>,
-    type_hash: HashMap<usize, TypeInfo<Reader<'input>>>,
+use wasminspect_vm::WasmValue;
+pub struct DwarfSubroutineMap {
+    pub subroutines: Vec<Subroutine<Reader>>,
+    type_hash: HashMap<usize, TypeInfo<Reader>>,
+    /// Levels of pointers to follow when printing values
+    max_depth: Cell<usize>,
 }
 
-impl<'input> subroutine::SubroutineMap for DwarfSubroutineMap<'input> {
+impl subroutine::SubroutineMap for DwarfSubroutineMap {
     fn variable_name_list(&self, code_offset: usize) -> Result<Vec<subroutine::Variable>> {
-        let offset = &(code_offset as u64);
-        let subroutine = match self
-            .subroutines
-            .iter()
-            .filter(|s| s.pc.contains(offset))
-            .next()
-        {
-            Some(s) => s,
-            None => return Err(anyhow!("failed to determine subroutine")),
-        };
-        Ok(subroutine
-            .variables
+        let scopes = frame_scopes(&self.subroutines, code_offset as u64);
+        if scopes.is_empty() {
+            return Err(anyhow!("failed to determine subroutine"));
+        }
+        // Variables in inner blocks shadow the outer ones
+        let mut seen = HashSet::new();
+        Ok(scopes
             .iter()
+            .flat_map(|s| s.variables.iter())
+            .filter(|var| match &var.name {
+                Some(name) => seen.insert(name.clone()),
+                None => true,
+            })
             .map(|var| {
                 let mut v = subroutine::Variable {
                     name: "<<not parsed yet>>".to_string(),
@@ -427,81 +786,741 @@ impl<'input> subroutine::SubroutineMap for DwarfSubroutineMap<'input> {
             })
             .collect())
     }
-    fn display_variable(
-        &self,
-        code_offset: usize,
-        rbp: u32,
-        memory: &[u8],
-        name: String,
-    ) -> Result<()> {
-        let offset = &(code_offset as u64);
-        let subroutine = match self
+    fn display_return_value(&self, code_offset: usize, value: &WasmValue) -> Result<String> {
+        let offset = code_offset as u64;
+        // The returning function is the innermost one which is not inlined into it
+        let subroutine = self
             .subroutines
             .iter()
-            .filter(|s| s.pc.contains(offset))
-            .next()
-        {
-            Some(s) => s,
-            None => return Err(anyhow!("failed to determine subroutine")),
+            .filter(|s| s.pc.contains(&offset) && s.kind == SubroutineKind::Function)
+            .max_by_key(|s| s.depth)
+            .ok_or(anyhow!("failed to determine subroutine"))?;
+        let ty = subroutine
+            .ty_offset
+            .ok_or(anyhow!("function has no return type"))?;
+        // The linear memory is not available here, so pointers are not followed
+        let options = format::FormatOptions {
+            linear_memory: None,
+            max_depth: 0,
         };
-        let var = match subroutine
-            .variables
+        format::format_object(
+            ty,
+            &value_bytes(value),
+            subroutine.encoding,
+            &self.type_hash,
+            &options,
+        )
+    }
+    fn evaluate_expression(
+        &self,
+        frame: &subroutine::FrameState,
+        expression: &str,
+    ) -> Result<subroutine::ExpressionResult> {
+        eval::Evaluator::new(
+            &self.subroutines,
+            &self.type_hash,
+            frame,
+            self.max_depth.get(),
+        )
+        .evaluate_and_format(expression)
+    }
+    fn set_max_depth(&self, depth: usize) {
+        self.max_depth.set(depth);
+    }
+    fn inlined_frames(&self, code_offset: usize) -> Vec<subroutine::InlinedFrame> {
+        let offset = code_offset as u64;
+        let function_depth = self
+            .subroutines
             .iter()
-            .filter(|v| {
-                if let Some(vname) = v.name.clone() {
-                    vname == name
-                } else {
-                    false
+            .filter(|s| s.pc.contains(&offset) && s.kind == SubroutineKind::Function)
+            .map(|s| s.depth)
+            .max();
+        let mut frames: Vec<_> = self
+            .subroutines
+            .iter()
+            .filter(|s| s.pc.contains(&offset) && s.kind == SubroutineKind::Inlined)
+            .filter(|s| function_depth.map(|d| s.depth > d).unwrap_or(true))
+            .collect();
+        frames.sort_by_key(|s| std::cmp::Reverse(s.depth));
+        frames
+            .into_iter()
+            .map(|s| subroutine::InlinedFrame {
+                name: s.name.clone().unwrap_or_else(|| "<<unknown>>".to_string()),
+                call_file: s.call_file.clone(),
+                call_line: s.call_line,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::subroutine::SubroutineMap;
+    use gimli::write::{self, Address, DwarfUnit, EndianVec, Sections};
+    use std::convert::TryInto;
+
+    const DWO_ID: u64 = 0x1234;
+
+    fn encoding(version: u16) -> gimli::Encoding {
+        gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version,
+            address_size: 4,
+        }
+    }
+
+    fn func(
+        dwarf: &mut DwarfUnit,
+        name: &str,
+        high_pc: write::AttributeValue,
+    ) -> write::UnitEntryId {
+        let root = dwarf.unit.root();
+        let id = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(id);
+        entry.set(
+            gimli::DW_AT_name,
+            write::AttributeValue::String(name.into()),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            write::AttributeValue::Address(Address::Constant(0x10)),
+        );
+        entry.set(gimli::DW_AT_high_pc, high_pc);
+        id
+    }
+
+    fn constant(dwarf: &mut DwarfUnit, parent: write::UnitEntryId, value: write::AttributeValue) {
+        let id = dwarf.unit.add(parent, gimli::DW_TAG_variable);
+        dwarf.unit.get_mut(id).set(gimli::DW_AT_const_value, value);
+    }
+
+    /// Non-empty sections of the unit, named as in `.dwo` files if `dwo` is set
+    fn sections(dwarf: &mut DwarfUnit, dwo: bool) -> Vec<(String, Vec<u8>)> {
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        let mut named = vec![];
+        sections
+            .for_each(|id, data| -> Result<()> {
+                let name = if dwo { id.dwo_name() } else { Some(id.name()) };
+                if let (Some(name), false) = (name, data.slice().is_empty()) {
+                    named.push((name.to_string(), data.slice().to_vec()));
                 }
+                Ok(())
             })
-            .next()
-        {
-            Some(v) => v,
-            None => {
-                return Err(anyhow!("'{}' is not valid variable name", name));
-            }
+            .unwrap();
+        named
+    }
+
+    /// Turn the DWARF 5 compilation unit into a skeleton or split unit,
+    /// because gimli can't write them
+    fn set_unit_type(sections: &mut [(String, Vec<u8>)], unit_type: gimli::DwUt, dwo_id: u64) {
+        let (_, info) = sections
+            .iter_mut()
+            .find(|(name, _)| name.starts_with(".debug_info"))
+            .unwrap();
+        // unit_length, version, unit_type, address_size and debug_abbrev_offset precede dwo_id
+        let length = u32::from_le_bytes(info[0..4].try_into().unwrap()) + 8;
+        info[0..4].copy_from_slice(&length.to_le_bytes());
+        info[6] = unit_type.0;
+        info.splice(12..12, dwo_id.to_le_bytes().iter().copied());
+    }
+
+    /// Wasm module which has the sections as custom sections
+    fn module(sections: Vec<(String, Vec<u8>)>) -> Vec<u8> {
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        for (name, content) in sections {
+            let mut payload = vec![];
+            gimli::leb128::write::unsigned(&mut payload, name.len() as u64).unwrap();
+            payload.extend(name.as_bytes());
+            payload.extend(content);
+            bytes.push(0);
+            gimli::leb128::write::unsigned(&mut bytes, payload.len() as u64).unwrap();
+            bytes.extend(payload);
+        }
+        bytes
+    }
+
+    /// Skeleton unit which refers to the split unit `dwo_id` in `comp_dir/dwo_name`
+    fn skeleton(comp_dir: &Path, dwo_name: &str, dwo_id: u64) -> Vec<u8> {
+        let mut dwarf = DwarfUnit::new(encoding(5));
+        let root = dwarf.unit.root();
+        let entry = dwarf.unit.get_mut(root);
+        entry.set(
+            gimli::DW_AT_dwo_name,
+            write::AttributeValue::String(dwo_name.into()),
+        );
+        entry.set(
+            gimli::DW_AT_comp_dir,
+            write::AttributeValue::String(comp_dir.to_str().unwrap().into()),
+        );
+        let mut sections = sections(&mut dwarf, false);
+        set_unit_type(&mut sections, gimli::DW_UT_skeleton, dwo_id);
+        module(sections)
+    }
+
+    /// Sections of the split unit `DWO_ID` which has the function `f`
+    fn split_unit() -> Vec<(String, Vec<u8>)> {
+        let mut dwarf = DwarfUnit::new(encoding(5));
+        func(&mut dwarf, "f", write::AttributeValue::Udata(0x20));
+        let mut sections = sections(&mut dwarf, true);
+        set_unit_type(&mut sections, gimli::DW_UT_split_compile, DWO_ID);
+        sections
+    }
+
+    /// `.debug_cu_index` whose only unit `DWO_ID` contributes the whole sections
+    fn cu_index(sections: &[(gimli::DwSect, usize)]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend(&5u16.to_le_bytes());
+        bytes.extend(&0u16.to_le_bytes());
+        bytes.extend(&(sections.len() as u32).to_le_bytes());
+        // Unit count and slot count
+        bytes.extend(&1u32.to_le_bytes());
+        bytes.extend(&2u32.to_le_bytes());
+        // `DWO_ID` is hashed into the first slot, which points to the first row
+        bytes.extend(&DWO_ID.to_le_bytes());
+        bytes.extend(&0u64.to_le_bytes());
+        bytes.extend(&1u32.to_le_bytes());
+        bytes.extend(&0u32.to_le_bytes());
+        for (id, _) in sections {
+            bytes.extend(&id.0.to_le_bytes());
+        }
+        for _ in sections {
+            bytes.extend(&0u32.to_le_bytes());
+        }
+        for (_, size) in sections {
+            bytes.extend(&(*size as u32).to_le_bytes());
+        }
+        bytes
+    }
+
+    fn functions(info: &DwarfDebugInfo) -> Vec<(String, std::ops::Range<u64>)> {
+        info.subroutine
+            .subroutines
+            .iter()
+            .map(|s| (s.name.clone().unwrap(), s.pc.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_malformed_attributes() {
+        let mut dwarf = DwarfUnit::new(encoding(4));
+        // Neither an address nor a size
+        func(&mut dwarf, "broken", write::AttributeValue::Flag(true));
+        let id = func(&mut dwarf, "f", write::AttributeValue::Udata(0x20));
+        constant(&mut dwarf, id, write::AttributeValue::Udata(7));
+        let string = dwarf.strings.add("abc");
+        constant(&mut dwarf, id, write::AttributeValue::StringRef(string));
+        constant(&mut dwarf, id, write::AttributeValue::Flag(true));
+
+        let module = module(sections(&mut dwarf, false));
+        let info = transform_dwarf(parse_dwarf(&module).unwrap(), None).unwrap();
+
+        assert_eq!(functions(&info), vec![("f".to_string(), 0x10..0x30)]);
+        let subroutines = &info.subroutine.subroutines;
+        let contents: Vec<_> = subroutines[0]
+            .variables
+            .iter()
+            .map(|var| match &var.content {
+                VariableContent::ConstValue(bytes) => bytes.clone(),
+                VariableContent::Unknown { debug_info } => debug_info.as_bytes().to_vec(),
+                _ => panic!("unexpected content"),
+            })
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                7u64.to_le_bytes().to_vec(),
+                b"abc".to_vec(),
+                b"unsupported constant form Flag(true)".to_vec(),
+            ]
+        );
+    }
+
+    /// Unit whose line program maps 0x10 to the line 3 of `f.c` in the directory `d`.
+    /// Paths are in `.debug_line_str` since DWARF 5.
+    fn line_unit(version: u16) -> DwarfUnit {
+        let mut dwarf = DwarfUnit::new(encoding(version));
+        let line_strings = &mut dwarf.line_strings;
+        let mut string = |s: &str| write::LineString::new(s, encoding(version), line_strings);
+        let mut program = write::LineProgram::new(
+            encoding(version),
+            gimli::LineEncoding::default(),
+            string("/comp"),
+            string("a.c"),
+            None,
+        );
+        let dir = program.add_directory(string("d"));
+        let file = program.add_file(string("f.c"), dir, None);
+        program.begin_sequence(Some(Address::Constant(0x10)));
+        program.row().file = file;
+        program.row().line = 3;
+        program.generate_row();
+        program.end_sequence(4);
+        let comp_dir = match string("/comp") {
+            write::LineString::String(s) => write::AttributeValue::String(s),
+            write::LineString::LineStringRef(id) => write::AttributeValue::LineStringRef(id),
+            write::LineString::StringRef(id) => write::AttributeValue::StringRef(id),
         };
-        let piece = match var.content {
-            VariableContent::Location(location) => match location {
-                AttributeValue::Exprloc(expr) => {
-                    evaluate_variable_location(subroutine.encoding, rbp, expr)?
-                }
-                AttributeValue::LocationListsRef(_listsref) => unimplemented!("listsref"),
-                _ => panic!(),
-            },
-            VariableContent::ConstValue(ref _bytes) => unimplemented!(),
-            VariableContent::Unknown { ref debug_info } => {
-                unimplemented!("Unknown variable content found {}", debug_info)
-            }
+        dwarf.unit.line_program = program;
+        let root = dwarf.unit.root();
+        dwarf
+            .unit
+            .get_mut(root)
+            .set(gimli::DW_AT_comp_dir, comp_dir);
+        dwarf
+    }
+
+    fn debug_line_section(sections: &mut [(String, Vec<u8>)]) -> &mut Vec<u8> {
+        let (_, debug_line) = sections
+            .iter_mut()
+            .find(|(name, _)| name == ".debug_line")
+            .unwrap();
+        debug_line
+    }
+
+    #[test]
+    fn test_debug_line() {
+        let module = module(sections(&mut line_unit(4), false));
+        let info = transform_dwarf(parse_dwarf(&module).unwrap(), None).unwrap();
+        let (address, line_info) = &info.sourcemap.address_sorted_rows[0];
+        assert_eq!(*address, 0x10);
+        assert_eq!(line_info.filepath, "/comp/d/f.c");
+        assert_eq!(line_info.line, Some(3));
+    }
+
+    #[test]
+    fn test_dwarf5_sections() {
+        let mut dwarf = line_unit(5);
+        let ranges = dwarf
+            .unit
+            .ranges
+            .add(write::RangeList(vec![write::Range::StartLength {
+                begin: Address::Constant(0x40),
+                length: 0x10,
+            }]));
+        let root = dwarf.unit.root();
+        entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_subprogram,
+            vec![
+                name("g"),
+                (
+                    gimli::DW_AT_ranges,
+                    write::AttributeValue::RangeListRef(ranges),
+                ),
+            ],
+        );
+        let sections = sections(&mut dwarf, false);
+        let names: Vec<&str> = sections.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&".debug_line_str"));
+        assert!(names.contains(&".debug_rnglists"));
+
+        let module = module(sections);
+        let info = transform_dwarf(parse_dwarf(&module).unwrap(), None).unwrap();
+        assert_eq!(functions(&info), vec![("g".to_string(), 0x40..0x50)]);
+        let (_, line_info) = &info.sourcemap.address_sorted_rows[0];
+        assert_eq!(line_info.filepath, "/comp/d/f.c");
+    }
+
+    /// Unit whose function `f` has the name in `DW_FORM_strx1` and the address in
+    /// `DW_FORM_addrx`, which gimli can't write
+    #[test]
+    fn test_indexed_attributes() {
+        let abbrev = vec![
+            // Compile unit with DW_AT_str_offsets_base and DW_AT_addr_base
+            1, 0x11, 1, 0x72, 0x17, 0x73, 0x17, 0, 0,
+            // Subprogram with DW_AT_name, DW_AT_low_pc and DW_AT_high_pc
+            2, 0x2e, 0, 0x03, 0x25, 0x11, 0x1b, 0x12, 0x06, 0, 0, 0,
+        ];
+        let mut info = vec![];
+        info.extend(&5u16.to_le_bytes());
+        info.extend(&[gimli::DW_UT_compile.0, 4]);
+        info.extend(&0u32.to_le_bytes());
+        // Both bases are after the headers
+        info.push(1);
+        info.extend(&8u32.to_le_bytes());
+        info.extend(&8u32.to_le_bytes());
+        // The string 0 and the address 1
+        info.extend(&[2, 0, 1]);
+        info.extend(&0x20u32.to_le_bytes());
+        info.push(0);
+        let mut debug_info = (info.len() as u32).to_le_bytes().to_vec();
+        debug_info.extend(info);
+
+        let mut str_offsets = vec![];
+        str_offsets.extend(&8u32.to_le_bytes());
+        str_offsets.extend(&5u16.to_le_bytes());
+        str_offsets.extend(&0u16.to_le_bytes());
+        str_offsets.extend(&0u32.to_le_bytes());
+        let mut addr = vec![];
+        addr.extend(&12u32.to_le_bytes());
+        addr.extend(&5u16.to_le_bytes());
+        addr.extend(&[4, 0]);
+        addr.extend(&0u32.to_le_bytes());
+        addr.extend(&0x10u32.to_le_bytes());
+
+        let module = module(vec![
+            (".debug_abbrev".to_string(), abbrev),
+            (".debug_info".to_string(), debug_info),
+            (".debug_str".to_string(), b"f\0".to_vec()),
+            (".debug_str_offsets".to_string(), str_offsets),
+            (".debug_addr".to_string(), addr),
+        ]);
+        let info = transform_dwarf(parse_dwarf(&module).unwrap(), None).unwrap();
+        assert_eq!(functions(&info), vec![("f".to_string(), 0x10..0x30)]);
+    }
+
+    #[test]
+    fn test_malformed_debug_line() {
+        let mut sections = sections(&mut line_unit(4), false);
+        // Refer to the directory 9 from `f.c`
+        let debug_line = debug_line_section(&mut sections);
+        let name = debug_line
+            .windows(4)
+            .position(|bytes| bytes == b"f.c\0")
+            .unwrap();
+        debug_line[name + 4] = 9;
+        let module = module(sections);
+        let err = transform_dwarf(parse_dwarf(&module).unwrap(), None)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "directory index 9 of 'f.c' is out of range"
+        );
+
+        let mut sections = self::sections(&mut line_unit(4), false);
+        debug_line_section(&mut sections).truncate(8);
+        let module = self::module(sections);
+        assert!(transform_dwarf(parse_dwarf(&module).unwrap(), None).is_err());
+    }
+
+    /// Add a typed entry under `parent` whose attributes are `attrs`
+    fn entry(
+        dwarf: &mut DwarfUnit,
+        parent: write::UnitEntryId,
+        tag: gimli::DwTag,
+        attrs: Vec<(gimli::DwAt, write::AttributeValue)>,
+    ) -> write::UnitEntryId {
+        let id = dwarf.unit.add(parent, tag);
+        let entry = dwarf.unit.get_mut(id);
+        for (name, value) in attrs {
+            entry.set(name, value);
+        }
+        id
+    }
+
+    fn name(name: &str) -> (gimli::DwAt, write::AttributeValue) {
+        (
+            gimli::DW_AT_name,
+            write::AttributeValue::String(name.into()),
+        )
+    }
+
+    fn type_ref(id: write::UnitEntryId) -> (gimli::DwAt, write::AttributeValue) {
+        (gimli::DW_AT_type, write::AttributeValue::UnitRef(id))
+    }
+
+    #[test]
+    fn test_inlined_subroutines() {
+        let mut dwarf = line_unit(4);
+        let program = &mut dwarf.unit.line_program;
+        let dir = program.add_directory(write::LineString::String(b"d".to_vec()));
+        let file = program.add_file(write::LineString::String(b"f.c".to_vec()), dir, None);
+        let root = dwarf.unit.root();
+        let inl = entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_subprogram,
+            vec![name("inl")],
+        );
+        let deep = entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_subprogram,
+            vec![name("deep")],
+        );
+        let inlined = |dwarf: &mut DwarfUnit, parent, origin, low_pc, size, line| {
+            entry(
+                dwarf,
+                parent,
+                gimli::DW_TAG_inlined_subroutine,
+                vec![
+                    (
+                        gimli::DW_AT_abstract_origin,
+                        write::AttributeValue::UnitRef(origin),
+                    ),
+                    (
+                        gimli::DW_AT_low_pc,
+                        write::AttributeValue::Address(Address::Constant(low_pc)),
+                    ),
+                    (gimli::DW_AT_high_pc, write::AttributeValue::Udata(size)),
+                    (
+                        gimli::DW_AT_call_file,
+                        write::AttributeValue::FileIndex(Some(file)),
+                    ),
+                    (gimli::DW_AT_call_line, write::AttributeValue::Udata(line)),
+                ],
+            )
         };
+        let outer = func(&mut dwarf, "outer", write::AttributeValue::Udata(0x20));
+        entry(&mut dwarf, outer, gimli::DW_TAG_variable, vec![name("x")]);
+        let inl = inlined(&mut dwarf, outer, inl, 0x18, 0x8, 7);
+        entry(&mut dwarf, inl, gimli::DW_TAG_variable, vec![name("y")]);
+        inlined(&mut dwarf, inl, deep, 0x1a, 0x2, 9);
 
-        let piece = match piece.iter().next() {
-            Some(p) => p,
-            None => {
-                println!("failed to get piece of variable");
-                return Ok(());
-            }
+        let module = module(sections(&mut dwarf, false));
+        let info = transform_dwarf(parse_dwarf(&module).unwrap(), None).unwrap();
+        let frames = |offset| {
+            info.subroutine
+                .inlined_frames(offset)
+                .into_iter()
+                .map(|frame| (frame.name, frame.call_file, frame.call_line))
+                .collect::<Vec<_>>()
         };
+        let call_file = Some("/comp/d/f.c".to_string());
+        assert_eq!(
+            frames(0x1b),
+            vec![
+                ("deep".to_string(), call_file.clone(), Some(9)),
+                ("inl".to_string(), call_file, Some(7)),
+            ]
+        );
+        assert!(frames(0x12).is_empty());
 
-        if let Some(offset) = var.ty_offset {
-            use format::format_object;
-            match piece.location {
-                gimli::Location::Address { address } => {
-                    println!(
-                        "{}",
-                        format_object(
-                            offset,
-                            &memory[(address as usize)..],
-                            subroutine.encoding,
-                            &self.type_hash
-                        )?
-                    );
-                }
-                _ => unimplemented!(),
+        // Only variables of the innermost frame are visible
+        let variables = |offset| {
+            info.subroutine
+                .variable_name_list(offset)
+                .unwrap()
+                .into_iter()
+                .map(|var| var.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(variables(0x12), vec!["x"]);
+        assert_eq!(variables(0x19), vec!["y"]);
+    }
+
+    #[test]
+    fn test_return_value() {
+        let mut dwarf = DwarfUnit::new(encoding(4));
+        let root = dwarf.unit.root();
+        let int = entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_base_type,
+            vec![
+                name("int"),
+                (gimli::DW_AT_byte_size, write::AttributeValue::Udata(4)),
+                (
+                    gimli::DW_AT_encoding,
+                    write::AttributeValue::Encoding(gimli::DW_ATE_signed),
+                ),
+            ],
+        );
+        let id = func(&mut dwarf, "f", write::AttributeValue::Udata(0x20));
+        let (ty, value) = type_ref(int);
+        dwarf.unit.get_mut(id).set(ty, value);
+        let module = module(sections(&mut dwarf, false));
+        let info = transform_dwarf(parse_dwarf(&module).unwrap(), None).unwrap();
+
+        let subroutine = &info.subroutine;
+        assert_eq!(
+            subroutine
+                .display_return_value(0x18, &WasmValue::I32(-1))
+                .unwrap(),
+            "int(-1)"
+        );
+        assert!(subroutine
+            .display_return_value(0x40, &WasmValue::I32(-1))
+            .is_err());
+
+        // Functions without return types
+        let mut dwarf = DwarfUnit::new(encoding(4));
+        func(&mut dwarf, "g", write::AttributeValue::Udata(0x20));
+        let module = self::module(sections(&mut dwarf, false));
+        let info = transform_dwarf(parse_dwarf(&module).unwrap(), None).unwrap();
+        let err = info
+            .subroutine
+            .display_return_value(0x18, &WasmValue::I32(0))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "function has no return type");
+    }
+
+    #[test]
+    fn test_types() {
+        let mut dwarf = DwarfUnit::new(encoding(4));
+        let root = dwarf.unit.root();
+        let int = entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_base_type,
+            vec![
+                name("int"),
+                (gimli::DW_AT_byte_size, write::AttributeValue::Udata(4)),
+                (
+                    gimli::DW_AT_encoding,
+                    write::AttributeValue::Encoding(gimli::DW_ATE_signed),
+                ),
+            ],
+        );
+        let array = |dwarf: &mut DwarfUnit, bound: (gimli::DwAt, write::AttributeValue)| {
+            let id = entry(dwarf, root, gimli::DW_TAG_array_type, vec![type_ref(int)]);
+            entry(dwarf, id, gimli::DW_TAG_subrange_type, vec![bound]);
+            id
+        };
+        let upper_bound = gimli::DW_AT_upper_bound;
+        // GCC's zero-length arrays
+        array(&mut dwarf, (upper_bound, write::AttributeValue::Data8(!0)));
+        array(&mut dwarf, (upper_bound, write::AttributeValue::Data4(!0)));
+        array(&mut dwarf, (upper_bound, write::AttributeValue::Sdata(-1)));
+        array(&mut dwarf, (upper_bound, write::AttributeValue::Udata(2)));
+        array(
+            &mut dwarf,
+            (gimli::DW_AT_count, write::AttributeValue::Udata(4)),
+        );
+        let union = entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_union_type,
+            vec![
+                name("U"),
+                (gimli::DW_AT_byte_size, write::AttributeValue::Udata(4)),
+            ],
+        );
+        entry(
+            &mut dwarf,
+            union,
+            gimli::DW_TAG_member,
+            vec![name("a"), type_ref(int)],
+        );
+        entry(
+            &mut dwarf,
+            union,
+            gimli::DW_TAG_member,
+            vec![name("b"), type_ref(int)],
+        );
+        let bits = entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_structure_type,
+            vec![name("Bits")],
+        );
+        entry(
+            &mut dwarf,
+            bits,
+            gimli::DW_TAG_member,
+            vec![
+                name("flag"),
+                type_ref(int),
+                (
+                    gimli::DW_AT_data_bit_offset,
+                    write::AttributeValue::Udata(5),
+                ),
+                (gimli::DW_AT_bit_size, write::AttributeValue::Udata(3)),
+            ],
+        );
+        let callback = entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_subroutine_type,
+            vec![type_ref(int)],
+        );
+        entry(
+            &mut dwarf,
+            callback,
+            gimli::DW_TAG_formal_parameter,
+            vec![type_ref(int)],
+        );
+        entry(
+            &mut dwarf,
+            callback,
+            gimli::DW_TAG_unspecified_parameters,
+            vec![],
+        );
+        entry(
+            &mut dwarf,
+            root,
+            gimli::DW_TAG_pointer_type,
+            vec![type_ref(callback)],
+        );
+
+        let module = module(sections(&mut dwarf, false));
+        let dwarf = parse_dwarf(&module).unwrap();
+        let header = dwarf.units().next().unwrap().unwrap();
+        let unit = dwarf.unit(header).unwrap();
+        let mut type_hash = HashMap::new();
+        get_types(&dwarf, &unit, &mut type_hash).unwrap();
+        // Entries are written in the order of addition after the root
+        let mut offsets: Vec<usize> = type_hash.keys().copied().collect();
+        offsets.sort();
+        let ty = |index: usize| &type_hash[&offsets[index]];
+        let name = |index: usize| format::type_name(Some(offsets[index]), &type_hash).unwrap();
+
+        for (index, expected) in [None, None, None, Some(3), Some(4)].iter().enumerate() {
+            match ty(1 + index) {
+                TypeInfo::ArrayType(array) => assert_eq!(array.dimensions, vec![*expected]),
+                ty => panic!("unexpected type {:?}", ty),
             }
-        } else {
-            println!("no explicit type");
         }
-        Ok(())
+        assert_eq!(name(1), "int[]");
+        assert_eq!(name(4), "int[3]");
+        match ty(6) {
+            TypeInfo::UnionType(union) => {
+                assert_eq!(union.name, Some("U".to_string()));
+                assert_eq!(union.members.len(), 2);
+            }
+            ty => panic!("unexpected type {:?}", ty),
+        }
+        match ty(7) {
+            TypeInfo::StructType(bits) => {
+                assert_eq!(bits.members[0].data_bit_offset, Some(5));
+                assert_eq!(bits.members[0].bit_size, Some(3));
+            }
+            ty => panic!("unexpected type {:?}", ty),
+        }
+        match ty(8) {
+            TypeInfo::SubroutineType(callback) => {
+                assert_eq!(callback.ty, Some(offsets[0]));
+                assert_eq!(callback.parameters, vec![Some(offsets[0])]);
+                assert!(callback.variadic);
+            }
+            ty => panic!("unexpected type {:?}", ty),
+        }
+        assert_eq!(name(9), "int (*)(int, ...)");
+    }
+
+    #[test]
+    fn test_split_unit() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.dwo"), module(split_unit())).unwrap();
+        let main = skeleton(dir.path(), "main.dwo", DWO_ID);
+        let info = load_debug_info(&dir.path().join("main.wasm"), &main).unwrap();
+        assert_eq!(functions(&info), vec![("f".to_string(), 0x10..0x30)]);
+
+        let main = skeleton(dir.path(), "main.dwo", DWO_ID + 1);
+        match load_debug_info(&dir.path().join("main.wasm"), &main) {
+            Err(err) => assert_eq!(err.to_string(), "split unit 0x1235 is not found"),
+            Ok(_) => panic!("split unit of another id should not be loaded"),
+        }
+    }
+
+    #[test]
+    fn test_dwarf_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sections = split_unit();
+        let size = |name: &str| sections.iter().find(|(n, _)| n == name).unwrap().1.len();
+        let index = cu_index(&[
+            (gimli::DW_SECT_INFO, size(".debug_info.dwo")),
+            (gimli::DW_SECT_ABBREV, size(".debug_abbrev.dwo")),
+        ]);
+        sections.push((".debug_cu_index".to_string(), index));
+        std::fs::write(dir.path().join("main.wasm.dwp"), module(sections)).unwrap();
+        // The package is looked up before the missing .dwo
+        let main = skeleton(dir.path(), "main.dwo", DWO_ID);
+        let info = load_debug_info(&dir.path().join("main.wasm"), &main).unwrap();
+        assert_eq!(functions(&info), vec![("f".to_string(), 0x10..0x30)]);
     }
 }
@@ -9,6 +9,7 @@ use super::utils::*;
 pub struct BaseTypeInfo {
     pub name: String,
     pub byte_size: u64,
+    pub encoding: gimli::DwAte,
 }
 
 #[derive(Debug)]
@@ -35,6 +36,9 @@ pub struct Member<R: gimli::Reader> {
     pub name: Option<String>,
     pub ty: R::Offset,
     pub location: MemberLocation<R>,
+    /// Offset in bits from the beginning of the containing entity, only for bit-field members
+    pub data_bit_offset: Option<u64>,
+    pub bit_size: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -51,6 +55,20 @@ pub struct StructTypeInfo<R: gimli::Reader> {
     pub declaration: bool,
 }
 
+#[derive(Debug)]
+pub struct ArrayTypeInfo<R: gimli::Reader> {
+    pub ty: Option<R::Offset>,
+    /// Element count of each dimension, `None` if the bound is unknown
+    pub dimensions: Vec<Option<u64>>,
+}
+
+#[derive(Debug)]
+pub struct SubroutineTypeInfo<R: gimli::Reader> {
+    pub ty: Option<R::Offset>,
+    pub parameters: Vec<Option<R::Offset>>,
+    pub variadic: bool,
+}
+
 #[derive(Debug)]
 pub struct TypeDef<R: gimli::Reader> {
     pub name: Option<String>,
@@ -75,6 +93,9 @@ pub enum TypeInfo<R: gimli::Reader> {
     BaseType(BaseTypeInfo),
     ModifiedType(ModifiedTypeInfo<R::Offset>),
     StructType(StructTypeInfo<R>),
+    UnionType(StructTypeInfo<R>),
+    ArrayType(ArrayTypeInfo<R>),
+    SubroutineType(SubroutineTypeInfo<R>),
     TypeDef(TypeDef<R>),
     EnumerationType(EnumerationTypeInfo<R>),
 }
@@ -103,6 +124,18 @@ pub fn parse_types_rec<R: gimli::Reader>(
         gimli::DW_TAG_class_type | gimli::DW_TAG_structure_type => Some(TypeInfo::<R>::StructType(
             parse_partial_struct_type(&node, dwarf, unit)?,
         )),
+        gimli::DW_TAG_union_type => Some(TypeInfo::<R>::UnionType(parse_partial_struct_type(
+            &node, dwarf, unit,
+        )?)),
+        gimli::DW_TAG_array_type => Some(TypeInfo::<R>::ArrayType(ArrayTypeInfo {
+            ty: parse_type_offset(&node)?,
+            dimensions: vec![],
+        })),
+        gimli::DW_TAG_subroutine_type => Some(TypeInfo::<R>::SubroutineType(SubroutineTypeInfo {
+            ty: parse_type_offset(&node)?,
+            parameters: vec![],
+            variadic: false,
+        })),
         gimli::DW_TAG_enumeration_type => Some(TypeInfo::<R>::EnumerationType(
             parse_partial_enum_type(&node, dwarf, unit)?
         )),
@@ -139,19 +172,35 @@ pub fn parse_types_rec<R: gimli::Reader>(
     let mut children = node.children();
     let mut members = vec![];
     let mut enumerators = vec![];
+    let mut dimensions = vec![];
+    let mut parameters = vec![];
+    let mut variadic = false;
     while let Some(child) = children.next()? {
         match child.entry().tag() {
             gimli::DW_TAG_member => members.push(parse_member(&child, dwarf, unit)?),
             gimli::DW_TAG_enumerator => enumerators.push(parse_enumerator(&child, dwarf, unit)?),
+            gimli::DW_TAG_subrange_type => dimensions.push(parse_subrange(&child)?),
+            gimli::DW_TAG_formal_parameter => parameters.push(parse_type_offset(&child)?),
+            gimli::DW_TAG_unspecified_parameters => variadic = true,
             _ => parse_types_rec(child, dwarf, unit, out_type_hash)?,
         }
     }
 
-    if let Some(TypeInfo::StructType(ty)) = ty.as_mut() {
-        ty.members.append(&mut members);
-    }
-    if let Some(TypeInfo::EnumerationType(ty)) = ty.as_mut() {
-        ty.enumerators.append(&mut enumerators);
+    match ty.as_mut() {
+        Some(TypeInfo::StructType(ty)) | Some(TypeInfo::UnionType(ty)) => {
+            ty.members.append(&mut members);
+        }
+        Some(TypeInfo::EnumerationType(ty)) => {
+            ty.enumerators.append(&mut enumerators);
+        }
+        Some(TypeInfo::ArrayType(ty)) => {
+            ty.dimensions.append(&mut dimensions);
+        }
+        Some(TypeInfo::SubroutineType(ty)) => {
+            ty.parameters.append(&mut parameters);
+            ty.variadic = variadic;
+        }
+        _ => (),
     }
     if let Some(ty) = ty {
         out_type_hash.insert(offset.0, ty);
@@ -176,7 +225,15 @@ fn parse_base_type<R: gimli::Reader>(
         Some(s) => s,
         None => return Err(anyhow!("Failed to get byte_size")),
     };
-    Ok(BaseTypeInfo { name, byte_size })
+    let encoding = match node.entry().attr_value(gimli::DW_AT_encoding)? {
+        Some(gimli::AttributeValue::Encoding(encoding)) => encoding,
+        _ => return Err(anyhow!("Failed to get encoding")),
+    };
+    Ok(BaseTypeInfo {
+        name,
+        byte_size,
+        encoding,
+    })
 }
 
 fn parse_modified_type<R: gimli::Reader>(
@@ -252,22 +309,68 @@ fn parse_member<R: gimli::Reader>(
     let mut member_location = MemberLocation::ConstOffset(0);
     if let Some(loc_attr) = node.entry().attr_value(gimli::DW_AT_data_member_location)? {
         match loc_attr {
-            gimli::AttributeValue::Udata(offset) => {
-                member_location = MemberLocation::ConstOffset(offset);
-            }
             gimli::AttributeValue::Exprloc(expr) => {
                 member_location = MemberLocation::LocationDescription(expr);
             }
-            _ => unimplemented!(),
+            attr => match attr.udata_value() {
+                Some(offset) => member_location = MemberLocation::ConstOffset(offset),
+                None => return Err(anyhow!("Unsupported member location: {:?}", attr)),
+            },
         }
     }
+    let data_bit_offset = node
+        .entry()
+        .attr_value(gimli::DW_AT_data_bit_offset)?
+        .and_then(|attr| attr.udata_value());
+    let bit_size = node
+        .entry()
+        .attr_value(gimli::DW_AT_bit_size)?
+        .and_then(|attr| attr.udata_value());
     Ok(Member {
         name,
         location: member_location,
         ty,
+        data_bit_offset,
+        bit_size,
     })
 }
 
+fn parse_type_offset<R: gimli::Reader>(
+    node: &gimli::EntriesTreeNode<R>,
+) -> Result<Option<R::Offset>> {
+    match node.entry().attr_value(gimli::DW_AT_type)? {
+        Some(gimli::AttributeValue::UnitRef(ref offset)) => Ok(Some(offset.0)),
+        _ => Ok(None),
+    }
+}
+
+fn parse_subrange<R: gimli::Reader>(node: &gimli::EntriesTreeNode<R>) -> Result<Option<u64>> {
+    // DWARF v5 Page 115
+    if let Some(count) = node.entry().attr_value(gimli::DW_AT_count)? {
+        return Ok(count.udata_value());
+    }
+    let lower_bound = node
+        .entry()
+        .attr_value(gimli::DW_AT_lower_bound)?
+        .and_then(|attr| attr.udata_value())
+        .unwrap_or(0);
+    let upper_bound = match node.entry().attr_value(gimli::DW_AT_upper_bound)? {
+        // Flexible and zero-length arrays may have -1 as the upper bound,
+        // which is all ones of the width in fixed size forms
+        Some(gimli::AttributeValue::Sdata(bound)) if bound < 0 => None,
+        Some(gimli::AttributeValue::Data1(u8::MAX))
+        | Some(gimli::AttributeValue::Data2(u16::MAX))
+        | Some(gimli::AttributeValue::Data4(u32::MAX))
+        | Some(gimli::AttributeValue::Data8(u64::MAX))
+        | Some(gimli::AttributeValue::Udata(u64::MAX)) => None,
+        Some(attr) => attr.udata_value(),
+        None => None,
+    };
+    Ok(upper_bound
+        .and_then(|upper_bound| upper_bound.checked_add(1))
+        .map(|count| count.saturating_sub(lower_bound)))
+}
+
 fn parse_typedef<R: gimli::Reader>(
     node: &gimli::EntriesTreeNode<R>,
     dwarf: &gimli::Dwarf<R>,
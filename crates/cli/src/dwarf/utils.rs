@@ -1,6 +1,19 @@
 use anyhow::Result;
 use gimli;
 
+/// Resolve `DW_FORM_addr` and `DW_FORM_addrx` attributes to an address
+pub(crate) fn attr_address<R: gimli::Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R, R::Offset>,
+    attr: gimli::AttributeValue<R>,
+) -> Result<Option<u64>> {
+    match attr {
+        gimli::AttributeValue::Addr(address) => Ok(Some(address)),
+        gimli::AttributeValue::DebugAddrIndex(index) => Ok(Some(dwarf.address(unit, index)?)),
+        _ => Ok(None),
+    }
+}
+
 pub(crate) fn clone_string_attribute<R: gimli::Reader>(
     dwarf: &gimli::Dwarf<R>,
     unit: &gimli::Unit<R, R::Offset>,
@@ -12,3 +25,25 @@ pub(crate) fn clone_string_attribute<R: gimli::Reader>(
         .as_ref()
         .to_string())
 }
+
+/// Look up an attribute through `DW_AT_abstract_origin` and `DW_AT_specification`,
+/// because inlined and out-of-line instances leave most attributes to their origin
+pub(crate) fn attr_value_with_origin<R: gimli::Reader>(
+    unit: &gimli::Unit<R, R::Offset>,
+    entry: &gimli::DebuggingInformationEntry<R>,
+    name: gimli::DwAt,
+) -> Result<Option<gimli::AttributeValue<R>>> {
+    if let Some(value) = entry.attr_value(name)? {
+        return Ok(Some(value));
+    }
+    for origin in &[gimli::DW_AT_abstract_origin, gimli::DW_AT_specification] {
+        if let Some(gimli::AttributeValue::UnitRef(offset)) = entry.attr_value(*origin)? {
+            let mut cursor = unit.entries_at_offset(offset)?;
+            cursor.next_entry()?;
+            if let Some(origin) = cursor.current() {
+                return attr_value_with_origin(unit, origin, name);
+            }
+        }
+    }
+    Ok(None)
+}
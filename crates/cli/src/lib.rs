@@ -1,11 +1,14 @@
 mod commands;
+mod coverage;
 mod debugger;
 mod dwarf;
 mod process;
+mod profile;
 
 use anyhow::{anyhow, Result};
 use std::env;
 use std::io::Read;
+use std::path::{Path, PathBuf};
 
 fn history_file_path() -> String {
     format!(
@@ -14,8 +17,143 @@ fn history_file_path() -> String {
     )
 }
 
-pub fn run_loop(file: Option<String>, init_source: Option<String>) -> Result<()> {
+/// Load DWARF of the module, or fall back to empty maps if it's unavailable.
+/// DWARF separated from the module is preferred.
+fn load_debug_info(
+    file: &str,
+    buffer: &[u8],
+    symbols: Option<String>,
+) -> (
+    Box<dyn commands::sourcemap::SourceMap>,
+    Box<dyn commands::subroutine::SubroutineMap>,
+) {
+    let result = match read_external_debug_info(file, buffer, symbols) {
+        Some((path, external)) => dwarf::load_debug_info(&path, &external),
+        None => dwarf::load_debug_info(Path::new(file), buffer),
+    };
+    match result {
+        Ok(debug_info) => (
+            Box::new(debug_info.sourcemap),
+            Box::new(debug_info.subroutine),
+        ),
+        Err(err) => {
+            eprintln!("warning: failed to load debug info: {}", err);
+            (
+                Box::new(commands::sourcemap::EmptySourceMap::new()),
+                Box::new(commands::subroutine::EmptySubroutineMap::new()),
+            )
+        }
+    }
+}
+
+/// Read DWARF separated from the module, which is given by `--symbols` or
+/// referred by `external_debug_info` section, with its path
+fn read_external_debug_info(
+    file: &str,
+    buffer: &[u8],
+    symbols: Option<String>,
+) -> Option<(PathBuf, Vec<u8>)> {
+    let path = match symbols {
+        Some(symbols) => PathBuf::from(symbols),
+        // The recorded path is relative to the module
+        None => match dwarf::external_debug_info(buffer) {
+            Ok(Some(path)) => Path::new(file)
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(path),
+            _ => return None,
+        },
+    };
+    match std::fs::read(&path) {
+        Ok(bytes) => Some((path, bytes)),
+        Err(err) => {
+            eprintln!("warning: failed to read {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Run the module to completion and report executed instruction counts
+pub fn run_profile(file: String, entry: Option<String>, output: String, top: usize) -> Result<()> {
+    use commands::debugger::{Debugger, RunResult};
+    let buffer = std::fs::read(&file)?;
     let mut debugger = debugger::MainDebugger::new()?;
+    debugger.load_module(&buffer)?;
+    debugger.start_profile();
+    if let RunResult::Finish(values) = debugger.run(entry)? {
+        println!("{:?}", values);
+    }
+    let profiler = debugger
+        .finish_profile()
+        .ok_or(anyhow!("Profile is not recorded"))?;
+
+    let mut f = std::io::BufWriter::new(std::fs::File::create(&output)?);
+    profiler.write_folded(&mut f)?;
+    println!("Folded stacks were written to {}", output);
+
+    println!("Total instructions: {}", profiler.total_count());
+    println!("{:>12} {:>12}  Function", "Self", "Inclusive");
+    for stat in profiler.function_stats().iter().take(top) {
+        println!(
+            "{:>12} {:>12}  {}",
+            stat.self_count, stat.inclusive_count, stat.name
+        );
+    }
+
+    let (sourcemap, _) = load_debug_info(&file, &buffer, None);
+    let lines = profiler.line_stats(sourcemap.as_ref());
+    if !lines.is_empty() {
+        println!("{:>12}  Line", "Self");
+        for (line, count) in lines.iter().take(top) {
+            println!("{:>12}  {}", count, line);
+        }
+    }
+    Ok(())
+}
+
+/// Run the module to completion and write line coverage in the lcov format
+pub fn run_coverage(file: String, entry: Option<String>, output: String) -> Result<()> {
+    use commands::debugger::{Debugger, RunResult};
+    let buffer = std::fs::read(&file)?;
+    let mut debugger = debugger::MainDebugger::new()?;
+    debugger.load_module(&buffer)?;
+    debugger.start_profile();
+    // Write the coverage even if the execution traps
+    let result = debugger.run(entry);
+    let profiler = debugger
+        .finish_profile()
+        .ok_or(anyhow!("Coverage is not recorded"))?;
+
+    let (sourcemap, _) = load_debug_info(&file, &buffer, None);
+    let coverage = coverage::LineCoverage::new(profiler.inst_counts(), sourcemap.as_ref());
+    let mut f = std::io::BufWriter::new(std::fs::File::create(&output)?);
+    coverage.write_lcov(&file, &mut f)?;
+    let (hit, total) = coverage.summary();
+    println!("Coverage was written to {}", output);
+    println!("Lines hit: {} of {}", hit, total);
+
+    if let RunResult::Finish(values) = result? {
+        println!("{:?}", values);
+    }
+    Ok(())
+}
+
+pub fn run_loop(
+    file: Option<String>,
+    init_source: Option<String>,
+    core: Option<String>,
+    symbols: Option<String>,
+    history: Option<usize>,
+    core_on_trap: Option<String>,
+) -> Result<()> {
+    let mut debugger = debugger::MainDebugger::new()?;
+    if let Some(limit) = history {
+        debugger.set_history_limit(limit);
+    }
+    if let Some(path) = core_on_trap {
+        debugger.set_trap_core_file(path);
+    }
+    debugger.handle_interrupt_signal()?;
     let mut buffer = Vec::new();
     let mut context = commands::command::CommandContext {
         sourcemap: Box::new(commands::sourcemap::EmptySourceMap::new()),
@@ -23,14 +161,15 @@ pub fn run_loop(file: Option<String>, init_source: Option<String>) -> Result<()>
     };
 
     if let Some(file) = file {
-        let mut f = ::std::fs::File::open(file)?;
+        let mut f = ::std::fs::File::open(&file)?;
         f.read_to_end(&mut buffer)?;
         debugger.load_module(&buffer)?;
-        use dwarf::{parse_dwarf, transform_dwarf};
-        let dwarf = parse_dwarf(&buffer)?;
-        let debug_info = transform_dwarf(dwarf)?;
-        context.sourcemap = Box::new(debug_info.sourcemap);
-        context.subroutine = Box::new(debug_info.subroutine);
+        let (sourcemap, subroutine) = load_debug_info(&file, &buffer, symbols);
+        context.sourcemap = sourcemap;
+        context.subroutine = subroutine;
+    }
+    if let Some(core) = core {
+        debugger.load_core(&std::fs::read(core)?)?;
     }
     let mut process = process::Process::new(
         debugger,
@@ -48,6 +187,9 @@ pub fn run_loop(file: Option<String>, init_source: Option<String>) -> Result<()>
             Box::new(commands::frame::FrameCommand::new()),
             Box::new(commands::settings::SettingsCommand::new()),
             Box::new(commands::process::ProcessCommand::new()),
+            Box::new(commands::trace::TraceCommand::new()),
+            Box::new(commands::target::TargetCommand::new()),
+            Box::new(commands::watchpoint::WatchpointCommand::new()),
         ],
         vec![Box::new(commands::backtrace::BacktraceCommand::new())],
         &history_file_path(),
@@ -71,9 +213,27 @@ pub fn run_loop(file: Option<String>, init_source: Option<String>) -> Result<()>
             }
         };
         for line in lines {
-            process.dispatch_command(line, &context)?;
+            process.dispatch_command(line, &mut context)?;
         }
     }
     process.run_loop(context)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasminspect_vm::WasmValue;
+
+    #[test]
+    fn test_load_without_dwarf() {
+        let module = b"\0asm\x01\0\0\0";
+        let (sourcemap, subroutine) = load_debug_info("no-dwarf.wasm", module, None);
+        assert!(sourcemap.find_line_info(0).is_none());
+        assert!(subroutine.variable_name_list(0).unwrap().is_empty());
+        let err = subroutine
+            .display_return_value(0, &WasmValue::I32(0))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "No debug information is available");
+    }
+}
@@ -48,15 +48,15 @@ impl<D: Debugger> Process<D> {
         })
     }
 
-    pub fn run_loop(&mut self, context: command::CommandContext) -> Result<()> {
+    pub fn run_loop(&mut self, mut context: command::CommandContext) -> Result<()> {
         let mut last_line: Option<String> = None;
         while let ReadResult::Input(line) = self.interface.read_line()? {
             if !line.trim().is_empty() {
                 self.interface.add_history_unique(line.clone());
                 last_line = Some(line.clone());
-                self.dispatch_command(line, &context)?;
+                self.dispatch_command(line, &mut context)?;
             } else if let Some(last_line) = last_line.as_ref() {
-                self.dispatch_command(last_line.clone(), &context)?;
+                self.dispatch_command(last_line.clone(), &mut context)?;
             }
         }
         Ok(())
@@ -65,12 +65,12 @@ impl<D: Debugger> Process<D> {
     pub fn dispatch_command(
         &mut self,
         line: String,
-        context: &command::CommandContext,
+        context: &mut command::CommandContext,
     ) -> Result<()> {
         let cmd_name = extract_command_name(&line);
         let args = line.split_whitespace().collect();
         if let Some(cmd) = self.commands.get(cmd_name) {
-            match cmd.run(&mut self.debugger, &context, args) {
+            match cmd.run(&mut self.debugger, context, args) {
                 Ok(()) => (),
                 Err(err) => {
                     eprintln!("{}", err);
@@ -0,0 +1,246 @@
+use super::commands::sourcemap::SourceMap;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+struct CallNode {
+    name: String,
+    parent: Option<usize>,
+    children: HashMap<String, usize>,
+    /// Instructions executed while this node is the innermost frame
+    count: u64,
+}
+
+pub struct FunctionStat {
+    pub name: String,
+    pub self_count: u64,
+    pub inclusive_count: u64,
+}
+
+/// Counts executed instructions per call stack and per code offset
+pub struct Profiler {
+    nodes: Vec<CallNode>,
+    current: usize,
+    inst_counts: HashMap<usize, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let root = CallNode {
+            name: String::new(),
+            parent: None,
+            children: HashMap::new(),
+            count: 0,
+        };
+        Self {
+            nodes: vec![root],
+            current: 0,
+            inst_counts: HashMap::new(),
+        }
+    }
+
+    pub fn enter_func(&mut self, name: &str) {
+        let next_index = self.nodes.len();
+        let current = &mut self.nodes[self.current];
+        let index = *current
+            .children
+            .entry(name.to_string())
+            .or_insert(next_index);
+        if index == next_index {
+            self.nodes.push(CallNode {
+                name: name.to_string(),
+                parent: Some(self.current),
+                children: HashMap::new(),
+                count: 0,
+            });
+        }
+        self.current = index;
+    }
+
+    pub fn exit_func(&mut self) {
+        if let Some(parent) = self.nodes[self.current].parent {
+            self.current = parent;
+        }
+    }
+
+    pub fn count_inst(&mut self, offset: usize) {
+        self.nodes[self.current].count += 1;
+        *self.inst_counts.entry(offset).or_insert(0) += 1;
+    }
+
+    /// Execution counts keyed by instruction offset
+    pub fn inst_counts(&self) -> &HashMap<usize, u64> {
+        &self.inst_counts
+    }
+
+    pub fn total_count(&self) -> u64 {
+        self.nodes.iter().map(|node| node.count).sum()
+    }
+
+    /// Function names from the outermost frame to the node
+    fn stack(&self, index: usize) -> Vec<&str> {
+        let mut stack = vec![];
+        let mut index = Some(index);
+        while let Some(node) = index.map(|i| &self.nodes[i]) {
+            if node.parent.is_some() {
+                stack.push(node.name.as_str());
+            }
+            index = node.parent;
+        }
+        stack.reverse();
+        stack
+    }
+
+    /// Write stacks in the folded format used by flamegraph.pl and inferno
+    pub fn write_folded<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.count == 0 {
+                continue;
+            }
+            writeln!(output, "{} {}", self.stack(index).join(";"), node.count)?;
+        }
+        Ok(())
+    }
+
+    /// Statistics sorted by self instruction count
+    pub fn function_stats(&self) -> Vec<FunctionStat> {
+        let mut stats: HashMap<&str, FunctionStat> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.count == 0 {
+                continue;
+            }
+            let stack = self.stack(index);
+            let mut visited = HashSet::new();
+            for name in stack.iter() {
+                let stat = stats.entry(name).or_insert(FunctionStat {
+                    name: name.to_string(),
+                    self_count: 0,
+                    inclusive_count: 0,
+                });
+                // Count recursive calls only once
+                if visited.insert(*name) {
+                    stat.inclusive_count += node.count;
+                }
+            }
+            if let Some(name) = stack.last() {
+                stats.get_mut(name).unwrap().self_count += node.count;
+            }
+        }
+        let mut stats: Vec<FunctionStat> = stats.into_values().collect();
+        stats.sort_by_key(|stat| std::cmp::Reverse(stat.self_count));
+        stats
+    }
+
+    /// Instruction counts per source line sorted by the count
+    pub fn line_stats(&self, sourcemap: &dyn SourceMap) -> Vec<(String, u64)> {
+        let mut lines: HashMap<String, u64> = HashMap::new();
+        for (offset, count) in self.inst_counts.iter() {
+            if let Some(info) = sourcemap.find_line_info(*offset) {
+                let line = match info.line {
+                    Some(line) => format!("{}:{}", info.filepath, line),
+                    None => info.filepath,
+                };
+                *lines.entry(line).or_insert(0) += count;
+            }
+        }
+        let mut lines: Vec<(String, u64)> = lines.into_iter().collect();
+        lines.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::commands::sourcemap::{ColumnType, LineInfo};
+    use super::*;
+
+    /// Offsets 0..10 are in line 1 and the others are in line 2
+    struct TwoLines {}
+
+    impl SourceMap for TwoLines {
+        fn find_line_info(&self, offset: usize) -> Option<LineInfo> {
+            Some(LineInfo {
+                filepath: "main.c".to_string(),
+                line: Some(if offset < 10 { 1 } else { 2 }),
+                column: ColumnType::LeftEdge,
+            })
+        }
+        fn line_infos(&self) -> Vec<LineInfo> {
+            vec![]
+        }
+        fn set_directory_map(&self, _: String, _: String) {}
+    }
+
+    /// main executes 2 instructions, calls foo which executes 3, and calls bar twice,
+    /// which executes 1 each time and calls foo which executes 3 again
+    fn profile() -> Profiler {
+        let mut profiler = Profiler::new();
+        profiler.enter_func("main");
+        profiler.count_inst(0);
+        profiler.count_inst(1);
+        profiler.enter_func("foo");
+        for offset in 10..13 {
+            profiler.count_inst(offset);
+        }
+        profiler.exit_func();
+        for _ in 0..2 {
+            profiler.enter_func("bar");
+            profiler.count_inst(20);
+            profiler.enter_func("foo");
+            for offset in 10..13 {
+                profiler.count_inst(offset);
+            }
+            profiler.exit_func();
+            profiler.exit_func();
+        }
+        profiler.exit_func();
+        profiler
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        let mut output = Vec::new();
+        profile().write_folded(&mut output).unwrap();
+        let mut lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec!["main 2", "main;bar 2", "main;bar;foo 6", "main;foo 3"]
+        );
+    }
+
+    #[test]
+    fn test_function_stats() {
+        let profiler = profile();
+        assert_eq!(profiler.total_count(), 13);
+        let stats = profiler.function_stats();
+        let stats: Vec<(&str, u64, u64)> = stats
+            .iter()
+            .map(|stat| (stat.name.as_str(), stat.self_count, stat.inclusive_count))
+            .collect();
+        assert_eq!(stats[0], ("foo", 9, 9));
+        assert!(stats.contains(&("main", 2, 13)));
+        assert!(stats.contains(&("bar", 2, 8)));
+    }
+
+    #[test]
+    fn test_recursion_counted_once() {
+        let mut profiler = Profiler::new();
+        profiler.enter_func("fib");
+        profiler.count_inst(0);
+        profiler.enter_func("fib");
+        profiler.count_inst(0);
+        let stats = profiler.function_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].self_count, 2);
+        assert_eq!(stats[0].inclusive_count, 2);
+    }
+
+    #[test]
+    fn test_line_stats() {
+        let lines = profile().line_stats(&TwoLines {});
+        assert_eq!(
+            lines,
+            vec![("main.c:2".to_string(), 11), ("main.c:1".to_string(), 2)]
+        );
+    }
+}
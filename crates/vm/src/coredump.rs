@@ -0,0 +1,641 @@
+//! Core dump in the format of a wasm module described in
+//! https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+//!
+//! The process state is stored in `core` and `corestack` custom sections, memories in memory and
+//! data sections and globals in a global section. Code offsets are relative to the code section
+//! as well as DWARF.
+//! Tables are not stored because they are never modified after instantiation.
+
+use super::address::{FuncAddr, GlobalAddr, MemoryAddr};
+use super::executor::{eval_const_expr, Executor};
+use super::func::InstIndex;
+use super::module::ModuleIndex;
+use super::stack::{CallFrame, ProgramCounter};
+use super::store::Store;
+use super::value::Value;
+use anyhow::Result;
+use wasmparser::{BinaryReader, DataKind, ModuleReader, SectionCode, Type};
+
+#[derive(Debug)]
+pub enum CoreDumpError {
+    MissingSection(&'static str),
+    UnsupportedVersion(u32),
+    UnknownFunction(/* function index: */ u32),
+    UnknownCodeOffset(/* function index: */ u32, /* code offset: */ u32),
+    LocalCountMismatch(/* function index: */ u32),
+    UnknownValueType(u32),
+    MemoryCountMismatch(/* expected: */ usize, /* actual: */ usize),
+    GlobalCountMismatch(/* expected: */ usize, /* actual: */ usize),
+    UnknownMemory(/* memory index: */ u32),
+    MemoryOverLimit(/* memory index: */ usize, /* page count: */ usize),
+    DataOutOfBounds(/* memory index: */ u32, /* offset: */ usize),
+}
+
+impl std::error::Error for CoreDumpError {}
+
+impl std::fmt::Display for CoreDumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingSection(name) => write!(f, "\"{}\" section not found", name),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported core dump version {}", version)
+            }
+            Self::UnknownFunction(index) => write!(f, "function {} not found", index),
+            Self::UnknownCodeOffset(index, offset) => write!(
+                f,
+                "no instruction at code offset 0x{:x} in function {}",
+                offset, index
+            ),
+            Self::LocalCountMismatch(index) => {
+                write!(f, "local count of function {} doesn't match", index)
+            }
+            Self::UnknownValueType(ty) => write!(f, "unknown value type 0x{:x}", ty),
+            Self::MemoryCountMismatch(expected, actual) => write!(
+                f,
+                "module has {} memories but core dump has {}",
+                expected, actual
+            ),
+            Self::GlobalCountMismatch(expected, actual) => write!(
+                f,
+                "module has {} globals but core dump has {}",
+                expected, actual
+            ),
+            Self::UnknownMemory(index) => write!(f, "memory {} not found", index),
+            Self::MemoryOverLimit(index, pages) => write!(
+                f,
+                "memory {} can't have {} pages in this module",
+                index, pages
+            ),
+            Self::DataOutOfBounds(index, offset) => write!(
+                f,
+                "data at 0x{:x} is out of bounds of memory {}",
+                offset, index
+            ),
+        }
+    }
+}
+
+/// Write the state of the module instance and the call stack of the executor
+pub fn write_coredump(
+    executor: &Executor,
+    store: &Store,
+    module_index: ModuleIndex,
+    executable_name: &str,
+) -> Vec<u8> {
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+
+    let mut core = vec![0x00];
+    write_name(&mut core, executable_name);
+    write_custom_section(&mut module, "core", &core);
+
+    let frame_values = executor.stack.peek_frame_values();
+    let mut pcs: Vec<ProgramCounter> = frame_values
+        .iter()
+        .skip(1)
+        .filter_map(|(frame, _)| frame.ret_pc)
+        .collect();
+    pcs.push(executor.pc);
+    let mut corestack = vec![0x00];
+    write_name(&mut corestack, "main");
+    write_u32(&mut corestack, frame_values.len() as u32);
+    for ((frame, values), pc) in frame_values.iter().zip(pcs.iter()).rev() {
+        let func_index = store
+            .func_index(frame.module_index(), pc.exec_addr())
+            .unwrap_or(0);
+        let insts = executor_insts(store, pc);
+        let code_offset = insts
+            .get(pc.inst_index().0 as usize)
+            .or(insts.last())
+            .map(|inst| inst.offset)
+            .unwrap_or(0);
+        corestack.push(0x00);
+        write_u32(&mut corestack, func_index as u32);
+        write_u32(&mut corestack, code_offset as u32);
+        write_u32(&mut corestack, frame.locals.len() as u32);
+        for local in &frame.locals {
+            write_value(&mut corestack, local);
+        }
+        write_u32(&mut corestack, values.len() as u32);
+        for value in values {
+            write_value(&mut corestack, value);
+        }
+    }
+    write_custom_section(&mut module, "corestack", &corestack);
+
+    let memory_count = store.memory_count(module_index);
+    let mut memories = vec![];
+    let mut data = vec![];
+    write_u32(&mut memories, memory_count as u32);
+    let mut segments = vec![];
+    for index in 0..memory_count {
+        let memory = store.memory(MemoryAddr::new_unsafe(module_index, index));
+        let memory = memory.borrow();
+        match memory.max {
+            Some(max) => {
+                memories.push(0x01);
+                write_u32(&mut memories, memory.page_count() as u32);
+                write_u32(&mut memories, max as u32);
+            }
+            None => {
+                memories.push(0x00);
+                write_u32(&mut memories, memory.page_count() as u32);
+            }
+        }
+        let bytes = memory.raw_data();
+        let len = bytes.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        if len > 0 {
+            segments.push((index, bytes[..len].to_vec()));
+        }
+    }
+    write_u32(&mut data, segments.len() as u32);
+    for (index, bytes) in segments {
+        write_u32(&mut data, index as u32);
+        data.extend_from_slice(&[0x41, 0x00, 0x0b]);
+        write_u32(&mut data, bytes.len() as u32);
+        data.extend_from_slice(&bytes);
+    }
+
+    let global_count = store.global_count(module_index);
+    let mut globals = vec![];
+    write_u32(&mut globals, global_count as u32);
+    for index in 0..global_count {
+        let global = store.global(GlobalAddr::new_unsafe(module_index, index));
+        let global = global.borrow();
+        globals.push(value_type_code(global.value()));
+        globals.push(global.is_mutable() as u8);
+        match global.value() {
+            Value::I32(v) => {
+                globals.push(0x41);
+                write_i64(&mut globals, v as i64);
+            }
+            Value::I64(v) => {
+                globals.push(0x42);
+                write_i64(&mut globals, v);
+            }
+            Value::F32(v) => {
+                globals.push(0x43);
+                globals.extend_from_slice(&v.to_bits().to_le_bytes());
+            }
+            Value::F64(v) => {
+                globals.push(0x44);
+                globals.extend_from_slice(&v.to_bits().to_le_bytes());
+            }
+        }
+        globals.push(0x0b);
+    }
+
+    write_section(&mut module, 5, &memories);
+    write_section(&mut module, 6, &globals);
+    write_section(&mut module, 11, &data);
+    module
+}
+
+/// Restore memories and globals of the module instance and returns an executor
+/// which has the dumped call stack
+pub fn read_coredump(bytes: &[u8], store: &Store, module_index: ModuleIndex) -> Result<Executor> {
+    let mut reader = ModuleReader::new(bytes)?;
+    let mut corestack = None;
+    let mut memories = vec![];
+    let mut globals = vec![];
+    let mut data_segs = vec![];
+    while !reader.eof() {
+        let section = reader.read()?;
+        match section.code {
+            SectionCode::Custom {
+                name: "corestack",
+                kind: _,
+            } => {
+                corestack = Some(section.get_binary_reader());
+            }
+            SectionCode::Memory => {
+                for entry in section.get_memory_section_reader()? {
+                    memories.push(entry?);
+                }
+            }
+            SectionCode::Global => {
+                for entry in section.get_global_section_reader()? {
+                    globals.push(entry?);
+                }
+            }
+            SectionCode::Data => {
+                for entry in section.get_data_section_reader()? {
+                    data_segs.push(entry?);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Validate everything before modifying the store not to leave it half restored
+    let memory_count = store.memory_count(module_index);
+    if memories.len() != memory_count {
+        return Err(CoreDumpError::MemoryCountMismatch(memory_count, memories.len()).into());
+    }
+    let mut page_counts = vec![];
+    for (index, ty) in memories.iter().enumerate() {
+        let page_count = ty.limits.initial as usize;
+        let max = store
+            .memory(MemoryAddr::new_unsafe(module_index, index))
+            .borrow()
+            .max;
+        if page_count > 65536 || max.map(|max| page_count > max).unwrap_or(false) {
+            return Err(CoreDumpError::MemoryOverLimit(index, page_count).into());
+        }
+        page_counts.push(page_count);
+    }
+    let mut data = vec![];
+    for segment in data_segs {
+        if let DataKind::Active {
+            memory_index,
+            init_expr,
+        } = segment.kind
+        {
+            let page_count = *page_counts
+                .get(memory_index as usize)
+                .ok_or(CoreDumpError::UnknownMemory(memory_index))?;
+            let offset = match eval_const_expr(&init_expr, store, module_index)? {
+                Value::I32(v) => v as u32 as usize,
+                _ => 0,
+            };
+            let in_bounds = offset
+                .checked_add(segment.data.len())
+                .map(|end| end <= page_count * crate::WASM_PAGE_SIZE)
+                .unwrap_or(false);
+            if !in_bounds {
+                return Err(CoreDumpError::DataOutOfBounds(memory_index, offset).into());
+            }
+            data.push((memory_index as usize, offset, segment.data));
+        }
+    }
+
+    let global_count = store.global_count(module_index);
+    if globals.len() != global_count {
+        return Err(CoreDumpError::GlobalCountMismatch(global_count, globals.len()).into());
+    }
+    let global_values = globals
+        .iter()
+        .map(|entry| eval_const_expr(&entry.init_expr, store, module_index))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut reader = corestack.ok_or(CoreDumpError::MissingSection("corestack"))?;
+    let version = reader.read_u8()?;
+    if version != 0 {
+        return Err(CoreDumpError::UnsupportedVersion(version).into());
+    }
+    let _thread_name = reader.read_string()?;
+    let frame_count = reader.read_var_u32()?;
+    let mut frames = vec![];
+    let mut ret_pc = None;
+    // Frames are dumped from the youngest but rebuilt from the oldest
+    let mut dumped = vec![];
+    for _ in 0..frame_count {
+        let version = reader.read_u8()?;
+        if version != 0 {
+            return Err(CoreDumpError::UnsupportedVersion(version).into());
+        }
+        let func_index = reader.read_var_u32()?;
+        let code_offset = reader.read_var_u32()?;
+        let locals = read_values(&mut reader)?;
+        let values = read_values(&mut reader)?;
+        dumped.push((func_index, code_offset, locals, values));
+    }
+    for (func_index, code_offset, locals, values) in dumped.into_iter().rev() {
+        let func_addr = FuncAddr::new_unsafe(module_index, func_index as usize);
+        let (func, exec_addr) = store
+            .func(func_addr)
+            .ok_or(CoreDumpError::UnknownFunction(func_index))?;
+        let func = func
+            .defined()
+            .ok_or(CoreDumpError::UnknownFunction(func_index))?;
+        if locals.len() != func.ty().params.len() + func.locals().len() {
+            return Err(CoreDumpError::LocalCountMismatch(func_index).into());
+        }
+        let inst_index = func
+            .instructions()
+            .iter()
+            .position(|inst| inst.offset == code_offset as usize)
+            .ok_or(CoreDumpError::UnknownCodeOffset(func_index, code_offset))?;
+        let pc = ProgramCounter::new(module_index, exec_addr, InstIndex(inst_index as u32));
+        let frame = CallFrame::new_from_func(exec_addr, func, locals, ret_pc);
+        frames.push((frame, values));
+        ret_pc = Some(pc);
+    }
+    let pc = ret_pc.ok_or(CoreDumpError::MissingSection("corestack"))?;
+    let executor = Executor::from_frames(frames, pc, store)?;
+
+    for (index, page_count) in page_counts.into_iter().enumerate() {
+        let memory = store.memory(MemoryAddr::new_unsafe(module_index, index));
+        let mut memory = memory.borrow_mut();
+        if page_count > memory.page_count() {
+            let diff = page_count - memory.page_count();
+            memory.grow(diff).map_err(|e| anyhow::anyhow!("{}", e))?;
+        } else {
+            memory.shrink(page_count);
+        }
+        for byte in memory.raw_data_mut() {
+            *byte = 0;
+        }
+    }
+    for (index, offset, bytes) in data {
+        let memory = store.memory(MemoryAddr::new_unsafe(module_index, index));
+        memory
+            .borrow_mut()
+            .store(offset, bytes)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+    }
+    for (index, value) in global_values.into_iter().enumerate() {
+        let global = store.global(GlobalAddr::new_unsafe(module_index, index));
+        let mut global = global.borrow_mut();
+        if global.is_mutable() {
+            global.set_value(value);
+        }
+    }
+    Ok(executor)
+}
+
+fn executor_insts<'a>(store: &'a Store, pc: &ProgramCounter) -> &'a [super::Instruction] {
+    match store.func_global(pc.exec_addr()).defined() {
+        Some(func) => func.instructions(),
+        None => &[],
+    }
+}
+
+fn read_values(reader: &mut BinaryReader) -> Result<Vec<Value>> {
+    let count = reader.read_var_u32()?;
+    let mut values = vec![];
+    for _ in 0..count {
+        let value = match reader.read_u8()? {
+            0x7f => Value::I32(reader.read_var_i32()?),
+            0x7e => Value::I64(reader.read_var_i64()?),
+            0x7d => Value::F32(f32::from_bits(reader.read_f32()?.bits())),
+            0x7c => Value::F64(f64::from_bits(reader.read_f64()?.bits())),
+            ty => return Err(CoreDumpError::UnknownValueType(ty).into()),
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn value_type_code(value: Value) -> u8 {
+    match value.value_type() {
+        Type::I32 => 0x7f,
+        Type::I64 => 0x7e,
+        Type::F32 => 0x7d,
+        _ => 0x7c,
+    }
+}
+
+fn write_value(buf: &mut Vec<u8>, value: &Value) {
+    buf.push(value_type_code(*value));
+    match *value {
+        Value::I32(v) => write_i64(buf, v as i64),
+        Value::I64(v) => write_i64(buf, v),
+        Value::F32(v) => buf.extend_from_slice(&v.to_bits().to_le_bytes()),
+        Value::F64(v) => buf.extend_from_slice(&v.to_bits().to_le_bytes()),
+    }
+}
+
+fn write_u32(buf: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn write_i64(buf: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let sign_bit = byte & 0x40 != 0;
+        if (value == 0 && !sign_bit) || (value == -1 && sign_bit) {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn write_name(buf: &mut Vec<u8>, name: &str) {
+    write_u32(buf, name.len() as u32);
+    buf.extend_from_slice(name.as_bytes());
+}
+
+fn write_section(module: &mut Vec<u8>, id: u8, payload: &[u8]) {
+    module.push(id);
+    write_u32(module, payload.len() as u32);
+    module.extend_from_slice(payload);
+}
+
+fn write_custom_section(module: &mut Vec<u8>, name: &str, payload: &[u8]) {
+    let mut content = vec![];
+    write_name(&mut content, name);
+    content.extend_from_slice(payload);
+    write_section(module, 0, &content);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::Trap;
+    use crate::interceptor::NopInterceptor;
+
+    /// `main` calls `inner`, which modifies the memory, a global and a local before trapping
+    const MODULE: &str = r#"
+    (module
+      (memory 1 2)
+      (global $g (mut i32) (i32.const 0))
+      (global $c i64 (i64.const 5))
+      (func $inner (param i32) (result i32) (local f64)
+        i32.const 100
+        i32.const 0xabcd
+        i32.store
+        i32.const 9
+        global.set $g
+        f64.const 1.5
+        local.set 1
+        i64.const -3
+        unreachable)
+      (func $main (export "main") (result i32)
+        i32.const 1
+        i32.const 2
+        call $inner
+        i32.add))
+    "#;
+
+    fn wat2wasm(wat: &str) -> Vec<u8> {
+        let buf = wast::parser::ParseBuffer::new(wat).unwrap();
+        let mut wat = wast::parser::parse::<wast::Wat>(&buf).unwrap();
+        wat.module.encode().unwrap()
+    }
+
+    fn instantiate(wasm: &[u8]) -> (Store, ModuleIndex) {
+        let mut store = Store::new();
+        let mut reader = ModuleReader::new(wasm).unwrap();
+        let module_index = store.load_parity_module(None, &mut reader).unwrap();
+        (store, module_index)
+    }
+
+    /// Run `main` until it traps
+    fn trapped_executor(store: &Store, module_index: ModuleIndex) -> Executor {
+        let func_addr = FuncAddr::new_unsafe(module_index, 1);
+        let (func, exec_addr) = store.func(func_addr).unwrap();
+        let func = func.defined().unwrap();
+        let frame = CallFrame::new_from_func(exec_addr, func, vec![], None);
+        let pc = ProgramCounter::new(module_index, exec_addr, InstIndex::zero());
+        let mut executor = Executor::new(frame, 1, pc);
+        let interceptor = NopInterceptor::new();
+        loop {
+            match executor.execute_step(store, &interceptor) {
+                Ok(_) => continue,
+                Err(Trap::Unreachable) => return executor,
+                Err(err) => panic!("unexpected trap {}", err),
+            }
+        }
+    }
+
+    fn frames(executor: &Executor, store: &Store) -> Vec<(String, Vec<Value>, Vec<Value>)> {
+        executor
+            .stack
+            .peek_frame_values()
+            .iter()
+            .map(|(frame, values)| {
+                (
+                    store.func_global(frame.exec_addr).name().clone(),
+                    frame.locals.clone(),
+                    values.iter().map(|v| **v).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let wasm = wat2wasm(MODULE);
+        let (store, module_index) = instantiate(&wasm);
+        let executor = trapped_executor(&store, module_index);
+        let core = write_coredump(&executor, &store, module_index, "test.wasm");
+
+        let (restored_store, restored_index) = instantiate(&wasm);
+        let restored = read_coredump(&core, &restored_store, restored_index).unwrap();
+
+        assert_eq!(
+            frames(&restored, &restored_store),
+            vec![
+                ("main".to_string(), vec![], vec![Value::I32(1)]),
+                (
+                    "inner".to_string(),
+                    vec![Value::I32(2), Value::F64(1.5)],
+                    vec![Value::I64(-3)]
+                ),
+            ]
+        );
+        assert_eq!(
+            frames(&restored, &restored_store),
+            frames(&executor, &store)
+        );
+        assert_eq!(restored.pc.inst_index().0, executor.pc.inst_index().0);
+
+        let memory = restored_store.memory(MemoryAddr::new_unsafe(restored_index, 0));
+        let memory = memory.borrow();
+        assert_eq!(memory.page_count(), 1);
+        assert_eq!(&memory.raw_data()[100..104], &0xabcdu32.to_le_bytes());
+        let global = |index| {
+            let addr = GlobalAddr::new_unsafe(restored_index, index);
+            restored_store.global(addr).borrow().value()
+        };
+        assert_eq!(global(0), Value::I32(9));
+        assert_eq!(global(1), Value::I64(5));
+    }
+
+    #[test]
+    fn test_restore_grown_memory() {
+        let wasm = wat2wasm(MODULE);
+        let (store, module_index) = instantiate(&wasm);
+        let executor = trapped_executor(&store, module_index);
+        let memory = store.memory(MemoryAddr::new_unsafe(module_index, 0));
+        memory.borrow_mut().grow(1).unwrap();
+        memory
+            .borrow_mut()
+            .store(crate::WASM_PAGE_SIZE, &[1])
+            .unwrap();
+        let core = write_coredump(&executor, &store, module_index, "test.wasm");
+
+        let (restored_store, restored_index) = instantiate(&wasm);
+        read_coredump(&core, &restored_store, restored_index).unwrap();
+        let memory = restored_store.memory(MemoryAddr::new_unsafe(restored_index, 0));
+        let memory = memory.borrow();
+        assert_eq!(memory.page_count(), 2);
+        assert_eq!(memory.raw_data()[crate::WASM_PAGE_SIZE], 1);
+    }
+
+    #[test]
+    fn test_module_mismatch() {
+        let (store, module_index) = instantiate(&wat2wasm(MODULE));
+        let executor = trapped_executor(&store, module_index);
+        let core = write_coredump(&executor, &store, module_index, "test.wasm");
+
+        let (other_store, other_index) = instantiate(&wat2wasm("(module (func))"));
+        match read_coredump(&core, &other_store, other_index) {
+            Err(err) => assert_eq!(err.to_string(), "module has 0 memories but core dump has 1"),
+            Ok(_) => panic!("core dump of another module should be rejected"),
+        }
+    }
+
+    /// Assert that `core` is rejected with `message` and the store is left untouched
+    fn assert_rejected(core: &[u8], message: &str) {
+        let (store, module_index) = instantiate(&wat2wasm(MODULE));
+        match read_coredump(core, &store, module_index) {
+            Err(err) => assert_eq!(err.to_string(), message),
+            Ok(_) => panic!("core dump should be rejected with '{}'", message),
+        }
+        let memory = store.memory(MemoryAddr::new_unsafe(module_index, 0));
+        assert_eq!(&memory.borrow().raw_data()[100..104], &[0; 4]);
+        let global = store.global(GlobalAddr::new_unsafe(module_index, 0));
+        assert_eq!(global.borrow().value(), Value::I32(0));
+    }
+
+    fn trapped_core() -> Vec<u8> {
+        let (store, module_index) = instantiate(&wat2wasm(MODULE));
+        let executor = trapped_executor(&store, module_index);
+        write_coredump(&executor, &store, module_index, "test.wasm")
+    }
+
+    #[test]
+    fn test_unknown_memory() {
+        let mut core = trapped_core();
+        // A data segment for memory 1 at offset 0
+        write_section(&mut core, 11, &[1, 2, 1, 0x41, 0, 0x0b, 1, 0xff]);
+        assert_rejected(&core, "memory 1 not found");
+    }
+
+    #[test]
+    fn test_data_out_of_bounds() {
+        let mut core = trapped_core();
+        // A data segment for memory 0 at offset 0x10000, which is past its only page
+        let segment = [1, 0, 0x41, 0x80, 0x80, 0x04, 0x0b, 1, 0xff];
+        write_section(&mut core, 11, &segment);
+        assert_rejected(&core, "data at 0x10000 is out of bounds of memory 0");
+    }
+
+    #[test]
+    fn test_invalid_corestack() {
+        let mut core = trapped_core();
+        // The last corestack section replaces the valid one
+        write_custom_section(&mut core, "corestack", &[1]);
+        assert_rejected(&core, "unsupported core dump version 1");
+    }
+
+    #[test]
+    fn test_missing_corestack() {
+        let (store, module_index) = instantiate(&wat2wasm("(module)"));
+        match read_coredump(&wat2wasm("(module)"), &store, module_index) {
+            Err(err) => assert_eq!(err.to_string(), "\"corestack\" section not found"),
+            Ok(_) => panic!("module without corestack should be rejected"),
+        }
+    }
+}
@@ -1,12 +1,15 @@
 use super::address::{FuncAddr, GlobalAddr, MemoryAddr, TableAddr};
 use super::func::*;
+use super::history::{ExecutionHistory, StoreUndo};
 use super::inst::{Instruction, InstructionKind};
 use super::interceptor::{Interceptor, NopInterceptor};
+use super::interrupt::InterruptHandle;
 use super::memory;
 use super::memory::MemoryInstance;
 use super::module::*;
 use super::stack;
 use super::stack::{CallFrame, Label, ProgramCounter, Stack, StackValue};
+use super::stats::ExecutionStats;
 use super::store::*;
 use super::table;
 use super::value;
@@ -28,6 +31,12 @@ pub enum Trap {
     IndirectCallTypeMismatch(/* expected: */ FuncType, /* actual: */ FuncType),
     UnexpectedStackValueType(/* expected: */ Type, /* actual: */ Type),
     UndefinedFunc(usize),
+    /// The fuel given by `Executor::set_fuel` ran out before the next instruction
+    OutOfFuel,
+    /// The execution was stopped by `InterruptHandle::interrupt` before the next instruction
+    Interrupted,
+    /// The label stack doesn't match the blocks enclosing the program counter
+    LabelMismatch(/* expected: */ usize, /* actual: */ usize),
 }
 
 impl std::error::Error for Trap {}
@@ -46,6 +55,13 @@ impl std::fmt::Display for Trap {
             ),
             Self::UndefinedFunc(addr) => write!(f, "uninitialized func at {:?}", addr),
             Self::Unreachable => write!(f, "unreachable"),
+            Self::OutOfFuel => write!(f, "all fuel consumed"),
+            Self::Interrupted => write!(f, "interrupted"),
+            Self::LabelMismatch(expected, actual) => write!(
+                f,
+                "{} labels are expected in the current block but {} are on the stack",
+                expected, actual
+            ),
             _ => write!(f, "{:?}", self),
         }
     }
@@ -68,9 +84,14 @@ pub enum ReturnValError {
 
 pub type ReturnValResult = Result<Vec<Value>, ReturnValError>;
 
+#[derive(Clone)]
 pub struct Executor {
     pub pc: ProgramCounter,
     pub stack: Stack,
+    history: Option<ExecutionHistory>,
+    stats: Option<ExecutionStats>,
+    fuel: Option<u64>,
+    interrupt: InterruptHandle,
 }
 
 impl Executor {
@@ -78,7 +99,14 @@ impl Executor {
         let mut stack = Stack::default();
         let _ = stack.set_frame(initial_frame);
         stack.push_label(Label::Return(initial_arity));
-        Self { pc, stack }
+        Self {
+            pc,
+            stack,
+            history: None,
+            stats: None,
+            fuel: None,
+            interrupt: InterruptHandle::new(),
+        }
     }
 
     pub fn pop_result(&mut self, return_ty: Vec<Type>) -> ReturnValResult {
@@ -103,10 +131,52 @@ impl Executor {
         store: &Store,
         interceptor: &I,
     ) -> ExecResult<Signal> {
+        if self.interrupt.take() {
+            return Err(Trap::Interrupted);
+        }
+        if let Some(fuel) = self.fuel.as_mut() {
+            if *fuel == 0 {
+                return Err(Trap::OutOfFuel);
+            }
+            *fuel -= 1;
+        }
         let func = store.func_global(self.pc.exec_addr()).defined().unwrap();
         let module_index = func.module_index().clone();
         let inst = func.inst(self.pc.inst_index()).clone();
-        return self.execute_inst(&inst, module_index, store, interceptor);
+        interceptor.execute_inst(&inst, self);
+        if let Some(stats) = self.stats.as_mut() {
+            stats.count_inst(&inst);
+        }
+        self.record_operation(store, interceptor, |executor| {
+            executor.execute_inst(&inst, module_index, store, interceptor)
+        })
+    }
+
+    /// Apply `operation` to the current frame as an instruction, so that it can be reverted
+    /// by `step_back` and returns from functions are notified to the interceptor
+    fn record_operation<I: Interceptor>(
+        &mut self,
+        store: &Store,
+        interceptor: &I,
+        operation: impl FnOnce(&mut Self) -> ExecResult<Signal>,
+    ) -> ExecResult<Signal> {
+        let pc = self.pc;
+        let frame_depth = self.stack.frame_depth();
+        if self.history.is_some() {
+            self.stack.start_journal();
+        }
+        let result = operation(self);
+        if let Some(history) = self.history.as_mut() {
+            history.push(pc, frame_depth, self.stack.take_journal());
+        }
+        if result.is_ok() && self.stack.frame_depth() < frame_depth {
+            let func = store.func_global(pc.exec_addr());
+            let values = self.stack.peek_values();
+            let arity = func.ty().returns.len().min(values.len());
+            let results: Vec<Value> = values[values.len() - arity..].iter().map(|v| **v).collect();
+            interceptor.exit_func(func.name(), &results);
+        }
+        result
     }
 
     fn execute_inst<I: Interceptor>(
@@ -305,6 +375,8 @@ impl Executor {
                 let addr = GlobalAddr::new_unsafe(module_index, global_index as usize);
                 let value = self.stack.pop_value().map_err(Trap::Stack)?;
                 let global = store.global(addr);
+                let original = global.borrow().value();
+                self.record_store(StoreUndo::Global(addr, original));
                 global.borrow_mut().set_value(value);
                 Ok(Signal::Next)
             }
@@ -384,8 +456,13 @@ impl Executor {
                 let grow_page: i32 = self.pop_as()?;
                 let mem = self.memory(store)?;
                 let size = mem.borrow().page_count();
+                let mem_addr = self.memory_addr()?;
+                self.record_store(StoreUndo::MemoryGrow(mem_addr, size));
                 match mem.borrow_mut().grow(grow_page as usize) {
                     Ok(_) => {
+                        if let Some(stats) = self.stats.as_mut() {
+                            stats.count_memory_grow(size + grow_page as usize);
+                        }
                         self.stack.push_value(Value::I32(size as i32));
                     }
                     Err(err) => {
@@ -704,17 +781,34 @@ impl Executor {
         let arity = func.ty().returns.len();
         match func {
             FunctionInstance::Defined(func) => {
+                if let Some(stats) = self.stats.as_mut() {
+                    stats.count_call(exec_addr);
+                }
                 let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
                 let frame = CallFrame::new_from_func(exec_addr, &func, args, Some(self.pc));
                 self.stack.set_frame(frame).map_err(Trap::Stack)?;
                 self.stack.push_label(Label::Return(arity));
                 self.pc = pc;
+                let params_len = func.ty().params.len();
+                let frame = self.stack.current_frame().map_err(Trap::Stack)?;
+                interceptor.enter_func(func.name(), &frame.locals[..params_len]);
                 interceptor.invoke_func(func.name())
             }
             FunctionInstance::Host(func) => {
+                if let Some(stats) = self.stats.as_mut() {
+                    stats.count_host_call(exec_addr);
+                }
                 let mut result = Vec::new();
+                let mem_addr = self.prepare_host_call(store)?;
+                interceptor.enter_func(func.field_name(), &args);
                 func.code()
                     .call(&args, &mut result, store, addr.module_index())?;
+                interceptor.exit_func(func.field_name(), &result);
+                if let (Some(mem_addr), Some(history)) = (mem_addr, self.history.as_mut()) {
+                    history.record_host_writes(mem_addr);
+                }
+                // Host functions may grow the memory as well as `memory.grow`
+                self.observe_memory_pages(store)?;
                 assert_eq!(result.len(), arity);
                 for v in result {
                     self.stack.push_value(v);
@@ -742,8 +836,10 @@ impl Executor {
 
         if let Some(ret_pc) = frame.ret_pc {
             self.pc = ret_pc;
+            Ok(Signal::Next)
+        } else {
+            Ok(Signal::End)
         }
-        Ok(Signal::Next)
     }
 
     fn set_local(&mut self, index: usize) -> ExecResult<Signal> {
@@ -753,10 +849,29 @@ impl Executor {
         Ok(Signal::Next)
     }
 
-    fn memory(&self, store: &Store) -> ExecResult<std::rc::Rc<std::cell::RefCell<MemoryInstance>>> {
+    fn memory_addr(&self) -> ExecResult<MemoryAddr> {
         let frame = self.stack.current_frame().map_err(Trap::Stack)?;
-        let mem_addr = MemoryAddr::new_unsafe(frame.module_index(), 0);
-        Ok(store.memory(mem_addr))
+        Ok(MemoryAddr::new_unsafe(frame.module_index(), 0))
+    }
+
+    fn memory(&self, store: &Store) -> ExecResult<std::rc::Rc<std::cell::RefCell<MemoryInstance>>> {
+        Ok(store.memory(self.memory_addr()?))
+    }
+
+    fn write_memory(&mut self, addr: usize, buf: &[u8], store: &Store) -> ExecResult<()> {
+        let mem = self.memory(store)?;
+        if self.history.is_some() {
+            mem.borrow()
+                .validate_region(addr, buf.len())
+                .map_err(Trap::Memory)?;
+            let original = mem.borrow().raw_data()[addr..addr + buf.len()].to_vec();
+            self.record_store(StoreUndo::Memory(self.memory_addr()?, addr, original));
+        }
+        mem.borrow_mut().store(addr, buf).map_err(Trap::Memory)?;
+        if let Some(history) = self.history.as_mut() {
+            history.sync_shadow(&mem, addr, buf);
+        }
+        Ok(())
     }
 
     fn store<T: NativeValue + IntoLittleEndian>(
@@ -772,10 +887,7 @@ impl Executor {
             .take(std::mem::size_of::<T>())
             .collect();
         val.into_le(&mut buf);
-        self.memory(store)?
-            .borrow_mut()
-            .store(addr, &buf)
-            .map_err(Trap::Memory)?;
+        self.write_memory(addr, &buf, store)?;
         Ok(Signal::Next)
     }
 
@@ -794,10 +906,7 @@ impl Executor {
             .collect();
         val.into_le(&mut buf);
         let buf: Vec<u8> = buf.into_iter().take(width).collect();
-        self.memory(store)?
-            .borrow_mut()
-            .store(addr, &buf)
-            .map_err(Trap::Memory)?;
+        self.write_memory(addr, &buf, store)?;
         Ok(Signal::Next)
     }
 
@@ -839,8 +948,280 @@ impl Executor {
     }
 }
 
+// Fuel metering
+impl Executor {
+    /// Limit the number of instructions executed from now on. `None` removes the limit.
+    /// When the fuel runs out, `execute_step` fails with `Trap::OutOfFuel` without
+    /// executing the next instruction, so the execution can be resumed after refueling.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    /// The remaining fuel
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+}
+
+// Interruption
+impl Executor {
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Share the handle with other executors to stop them at once
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.interrupt = handle;
+    }
+}
+
+// Statistics
+impl Executor {
+    /// Start counting executed instructions, calls and memory growth
+    pub fn enable_stats(&mut self, store: &Store) -> ExecResult<()> {
+        self.stats = Some(ExecutionStats::new());
+        self.observe_memory_pages(store)
+    }
+
+    /// Update the peak memory size in the statistics with the current memory
+    fn observe_memory_pages(&mut self, store: &Store) -> ExecResult<()> {
+        let stats = match self.stats.as_mut() {
+            Some(stats) => stats,
+            None => return Ok(()),
+        };
+        let frame = self.stack.current_frame().map_err(Trap::Stack)?;
+        if store.memory_count(frame.module_index()) == 0 {
+            return Ok(());
+        }
+        let mem_addr = MemoryAddr::new_unsafe(frame.module_index(), 0);
+        stats.observe_memory_pages(store.memory(mem_addr).borrow().page_count());
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Option<&ExecutionStats> {
+        self.stats.as_ref()
+    }
+
+    pub fn take_stats(&mut self) -> Option<ExecutionStats> {
+        self.stats.take()
+    }
+}
+
+// Reverse execution
+impl Executor {
+    /// Start recording executed instructions to be able to step back up to `limit` instructions
+    pub fn enable_history(&mut self, limit: usize) {
+        self.history = Some(ExecutionHistory::new(limit));
+    }
+
+    pub fn history(&self) -> Option<&ExecutionHistory> {
+        self.history.as_ref()
+    }
+
+    /// Forget the recorded instructions. Call this when the store or the stack is
+    /// modified outside of the execution, which the records can't revert.
+    pub fn clear_history(&mut self) {
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+    }
+
+    /// Revert the latest executed instruction. Returns false if there is no recorded instruction.
+    pub fn step_back(&mut self, store: &Store) -> bool {
+        let history = match self.history.as_mut() {
+            Some(history) => history,
+            None => return false,
+        };
+        let record = match history.pop() {
+            Some(record) => record,
+            None => return false,
+        };
+        self.pc = record.pc();
+        for undo in record.store.into_iter().rev() {
+            match undo {
+                StoreUndo::Memory(addr, offset, bytes) => {
+                    let mem = store.memory(addr);
+                    let _ = mem.borrow_mut().store(offset, &bytes);
+                    history.sync_shadow(&mem, offset, &bytes);
+                }
+                StoreUndo::MemoryGrow(addr, page_count) => {
+                    let mem = store.memory(addr);
+                    mem.borrow_mut().shrink(page_count);
+                    history.truncate_shadow(&mem, page_count * crate::WASM_PAGE_SIZE);
+                }
+                StoreUndo::Global(addr, value) => {
+                    store.global(addr).borrow_mut().set_value(value);
+                }
+            }
+        }
+        self.stack.undo(record.stack);
+        true
+    }
+
+    fn record_store(&mut self, undo: StoreUndo) {
+        if let Some(history) = self.history.as_mut() {
+            history.record_store(undo);
+        }
+    }
+
+    /// Get the memory which a host function may modify ready to find the modification.
+    /// Returns the address of the memory if the history is recorded.
+    fn prepare_host_call(&mut self, store: &Store) -> ExecResult<Option<MemoryAddr>> {
+        if self.history.is_none() {
+            return Ok(None);
+        }
+        let frame = self.stack.current_frame().map_err(Trap::Stack)?;
+        if store.memory_count(frame.module_index()) == 0 {
+            return Ok(None);
+        }
+        let mem_addr = self.memory_addr()?;
+        if let Some(history) = self.history.as_mut() {
+            history.prepare_shadow(&store.memory(mem_addr));
+        }
+        Ok(Some(mem_addr))
+    }
+}
+
 use anyhow;
 use wasmparser::InitExpr;
+// Debugger
+impl Executor {
+    /// Return from the current function with the given values as if `return` is executed
+    pub fn force_return<I: Interceptor>(
+        &mut self,
+        values: Vec<Value>,
+        store: &Store,
+        interceptor: &I,
+    ) -> ExecResult<Signal> {
+        self.record_operation(store, interceptor, |executor| {
+            for value in values {
+                executor.stack.push_value(value);
+            }
+            executor.do_return(store)
+        })
+    }
+
+    /// Move the program counter to the instruction in the current function.
+    /// Labels of exited blocks are popped and labels of entered blocks are pushed
+    /// to keep the label stack consistent with the destination.
+    pub fn jump<I: Interceptor>(
+        &mut self,
+        target: InstIndex,
+        store: &Store,
+        interceptor: &I,
+    ) -> ExecResult<()> {
+        let insts = self.current_func_insts(store)?;
+        let current = block_nesting(insts, self.pc.inst_index().0 as usize);
+        let destination = block_nesting(insts, target.0 as usize);
+        let common = current
+            .iter()
+            .zip(destination.iter())
+            .take_while(|(lhs, rhs)| lhs == rhs)
+            .count();
+        let labels = self
+            .stack
+            .current_frame_labels()
+            .map_err(Trap::Stack)?
+            .len();
+        // The function body itself has a label
+        if labels != current.len() + 1 {
+            return Err(Trap::LabelMismatch(current.len() + 1, labels));
+        }
+
+        self.record_operation(store, interceptor, |executor| {
+            for _ in common..current.len() {
+                executor
+                    .stack
+                    .pop_while(|v| matches!(v, StackValue::Value(_)));
+                executor.stack.pop_label().map_err(Trap::Stack)?;
+            }
+            for start in &destination[common..] {
+                executor.stack.push_label(block_label(insts, *start));
+            }
+            executor.pc =
+                ProgramCounter::new(executor.pc.module_index(), executor.pc.exec_addr(), target);
+            Ok(Signal::Next)
+        })?;
+        Ok(())
+    }
+}
+
+// Core dump
+impl Executor {
+    /// Rebuild an executor from call frames ordered from the oldest with their values.
+    /// `pc` is the program counter of the youngest frame and others are resumed at `ret_pc`
+    /// of the next frame. Values are pushed above the labels of enclosing blocks.
+    pub fn from_frames(
+        frames: Vec<(CallFrame, Vec<Value>)>,
+        pc: ProgramCounter,
+        store: &Store,
+    ) -> ExecResult<Self> {
+        let mut pcs: Vec<ProgramCounter> = frames
+            .iter()
+            .skip(1)
+            .map(|(frame, _)| frame.ret_pc.ok_or(Trap::Stack(stack::Error::NoCallFrame)))
+            .collect::<ExecResult<_>>()?;
+        pcs.push(pc);
+        let mut executor: Option<Executor> = None;
+        for ((frame, values), pc) in frames.into_iter().zip(pcs) {
+            let func = store.func_global(pc.exec_addr());
+            let arity = func.ty().returns.len();
+            let insts = func.defined().unwrap().instructions();
+            let executor = match executor.as_mut() {
+                Some(executor) => {
+                    executor.stack.set_frame(frame).map_err(Trap::Stack)?;
+                    executor.stack.push_label(Label::Return(arity));
+                    executor.pc = pc;
+                    executor
+                }
+                None => executor.get_or_insert(Executor::new(frame, arity, pc)),
+            };
+            for start in block_nesting(insts, pc.inst_index().0 as usize) {
+                executor.stack.push_label(block_label(insts, start));
+            }
+            for value in values {
+                executor.stack.push_value(value);
+            }
+        }
+        executor.ok_or(Trap::Stack(stack::Error::NoCallFrame))
+    }
+}
+
+fn block_arity(ty: TypeOrFuncType) -> usize {
+    match ty {
+        TypeOrFuncType::Type(Type::EmptyBlockType) => 0,
+        TypeOrFuncType::Type(_) => 1,
+        TypeOrFuncType::FuncType(_) => 1,
+    }
+}
+
+/// Label pushed when entering the block instruction at `start`
+fn block_label(insts: &[Instruction], start: usize) -> Label {
+    match insts[start].kind {
+        InstructionKind::Block { ty } => Label::Block(block_arity(ty)),
+        InstructionKind::If { ty } => Label::If(block_arity(ty)),
+        InstructionKind::Loop { ty: _ } => Label::new_loop(InstIndex(start as u32)),
+        _ => unreachable!(),
+    }
+}
+
+/// Returns indices of block instructions which enclose the instruction at `index`
+fn block_nesting(insts: &[Instruction], index: usize) -> Vec<usize> {
+    let mut starts = vec![];
+    for (index, inst) in insts[..index].iter().enumerate() {
+        match inst.kind {
+            InstructionKind::Block { ty: _ }
+            | InstructionKind::Loop { ty: _ }
+            | InstructionKind::If { ty: _ } => starts.push(index),
+            InstructionKind::End => {
+                starts.pop();
+            }
+            _ => (),
+        }
+    }
+    starts
+}
+
 pub fn eval_const_expr(
     init_expr: &InitExpr,
     store: &Store,
@@ -891,6 +1272,18 @@ pub fn simple_invoke_func(
     func_addr: FuncAddr,
     arguments: Vec<Value>,
     store: &mut Store,
+) -> Result<Vec<Value>, WasmError> {
+    invoke_func(func_addr, arguments, store, None, InterruptHandle::new())
+}
+
+/// Invoke the function executing at most `fuel` instructions if given.
+/// The execution fails with `Trap::Interrupted` when `interrupt` is requested.
+pub fn invoke_func(
+    func_addr: FuncAddr,
+    arguments: Vec<Value>,
+    store: &mut Store,
+    fuel: Option<u64>,
+    interrupt: InterruptHandle,
 ) -> Result<Vec<Value>, WasmError> {
     match store
         .func(func_addr)
@@ -915,6 +1308,8 @@ pub fn simple_invoke_func(
             let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
             let interceptor = NopInterceptor::new();
             let mut executor = Executor::new(frame, ret_types.len(), pc);
+            executor.set_fuel(fuel);
+            executor.set_interrupt_handle(interrupt);
             loop {
                 let result = executor.execute_step(store, &interceptor);
                 match result {
@@ -930,3 +1325,178 @@ pub fn simple_invoke_func(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmparser::ModuleReader;
+
+    const MODULE: &str = r#"
+    (module
+      (func $nested (export "nested") (result i32)
+        i32.const 1
+        block
+          i32.const 2
+          drop
+          loop
+            nop
+          end
+        end
+        i32.const 1
+        if (result i32)
+          i32.const 10
+        else
+          i32.const 20
+        end
+        i32.add)
+      (func $callee (result i32)
+        i32.const 5)
+      (func $caller (export "caller") (result i32)
+        call $callee
+        i32.const 1
+        i32.add))
+    "#;
+
+    fn wat2wasm(wat: &str) -> Vec<u8> {
+        let buf = wast::parser::ParseBuffer::new(wat).unwrap();
+        let mut wat = wast::parser::parse::<wast::Wat>(&buf).unwrap();
+        wat.module.encode().unwrap()
+    }
+
+    fn instantiate() -> (Store, ModuleIndex) {
+        let wasm = wat2wasm(MODULE);
+        let mut store = Store::new();
+        let mut reader = ModuleReader::new(&wasm).unwrap();
+        let module_index = store.load_parity_module(None, &mut reader).unwrap();
+        (store, module_index)
+    }
+
+    fn executor(store: &Store, module_index: ModuleIndex, func_index: usize) -> Executor {
+        let (func, exec_addr) = store
+            .func(FuncAddr::new_unsafe(module_index, func_index))
+            .unwrap();
+        let func = func.defined().unwrap();
+        let frame = CallFrame::new_from_func(exec_addr, func, vec![], None);
+        let pc = ProgramCounter::new(module_index, exec_addr, InstIndex::zero());
+        Executor::new(frame, func.ty().returns.len(), pc)
+    }
+
+    /// Index of the `nth` instruction of `kind` in the current function
+    fn inst_index(executor: &Executor, store: &Store, kind: &str, nth: usize) -> InstIndex {
+        let insts = executor.current_func_insts(store).unwrap();
+        let index = insts
+            .iter()
+            .enumerate()
+            .filter(|(_, inst)| format!("{:?}", inst.kind).starts_with(kind))
+            .nth(nth)
+            .unwrap()
+            .0;
+        InstIndex(index as u32)
+    }
+
+    fn labels(executor: &Executor) -> Vec<&'static str> {
+        executor
+            .stack
+            .current_frame_labels()
+            .unwrap()
+            .iter()
+            .map(|label| match label {
+                Label::Return(_) => "return",
+                Label::Block(_) => "block",
+                Label::Loop(_) => "loop",
+                Label::If(_) => "if",
+            })
+            .collect()
+    }
+
+    fn values(executor: &Executor) -> Vec<Value> {
+        executor.stack.peek_values().into_iter().copied().collect()
+    }
+
+    fn run_to_end(executor: &mut Executor, store: &Store) -> Vec<Value> {
+        let interceptor = NopInterceptor::new();
+        loop {
+            match executor.execute_step(store, &interceptor) {
+                Ok(Signal::End) => return values(executor),
+                Ok(_) => continue,
+                Err(err) => panic!("unexpected trap {}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_jump() {
+        let (store, module_index) = instantiate();
+        let mut executor = executor(&store, module_index, 0);
+        let interceptor = NopInterceptor::new();
+        // Stop at `drop` in the block
+        let drop = inst_index(&executor, &store, "Drop", 0);
+        while executor.pc.inst_index().0 != drop.0 {
+            executor.execute_step(&store, &interceptor).unwrap();
+        }
+        assert_eq!(labels(&executor), vec!["return", "block"]);
+        assert_eq!(values(&executor), vec![Value::I32(1), Value::I32(2)]);
+
+        // Into the loop
+        let nop = inst_index(&executor, &store, "Nop", 0);
+        executor.jump(nop, &store, &interceptor).unwrap();
+        assert_eq!(labels(&executor), vec!["return", "block", "loop"]);
+        assert_eq!(values(&executor), vec![Value::I32(1), Value::I32(2)]);
+
+        // Out of the loop and the block, and into the else branch
+        let else_value = inst_index(&executor, &store, "I32Const", 4);
+        executor.jump(else_value, &store, &interceptor).unwrap();
+        assert_eq!(labels(&executor), vec!["return", "if"]);
+        assert_eq!(values(&executor), vec![Value::I32(1)]);
+
+        assert_eq!(run_to_end(&mut executor, &store), vec![Value::I32(21)]);
+    }
+
+    #[test]
+    fn test_jump_backward() {
+        let (store, module_index) = instantiate();
+        let mut executor = executor(&store, module_index, 0);
+        let interceptor = NopInterceptor::new();
+        let then_value = inst_index(&executor, &store, "I32Const", 3);
+        executor.jump(then_value, &store, &interceptor).unwrap();
+        assert_eq!(labels(&executor), vec!["return", "if"]);
+        // Back to the top level, from which the whole function is run again
+        executor
+            .jump(InstIndex::zero(), &store, &interceptor)
+            .unwrap();
+        assert_eq!(labels(&executor), vec!["return"]);
+        assert_eq!(run_to_end(&mut executor, &store), vec![Value::I32(11)]);
+    }
+
+    #[test]
+    fn test_jump_with_label_mismatch() {
+        let (store, module_index) = instantiate();
+        let mut executor = executor(&store, module_index, 0);
+        let interceptor = NopInterceptor::new();
+        executor.stack.push_label(Label::Block(0));
+        let nop = inst_index(&executor, &store, "Nop", 0);
+        match executor.jump(nop, &store, &interceptor) {
+            Err(Trap::LabelMismatch(1, 2)) => (),
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+        assert_eq!(executor.pc.inst_index().0, 0);
+        assert_eq!(labels(&executor), vec!["return", "block"]);
+    }
+
+    #[test]
+    fn test_force_return() {
+        let (store, module_index) = instantiate();
+        let mut executor = executor(&store, module_index, 2);
+        let interceptor = NopInterceptor::new();
+        // Enter `callee`
+        executor.execute_step(&store, &interceptor).unwrap();
+        assert_eq!(executor.stack.peek_frames().len(), 2);
+
+        executor
+            .force_return(vec![Value::I32(41)], &store, &interceptor)
+            .unwrap();
+        assert_eq!(executor.stack.peek_frames().len(), 1);
+        assert_eq!(values(&executor), vec![Value::I32(41)]);
+        assert_eq!(run_to_end(&mut executor, &store), vec![Value::I32(42)]);
+    }
+}
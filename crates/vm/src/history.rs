@@ -0,0 +1,204 @@
+use super::address::{GlobalAddr, MemoryAddr};
+use super::memory::MemoryInstance;
+use super::stack::{ProgramCounter, StackUndo};
+use super::value::Value;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// The unit in which memory modified by host functions is compared
+const SHADOW_CHUNK_SIZE: usize = 4096;
+
+/// Operation to revert a modification of the store
+#[derive(Clone)]
+pub(crate) enum StoreUndo {
+    Memory(
+        MemoryAddr,
+        /* offset: */ usize,
+        /* original bytes: */ Vec<u8>,
+    ),
+    MemoryGrow(MemoryAddr, /* original page count: */ usize),
+    Global(GlobalAddr, Value),
+}
+
+/// Everything needed to revert a single executed instruction
+#[derive(Clone)]
+pub struct ExecutionRecord {
+    pc: ProgramCounter,
+    frame_depth: usize,
+    pub(crate) stack: Vec<StackUndo>,
+    pub(crate) store: Vec<StoreUndo>,
+}
+
+impl ExecutionRecord {
+    /// The program counter before the instruction was executed
+    pub fn pc(&self) -> ProgramCounter {
+        self.pc
+    }
+
+    /// The number of call frames before the instruction was executed
+    pub fn frame_depth(&self) -> usize {
+        self.frame_depth
+    }
+}
+
+/// Copy of a linear memory kept in sync with recorded stores and undos.
+/// Host functions write memory through a raw slice, so their writes are found
+/// by comparing the memory with this copy after the call.
+#[derive(Clone)]
+struct MemoryShadow {
+    memory: Rc<RefCell<MemoryInstance>>,
+    bytes: Vec<u8>,
+}
+
+/// Execution log which records the latest `limit` instructions
+#[derive(Clone)]
+pub struct ExecutionHistory {
+    records: VecDeque<ExecutionRecord>,
+    pending_store: Vec<StoreUndo>,
+    shadow: Option<MemoryShadow>,
+    limit: usize,
+}
+
+impl ExecutionHistory {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            records: VecDeque::new(),
+            pending_store: Vec::new(),
+            shadow: None,
+            limit,
+        }
+    }
+
+    /// Records from the oldest to the latest
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &ExecutionRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Forget all records. Used when the state is modified outside of the execution
+    /// because the records can't revert it consistently anymore.
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.pending_store.clear();
+        self.shadow = None;
+    }
+
+    pub(crate) fn record_store(&mut self, undo: StoreUndo) {
+        self.pending_store.push(undo);
+    }
+
+    pub(crate) fn push(&mut self, pc: ProgramCounter, frame_depth: usize, stack: Vec<StackUndo>) {
+        if self.records.len() >= self.limit {
+            self.records.pop_front();
+        }
+        let store = std::mem::take(&mut self.pending_store);
+        self.records.push_back(ExecutionRecord {
+            pc,
+            frame_depth,
+            stack,
+            store,
+        });
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<ExecutionRecord> {
+        self.records.pop_back()
+    }
+
+    /// Reflect bytes written to the memory to the shadow copy
+    pub(crate) fn sync_shadow(
+        &mut self,
+        memory: &Rc<RefCell<MemoryInstance>>,
+        offset: usize,
+        bytes: &[u8],
+    ) {
+        if let Some(shadow) = self.shadow.as_mut() {
+            if !Rc::ptr_eq(&shadow.memory, memory) {
+                return;
+            }
+            // Bytes beyond the copy are taken when it's extended
+            if let Some(dst) = shadow.bytes.get_mut(offset..offset + bytes.len()) {
+                dst.copy_from_slice(bytes);
+            }
+        }
+    }
+
+    /// Reflect the memory shrunk to `size` bytes to the shadow copy
+    pub(crate) fn truncate_shadow(&mut self, memory: &Rc<RefCell<MemoryInstance>>, size: usize) {
+        if let Some(shadow) = self.shadow.as_mut() {
+            if Rc::ptr_eq(&shadow.memory, memory) {
+                shadow.bytes.truncate(size);
+            }
+        }
+    }
+
+    /// Make the shadow copy identical to the memory before calling a host function.
+    /// The whole memory is copied only when another memory is shadowed.
+    pub(crate) fn prepare_shadow(&mut self, memory: &Rc<RefCell<MemoryInstance>>) {
+        let data = memory.borrow();
+        let data = data.raw_data();
+        match self.shadow.as_mut() {
+            Some(shadow) if Rc::ptr_eq(&shadow.memory, memory) => {
+                // The memory may be grown by `memory.grow` since the last call
+                if shadow.bytes.len() < data.len() {
+                    let len = shadow.bytes.len();
+                    shadow.bytes.extend_from_slice(&data[len..]);
+                } else {
+                    shadow.bytes.truncate(data.len());
+                }
+            }
+            _ => {
+                self.shadow = Some(MemoryShadow {
+                    memory: memory.clone(),
+                    bytes: data.to_vec(),
+                });
+            }
+        }
+    }
+
+    /// Record undos for bytes modified by a host function since `prepare_shadow`.
+    /// Unmodified chunks are skipped with a slice comparison.
+    pub(crate) fn record_host_writes(&mut self, mem_addr: MemoryAddr) {
+        let shadow = match self.shadow.as_mut() {
+            Some(shadow) => shadow,
+            None => return,
+        };
+        let data = shadow.memory.borrow();
+        let current = data.raw_data();
+        let original_len = shadow.bytes.len();
+        if current.len() > original_len {
+            let page_count = original_len / crate::WASM_PAGE_SIZE;
+            self.pending_store
+                .push(StoreUndo::MemoryGrow(mem_addr, page_count));
+        }
+        for chunk_start in (0..original_len).step_by(SHADOW_CHUNK_SIZE) {
+            let chunk_end = std::cmp::min(chunk_start + SHADOW_CHUNK_SIZE, original_len);
+            if shadow.bytes[chunk_start..chunk_end] == current[chunk_start..chunk_end] {
+                continue;
+            }
+            let mut offset = chunk_start;
+            while offset < chunk_end {
+                if shadow.bytes[offset] == current[offset] {
+                    offset += 1;
+                    continue;
+                }
+                let start = offset;
+                while offset < chunk_end && shadow.bytes[offset] != current[offset] {
+                    offset += 1;
+                }
+                let bytes = shadow.bytes[start..offset].to_vec();
+                self.pending_store
+                    .push(StoreUndo::Memory(mem_addr, start, bytes));
+            }
+            shadow.bytes[chunk_start..chunk_end].copy_from_slice(&current[chunk_start..chunk_end]);
+        }
+        shadow.bytes.extend_from_slice(&current[original_len..]);
+    }
+}
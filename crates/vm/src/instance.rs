@@ -1,5 +1,7 @@
-use super::executor::{simple_invoke_func, WasmError};
+use super::address::FuncAddr;
+use super::executor::{invoke_func, WasmError};
 use super::host::HostValue;
+use super::interrupt::InterruptHandle;
 use super::module::ModuleIndex;
 use super::store::Store;
 use super::value::Value;
@@ -10,6 +12,7 @@ use std::io::Read;
 
 pub struct WasmInstance {
     pub store: Store,
+    interrupt: InterruptHandle,
 }
 
 impl WasmInstance {
@@ -51,9 +54,16 @@ impl WasmInstance {
     pub fn new() -> Self {
         Self {
             store: Store::new(),
+            interrupt: InterruptHandle::new(),
         }
     }
 
+    /// Handle to stop `run` from another thread. A request made while nothing
+    /// is running is discarded when the next run starts.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
     pub fn get_global(&self, module_index: ModuleIndex, field: &str) -> Option<Value> {
         self.store
             .scan_global_by_name(module_index, field)
@@ -66,22 +76,47 @@ impl WasmInstance {
         func_name: Option<String>,
         arguments: Vec<Value>,
     ) -> Result<Vec<Value>, WasmError> {
+        let func_addr = self.entry_func_addr(module_index, func_name)?;
+        self.interrupt.clear();
+        let interrupt = self.interrupt.clone();
+        invoke_func(func_addr, arguments, &mut self.store, None, interrupt)
+    }
+
+    /// Run the function like `run`, but fail with `Trap::OutOfFuel` after executing
+    /// `fuel` instructions to stop runaway guests such as infinite loops
+    pub fn run_with_fuel(
+        &mut self,
+        module_index: ModuleIndex,
+        func_name: Option<String>,
+        arguments: Vec<Value>,
+        fuel: u64,
+    ) -> Result<Vec<Value>, WasmError> {
+        let func_addr = self.entry_func_addr(module_index, func_name)?;
+        self.interrupt.clear();
+        let interrupt = self.interrupt.clone();
+        invoke_func(func_addr, arguments, &mut self.store, Some(fuel), interrupt)
+    }
+
+    fn entry_func_addr(
+        &self,
+        module_index: ModuleIndex,
+        func_name: Option<String>,
+    ) -> Result<FuncAddr, WasmError> {
         let module = self.store.module(module_index).defined().unwrap();
-        let func_addr = if let Some(func_name) = func_name {
+        if let Some(func_name) = func_name {
             if let Some(Some(func_addr)) = module.exported_func(func_name.clone()).ok() {
-                func_addr
+                Ok(func_addr)
             } else {
-                return Err(WasmError::EntryFunctionNotFound(func_name.clone()));
+                Err(WasmError::EntryFunctionNotFound(func_name.clone()))
             }
         } else if let Some(start_func_addr) = module.start_func_addr() {
-            *start_func_addr
+            Ok(*start_func_addr)
         } else {
             if let Some(Some(func_addr)) = module.exported_func("_start".to_string()).ok() {
-                func_addr
+                Ok(func_addr)
             } else {
-                return Err(WasmError::EntryFunctionNotFound("_start".to_string()));
+                Err(WasmError::EntryFunctionNotFound("_start".to_string()))
             }
-        };
-        simple_invoke_func(func_addr, arguments, &mut self.store)
+        }
     }
 }
@@ -1,7 +1,15 @@
-use crate::executor::{ExecResult, Signal};
+use crate::executor::{ExecResult, Executor, Signal};
+use crate::inst::Instruction;
+use crate::value::Value;
 
 pub trait Interceptor {
     fn invoke_func(&self, name: &String) -> ExecResult<Signal>;
+    /// Called before executing each instruction
+    fn execute_inst(&self, _inst: &Instruction, _executor: &Executor) {}
+    /// Called when a function is entered with its arguments
+    fn enter_func(&self, _name: &str, _args: &[Value]) {}
+    /// Called when a function returns with its results
+    fn exit_func(&self, _name: &str, _results: &[Value]) {}
 }
 
 pub struct NopInterceptor {}
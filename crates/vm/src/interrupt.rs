@@ -0,0 +1,31 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Handle to stop a running execution from another thread.
+/// The execution fails with `Trap::Interrupted` before the next instruction,
+/// so it can be inspected and resumed.
+#[derive(Clone, Default)]
+pub struct InterruptHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the execution to stop
+    pub fn interrupt(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Discard the pending request
+    pub fn clear(&self) {
+        self.requested.store(false, Ordering::SeqCst);
+    }
+
+    /// Consume the pending request. Returns whether it was requested.
+    pub fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
+}
@@ -2,7 +2,6 @@ use super::module::ModuleIndex;
 use std::collections::HashMap;
 use std::fmt;
 
-#[derive(PartialEq, Eq, Hash)]
 pub struct GlobalAddress<T>(usize, std::marker::PhantomData<T>);
 
 impl<T> Clone for GlobalAddress<T> {
@@ -13,6 +12,21 @@ impl<T> Clone for GlobalAddress<T> {
 
 impl<T> Copy for GlobalAddress<T> {}
 
+// Implemented manually not to require `T` to be comparable as well as `Clone`
+impl<T> PartialEq for GlobalAddress<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for GlobalAddress<T> {}
+
+impl<T> std::hash::Hash for GlobalAddress<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
 impl<T> fmt::Debug for GlobalAddress<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "GlobalAddress({})", self.0)
@@ -120,6 +134,22 @@ impl<T> LinkableCollection<T> {
         )
     }
 
+    /// Reverse lookup of `resolve`
+    pub fn index_in_module(
+        &self,
+        module_index: ModuleIndex,
+        address: GlobalAddress<T>,
+    ) -> Option<usize> {
+        self.item_addrs_by_module
+            .get(&module_index)?
+            .iter()
+            .position(|index| *index == address.0)
+    }
+
+    pub fn all_items(&self) -> &[T] {
+        &self.items
+    }
+
     pub fn is_empty(&self, module_index: ModuleIndex) -> bool {
         self.item_addrs_by_module
             .get(&module_index)
@@ -1,6 +1,7 @@
 use super::value::FromLittleEndian;
 use super::WASM_PAGE_SIZE;
 
+#[derive(Clone)]
 pub struct MemoryInstance {
     data: Vec<u8>,
     pub max: Option<usize>,
@@ -95,6 +96,11 @@ impl MemoryInstance {
         self.data.append(&mut extra);
         return Ok(());
     }
+
+    /// Discard pages grown after the memory had `page_count` pages
+    pub(crate) fn shrink(&mut self, page_count: usize) {
+        self.data.truncate(page_count * WASM_PAGE_SIZE);
+    }
     pub fn raw_data_mut(&mut self) -> &mut [u8] {
         &mut self.data
     }
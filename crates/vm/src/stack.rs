@@ -169,6 +169,7 @@ impl CallFrame {
     }
 }
 
+#[derive(Clone)]
 pub enum StackValue {
     Value(Value),
     Label(Label),
@@ -242,10 +243,23 @@ impl std::fmt::Display for StackValue {
     }
 }
 
-#[derive(Default)]
+/// Operation to revert a modification of the stack
+#[derive(Clone)]
+pub enum StackUndo {
+    Pop,
+    Push(StackValue),
+    SetLocal(
+        /* frame position: */ usize,
+        /* local index: */ usize,
+        Value,
+    ),
+}
+
+#[derive(Clone, Default)]
 pub struct Stack {
     stack: Vec<StackValue>,
     frame_index: Vec<usize>,
+    journal: Option<Vec<StackUndo>>,
 }
 
 // Debugger
@@ -269,13 +283,109 @@ impl Stack {
             })
             .collect()
     }
+
+    /// At most `count` values on the top of the current block, from the deepest
+    pub fn peek_top_values(&self, count: usize) -> Vec<&Value> {
+        let mut values: Vec<&Value> = self
+            .stack
+            .iter()
+            .rev()
+            .map_while(|v| match v {
+                StackValue::Value(v) => Some(v),
+                _ => None,
+            })
+            .take(count)
+            .collect();
+        values.reverse();
+        values
+    }
+
+    pub fn peek_values_mut(&mut self) -> Vec<&mut Value> {
+        self.stack
+            .iter_mut()
+            .filter_map(|v| match v {
+                StackValue::Value(v) => Some(v),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Call frames from the oldest with values pushed while the frame is active
+    pub fn peek_frame_values(&self) -> Vec<(&CallFrame, Vec<&Value>)> {
+        let mut result: Vec<(&CallFrame, Vec<&Value>)> = vec![];
+        for v in &self.stack {
+            match v {
+                StackValue::Activation(frame) => result.push((frame, vec![])),
+                StackValue::Value(value) => {
+                    if let Some((_, values)) = result.last_mut() {
+                        values.push(value);
+                    }
+                }
+                StackValue::Label(_) => (),
+            }
+        }
+        result
+    }
+
+    pub fn frame_depth(&self) -> usize {
+        self.frame_index.len()
+    }
+}
+
+// Journal for reverse execution
+impl Stack {
+    pub(crate) fn start_journal(&mut self) {
+        self.journal = Some(Vec::new());
+    }
+
+    pub(crate) fn take_journal(&mut self) -> Vec<StackUndo> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    pub(crate) fn undo(&mut self, journal: Vec<StackUndo>) {
+        for undo in journal.into_iter().rev() {
+            match undo {
+                StackUndo::Pop => {
+                    if let Some(StackValue::Activation(_)) = self.stack.pop() {
+                        self.frame_index.pop();
+                    }
+                }
+                StackUndo::Push(value) => {
+                    if let StackValue::Activation(_) = value {
+                        self.frame_index.push(self.stack.len());
+                    }
+                    self.stack.push(value);
+                }
+                StackUndo::SetLocal(position, index, value) => {
+                    if let Some(StackValue::Activation(frame)) = self.stack.get_mut(position) {
+                        frame.set_local(index, value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, value: StackValue) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(StackUndo::Pop);
+        }
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Option<StackValue> {
+        let value = self.stack.pop()?;
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(StackUndo::Push(value.clone()));
+        }
+        Some(value)
+    }
 }
 
 impl Stack {
     pub fn pop_while<F: Fn(&StackValue) -> bool>(&mut self, f: F) -> Vec<StackValue> {
         let mut result = vec![];
         while f(self.latest()) {
-            result.push(self.stack.pop().unwrap());
+            result.push(self.pop().unwrap());
         }
         result
     }
@@ -316,22 +426,22 @@ impl Stack {
         self.stack.last().unwrap()
     }
     pub fn push_value(&mut self, val: Value) {
-        self.stack.push(StackValue::Value(val))
+        self.push(StackValue::Value(val))
     }
 
     pub fn pop_value(&mut self) -> Result<Value> {
-        match self.stack.pop() {
+        match self.pop() {
             Some(val) => val.as_value(),
             None => Err(Error::PopEmptyStack),
         }
     }
 
     pub fn push_label(&mut self, val: Label) {
-        self.stack.push(StackValue::Label(val))
+        self.push(StackValue::Label(val))
     }
 
     pub fn pop_label(&mut self) -> Result<Label> {
-        match self.stack.pop() {
+        match self.pop() {
             Some(val) => val.as_label(),
             None => Err(Error::PopEmptyStack),
         }
@@ -342,7 +452,7 @@ impl Stack {
             return Err(Error::Overflow);
         }
         self.frame_index.push(self.stack.len());
-        self.stack.push(StackValue::Activation(frame));
+        self.push(StackValue::Activation(frame));
         Ok(())
     }
 
@@ -351,7 +461,7 @@ impl Stack {
     }
 
     pub fn pop_frame(&mut self) -> Result<CallFrame> {
-        match self.stack.pop() {
+        match self.pop() {
             Some(val) => {
                 self.frame_index.pop();
                 val.as_activation()
@@ -368,7 +478,11 @@ impl Stack {
         let size = self.current_frame_index()?;
         if let Some(stack) = self.stack.get_mut(size) {
             let frame = stack.as_activation_mut()?;
+            let original = frame.local(index);
             frame.set_local(index, value);
+            if let Some(journal) = self.journal.as_mut() {
+                journal.push(StackUndo::SetLocal(size, index, original));
+            }
             Ok(())
         } else {
             Err(Error::NoCallFrame)
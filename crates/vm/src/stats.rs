@@ -0,0 +1,73 @@
+use super::address::ExecutableFuncAddr;
+use super::inst::{Instruction, InstructionClass};
+use std::collections::{BTreeMap, HashMap};
+
+/// Counters collected while executing instructions
+#[derive(Clone, Default)]
+pub struct ExecutionStats {
+    inst_count: u64,
+    class_counts: BTreeMap<InstructionClass, u64>,
+    call_counts: HashMap<ExecutableFuncAddr, u64>,
+    host_call_counts: HashMap<ExecutableFuncAddr, u64>,
+    memory_grow_count: u64,
+    peak_memory_pages: usize,
+}
+
+impl ExecutionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of executed instructions
+    pub fn inst_count(&self) -> u64 {
+        self.inst_count
+    }
+
+    /// Executed instruction counts per instruction class
+    pub fn class_counts(&self) -> &BTreeMap<InstructionClass, u64> {
+        &self.class_counts
+    }
+
+    /// Call counts per defined function
+    pub fn call_counts(&self) -> &HashMap<ExecutableFuncAddr, u64> {
+        &self.call_counts
+    }
+
+    /// Call counts per host function
+    pub fn host_call_counts(&self) -> &HashMap<ExecutableFuncAddr, u64> {
+        &self.host_call_counts
+    }
+
+    /// The number of executed `memory.grow` instructions
+    pub fn memory_grow_count(&self) -> u64 {
+        self.memory_grow_count
+    }
+
+    /// The largest page count of the memory since the statistics are enabled,
+    /// including growth by `memory.grow` and host functions
+    pub fn peak_memory_pages(&self) -> usize {
+        self.peak_memory_pages
+    }
+
+    pub(crate) fn count_inst(&mut self, inst: &Instruction) {
+        self.inst_count += 1;
+        *self.class_counts.entry(inst.kind.class()).or_insert(0) += 1;
+    }
+
+    pub(crate) fn count_call(&mut self, addr: ExecutableFuncAddr) {
+        *self.call_counts.entry(addr).or_insert(0) += 1;
+    }
+
+    pub(crate) fn count_host_call(&mut self, addr: ExecutableFuncAddr) {
+        *self.host_call_counts.entry(addr).or_insert(0) += 1;
+    }
+
+    pub(crate) fn count_memory_grow(&mut self, page_count: usize) {
+        self.memory_grow_count += 1;
+        self.observe_memory_pages(page_count);
+    }
+
+    pub(crate) fn observe_memory_pages(&mut self, page_count: usize) {
+        self.peak_memory_pages = self.peak_memory_pages.max(page_count);
+    }
+}
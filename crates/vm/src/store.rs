@@ -31,6 +31,14 @@ pub struct Store {
     embedded_contexts: HashMap<std::any::TypeId, Box<dyn std::any::Any>>,
 }
 
+/// Copy of the mutable instances in a store.
+/// Functions and modules are not included because they are never modified after instantiation.
+pub struct StoreSnapshot {
+    tables: Vec<TableInstance>,
+    mems: Vec<MemoryInstance>,
+    globals: Vec<GlobalInstance>,
+}
+
 impl Store {
     pub fn new() -> Self {
         Self {
@@ -52,6 +60,11 @@ impl Store {
         self.funcs.get(addr)
     }
 
+    /// Index of the function in the index space of the module
+    pub fn func_index(&self, module_index: ModuleIndex, addr: ExecutableFuncAddr) -> Option<usize> {
+        self.funcs.index_in_module(module_index, addr)
+    }
+
     pub fn global(&self, addr: GlobalAddr) -> Rc<RefCell<GlobalInstance>> {
         self.globals.get(addr).unwrap().0.clone()
     }
@@ -78,6 +91,10 @@ impl Store {
         self.mems.items(addr).map(|c| c.len()).unwrap_or(0)
     }
 
+    pub fn global_count(&self, addr: ModuleIndex) -> usize {
+        self.globals.items(addr).map(|c| c.len()).unwrap_or(0)
+    }
+
     pub fn module(&self, module_index: ModuleIndex) -> &ModuleInstance {
         &self.modules[module_index.0 as usize]
     }
@@ -95,6 +112,40 @@ impl Store {
     }
 }
 
+// Checkpoint
+impl Store {
+    pub fn snapshot(&self) -> StoreSnapshot {
+        fn copy<T: Clone>(items: &[Rc<RefCell<T>>]) -> Vec<T> {
+            items.iter().map(|item| item.borrow().clone()).collect()
+        }
+        StoreSnapshot {
+            tables: copy(self.tables.all_items()),
+            mems: copy(self.mems.all_items()),
+            globals: copy(self.globals.all_items()),
+        }
+    }
+
+    /// Overwrite instances with the snapshot. Returns false if the snapshot was taken from a store
+    /// which has different instances.
+    pub fn restore(&self, snapshot: &StoreSnapshot) -> bool {
+        fn restore<T: Clone>(items: &[Rc<RefCell<T>>], snapshot: &[T]) {
+            for (item, value) in items.iter().zip(snapshot.iter()) {
+                *item.borrow_mut() = value.clone();
+            }
+        }
+        if self.tables.all_items().len() != snapshot.tables.len()
+            || self.mems.all_items().len() != snapshot.mems.len()
+            || self.globals.all_items().len() != snapshot.globals.len()
+        {
+            return false;
+        }
+        restore(self.tables.all_items(), &snapshot.tables);
+        restore(self.mems.all_items(), &snapshot.mems);
+        restore(self.globals.all_items(), &snapshot.globals);
+        true
+    }
+}
+
 impl Store {
     pub fn load_host_module(&mut self, name: String, module: HashMap<String, HostValue>) {
         let module_index = ModuleIndex(self.modules.len() as u32);
@@ -31,6 +31,7 @@ impl std::fmt::Display for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
+#[derive(Clone)]
 pub struct TableInstance {
     buffer: Vec<Option<FuncAddr>>,
     pub max: Option<usize>,
@@ -1,5 +1,6 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs::File;
 use wasi_common::hostcalls::*;
 use wasi_common::{WasiCtx, WasiCtxBuilder};
 use wasminspect_vm::*;
@@ -9,9 +10,31 @@ pub struct WasiContext {
     ctx: RefCell<WasiCtx>,
 }
 
+/// Files used as the standard streams of the guest instead of the inherited ones
+#[derive(Default)]
+pub struct WasiStdio {
+    pub stdin: Option<File>,
+    pub stdout: Option<File>,
+    pub stderr: Option<File>,
+}
+
+pub fn instantiate_wasi_context(stdio: WasiStdio) -> WasiContext {
+    let mut builder = WasiCtxBuilder::new().inherit_stdio();
+    if let Some(stdin) = stdio.stdin {
+        builder = builder.stdin(stdin);
+    }
+    if let Some(stdout) = stdio.stdout {
+        builder = builder.stdout(stdout);
+    }
+    if let Some(stderr) = stdio.stderr {
+        builder = builder.stderr(stderr);
+    }
+    WasiContext {
+        ctx: RefCell::new(builder.build().unwrap()),
+    }
+}
+
 pub fn instantiate_wasi() -> (WasiContext, HashMap<String, HostValue>) {
-    let builder = WasiCtxBuilder::new().inherit_stdio();
-    let wasi_ctx = builder.build().unwrap();
     let mut module: HashMap<String, HostValue> = HashMap::new();
 
     fn define_wasi_fn<
@@ -834,8 +857,6 @@ pub fn instantiate_wasi() -> (WasiContext, HashMap<String, HostValue>) {
         },
     );
     module.insert("path_symlink".to_string(), func);
-    let context = WasiContext {
-        ctx: RefCell::new(wasi_ctx),
-    };
+    let context = instantiate_wasi_context(WasiStdio::default());
     (context, module)
 }
@@ -10,13 +10,80 @@ struct Opts {
     /// Tells the debugger to read in and execute the debugger commands in given file, after wasm file has been loaded
     #[structopt(short, long)]
     source: Option<String>,
+    /// The core dump file of the wasm binary to inspect
+    #[structopt(long)]
+    core: Option<String>,
+    /// The wasm file which has DWARF of the stripped wasm binary
+    #[structopt(long)]
+    symbols: Option<String>,
+    /// Record up to LIMIT executed instructions to be able to step back
+    #[structopt(long, value_name = "LIMIT")]
+    history: Option<usize>,
+    /// Write a core dump to the file when the process traps. An existing file is not overwritten
+    #[structopt(long, value_name = "PATH")]
+    core_on_trap: Option<String>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Run the wasm binary to completion and count executed instructions
+    #[structopt(name = "profile")]
+    Profile {
+        /// The wasm binary file
+        #[structopt(name = "FILE")]
+        filepath: String,
+        /// The function to start execution from
+        #[structopt(long)]
+        entry: Option<String>,
+        /// Write folded stacks for flamegraph.pl or inferno to the file
+        #[structopt(short, long, default_value = "wasminspect.folded")]
+        output: String,
+        /// The number of functions and lines shown in the summary
+        #[structopt(long, default_value = "20")]
+        top: usize,
+    },
+    /// Run the wasm binary to completion and write line coverage
+    #[structopt(name = "coverage")]
+    Coverage {
+        /// The wasm binary file
+        #[structopt(name = "FILE")]
+        filepath: String,
+        /// The function to start execution from
+        #[structopt(long)]
+        entry: Option<String>,
+        /// Write the coverage in the lcov format to the file
+        #[structopt(short, long, default_value = "wasminspect.info")]
+        output: String,
+    },
 }
 
 fn main() {
     pretty_env_logger::init();
     let opts = Opts::from_args();
-    match wasminspect_cli::run_loop(opts.filepath, opts.source) {
-        Err(err) => println!("{:?}", err),
-        _ => {}
+    let result = match opts.command {
+        Some(Command::Profile {
+            filepath,
+            entry,
+            output,
+            top,
+        }) => wasminspect_cli::run_profile(filepath, entry, output, top),
+        Some(Command::Coverage {
+            filepath,
+            entry,
+            output,
+        }) => wasminspect_cli::run_coverage(filepath, entry, output),
+        None => wasminspect_cli::run_loop(
+            opts.filepath,
+            opts.source,
+            opts.core,
+            opts.symbols,
+            opts.history,
+            opts.core_on_trap,
+        ),
+    };
+    if let Err(err) = result {
+        println!("{:?}", err);
     }
 }
@@ -16,4 +16,7 @@
     (call $add (get_local 0) (get_local 1)))
   (func $print_added (export "print_added") (param i32) (param i32)
     (call $print_i32 (call $add (get_local 0) (get_local 1))))
+  (func $spin (export "spin")
+    (loop $loop
+      (br $loop)))
 )
@@ -3,19 +3,33 @@ use wasminspect_vm::*;
 use wast_spec::instantiate_spectest;
 
 use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 
-fn run_wasm(filename: &str, func: &str, args: Vec<WasmValue>, results: Vec<WasmValue>) {
+/// Compile a fixture written in the text format, so that the tests don't depend on wabt
+fn load_wat(instance: &mut WasmInstance, filename: &str) -> ModuleIndex {
     let example_dir = Path::new(file!()).parent().unwrap().join("simple-example");
+    let wat = std::fs::read_to_string(example_dir.join(filename)).unwrap();
+    let buf = wast::parser::ParseBuffer::new(&wat).unwrap();
+    let mut wat = wast::parser::parse::<wast::Wat>(&buf).unwrap();
+    let bytes = wat.module.encode().unwrap();
+    let reader = wasmparser::ModuleReader::new(&bytes).unwrap();
+    instance
+        .load_module_from_parity_module(None, reader)
+        .unwrap()
+}
+
+fn instantiate_calc() -> (WasmInstance, ModuleIndex) {
     let mut instance = WasmInstance::new();
     let spectest = instantiate_spectest();
     instance.load_host_module("spectest".to_string(), spectest);
-    let module_index = instance
-        .load_module_from_file(
-            None,
-            example_dir.join(filename).to_str().unwrap().to_string(),
-        )
-        .ok()
-        .unwrap();
+    let module_index = load_wat(&mut instance, "calc.wat");
+    (instance, module_index)
+}
+
+fn run_calc(func: &str, args: Vec<WasmValue>, results: Vec<WasmValue>) {
+    let (mut instance, module_index) = instantiate_calc();
     match instance.run(module_index, Some(func.to_string()), args) {
         Ok(result) => assert_eq!(result, results),
         Err(err) => panic!("{}", err),
@@ -24,8 +38,7 @@ fn run_wasm(filename: &str, func: &str, args: Vec<WasmValue>, results: Vec<WasmV
 
 #[test]
 fn test_calc_add() {
-    run_wasm(
-        "calc.wasm",
+    run_calc(
         "add",
         vec![WasmValue::I32(1), WasmValue::I32(2)],
         vec![WasmValue::I32(3)],
@@ -34,8 +47,7 @@ fn test_calc_add() {
 
 #[test]
 fn test_calc_mul() {
-    run_wasm(
-        "calc.wasm",
+    run_calc(
         "mul",
         vec![WasmValue::I32(2), WasmValue::I32(3)],
         vec![WasmValue::I32(6)],
@@ -44,10 +56,47 @@ fn test_calc_mul() {
 
 #[test]
 fn test_calc_call() {
-    run_wasm(
-        "calc.wasm",
+    run_calc(
         "call_add",
         vec![WasmValue::I32(3), WasmValue::I32(4)],
         vec![WasmValue::I32(7)],
     );
 }
+
+#[test]
+fn test_calc_spin_out_of_fuel() {
+    let (mut instance, module_index) = instantiate_calc();
+    match instance.run_with_fuel(module_index, Some("spin".to_string()), vec![], 1000) {
+        Err(WasmError::ExecutionError(Trap::OutOfFuel)) => (),
+        Ok(_) => panic!("spin should not finish"),
+        Err(err) => panic!("{}", err),
+    }
+}
+
+#[test]
+fn test_calc_spin_interrupted() {
+    let (mut instance, module_index) = instantiate_calc();
+    let handle = instance.interrupt_handle();
+    let (stopped, wait_stopped) = mpsc::channel();
+    // Keep requesting until `run` returns because a request made before it starts is discarded
+    let interrupter = thread::spawn(move || loop {
+        handle.interrupt();
+        match wait_stopped.recv_timeout(Duration::from_millis(10)) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            _ => return,
+        }
+    });
+    let result = instance.run(module_index, Some("spin".to_string()), vec![]);
+    stopped.send(()).unwrap();
+    let (joined, wait_joined) = mpsc::channel();
+    thread::spawn(move || joined.send(interrupter.join()));
+    wait_joined
+        .recv_timeout(Duration::from_secs(10))
+        .expect("interrupter thread should finish")
+        .unwrap();
+    match result {
+        Err(WasmError::ExecutionError(Trap::Interrupted)) => (),
+        Ok(_) => panic!("spin should not finish"),
+        Err(err) => panic!("{}", err),
+    }
+}